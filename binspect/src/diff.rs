@@ -0,0 +1,134 @@
+//! Side by side comparison of two files, showing only the lines where they
+//! differ, followed by a summary of the differing ranges.
+
+use std::io::{self, Read, Write};
+
+/// Bytes of each file shown on each line.
+const WIDTH: usize = 8;
+
+/// Highlight of differing bytes, when colored.
+const HIGHLIGHT: &str = "\x1b[1;31m";
+
+/// Restores the default terminal attributes.
+const RESET: &str = "\x1b[0m";
+
+/// Compare everything read from `a` and `b`, numbering bytes from `start`, and
+/// write the differing lines and ranges to `output`, highlighting differing
+/// bytes if `colored`.
+///
+/// Returns whether any differences were found.
+pub fn diff(
+    a: &mut impl Read,
+    b: &mut impl Read,
+    output: &mut impl Write,
+    start: u64,
+    colored: bool,
+) -> io::Result<bool> {
+    let mut row_a = [0u8; WIDTH];
+    let mut row_b = [0u8; WIDTH];
+    let mut line = String::new();
+    // differing ranges, with inclusive ends
+    let mut ranges: Vec<(u64, u64)> = Vec::new();
+    let mut offset = start;
+    loop {
+        let n_a = crate::dump::fill(a, &mut row_a)?;
+        let n_b = crate::dump::fill(b, &mut row_b)?;
+        if n_a == 0 && n_b == 0 {
+            break;
+        }
+        let (row_a, row_b) = (&row_a[..n_a], &row_b[..n_b]);
+        if row_a != row_b {
+            for i in 0..n_a.max(n_b) {
+                if row_a.get(i) != row_b.get(i) {
+                    let at = offset + i as u64;
+                    match ranges.last_mut() {
+                        Some((_, end)) if *end + 1 == at => *end = at,
+                        _ => ranges.push((at, at)),
+                    }
+                }
+            }
+            line.clear();
+            line.push_str(&format!("{:08x}  ", offset));
+            format_side(&mut line, row_a, row_b, colored);
+            line.push_str("  ");
+            format_side(&mut line, row_b, row_a, colored);
+            line.push('\n');
+            output.write_all(line.as_bytes())?;
+        }
+        offset += n_a.max(n_b) as u64;
+    }
+
+    if !ranges.is_empty() {
+        let total: u64 = ranges.iter().map(|(start, end)| end - start + 1).sum();
+        writeln!(
+            output,
+            "\n{} differing range{}, {} byte{}:",
+            ranges.len(),
+            if ranges.len() == 1 { "" } else { "s" },
+            total,
+            if total == 1 { "" } else { "s" }
+        )?;
+        for (start, end) in ranges.iter() {
+            writeln!(output, "{:08x}-{:08x}  {}", start, end, end - start + 1)?;
+        }
+    }
+    output.flush()?;
+    Ok(!ranges.is_empty())
+}
+
+/// Append the hex and ASCII panel of `bytes` to `line`, highlighting those that
+/// differ from `other` if `colored`.
+fn format_side(line: &mut String, bytes: &[u8], other: &[u8], colored: bool) {
+    let highlight = |i: usize| colored && bytes.get(i) != other.get(i);
+    for i in 0..WIDTH {
+        match bytes.get(i) {
+            Some(byte) if highlight(i) => {
+                line.push_str(&format!("{}{:02x}{} ", HIGHLIGHT, byte, RESET))
+            }
+            Some(byte) => line.push_str(&format!("{:02x} ", byte)),
+            None => line.push_str("   "),
+        }
+    }
+    line.push('|');
+    for i in 0..WIDTH {
+        match bytes.get(i) {
+            Some(&byte) if highlight(i) => {
+                line.push_str(HIGHLIGHT);
+                line.push(crate::dump::printable(byte));
+                line.push_str(RESET);
+            }
+            Some(&byte) => line.push(crate::dump::printable(byte)),
+            None => line.push(' '),
+        }
+    }
+    line.push('|');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Verify that only differing lines are shown, and that adjacent differing
+    /// bytes are summarized as one range, including bytes past the end of the
+    /// shorter file.
+    fn test_diff() {
+        let a = b"same 8b.0123456789abcdefXY";
+        let b = b"same 8b.0123456789abcdeF";
+        let mut output = Vec::new();
+        let differs = diff(&mut &a[..], &mut &b[..], &mut output, 0, false).unwrap();
+        assert!(differs);
+        let exp = concat!(
+            "00000010  38 39 61 62 63 64 65 66 |89abcdef|  38 39 61 62 63 64 65 46 |89abcdeF|\n",
+            "00000018  58 59                   |XY      |                          |        |\n",
+            "\n",
+            "1 differing range, 3 bytes:\n",
+            "00000017-00000019  3\n",
+        );
+        assert_eq!(String::from_utf8(output).unwrap(), exp);
+
+        let mut output = Vec::new();
+        assert!(!diff(&mut &a[..], &mut &a[..], &mut output, 0, false).unwrap());
+        assert!(output.is_empty());
+    }
+}
@@ -0,0 +1,257 @@
+//! Canonical hex dump, as by `hexdump -C`: an offset column, the bytes in hex
+//! split into groups, and a panel of the printable ASCII characters.
+
+use std::io::{self, Read, Write};
+
+/// Arrangement of the bytes of each line.
+#[derive(Debug, Clone, Copy)]
+pub struct Layout {
+    /// Bytes shown on each line
+    pub width: usize,
+    /// Bytes in each group of hex digits, separated by an extra space
+    pub group: usize,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self {
+            width: 16,
+            group: 8,
+        }
+    }
+}
+
+/// Restores the default terminal attributes.
+const RESET: &str = "\x1b[0m";
+
+/// Dump everything read from `input` to `output`, numbering bytes from `start`
+/// and arranging them by `layout`.
+///
+/// Each of `marks`, sorted by offset, is labelled on a line of its own, before
+/// the line holding the byte at its offset. Bytes are colored by class if
+/// `colored`.
+pub fn hex(
+    input: &mut impl Read,
+    output: &mut impl Write,
+    start: u64,
+    layout: Layout,
+    marks: &[(u64, String)],
+    colored: bool,
+) -> io::Result<()> {
+    let mut bytes = vec![0u8; layout.width];
+    let mut line = String::new();
+    let mut offset = start;
+    let mut marks = marks.iter().skip_while(|(at, _)| *at < start).peekable();
+    loop {
+        let n = fill(input, &mut bytes)?;
+        if n == 0 {
+            break;
+        }
+        while let Some((at, label)) = marks.next_if(|(at, _)| *at < offset + n as u64) {
+            writeln!(output, "-- {:08x} {}", at, label)?;
+        }
+        format_line(&mut line, offset, &bytes[..n], layout, colored);
+        output.write_all(line.as_bytes())?;
+        offset += n as u64;
+    }
+    if offset != start {
+        writeln!(output, "{:08x}", offset)?;
+    }
+    output.flush()
+}
+
+/// Read into `buf` until it is full or the input ends, returning the number of
+/// bytes read.
+pub fn fill(input: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match input.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+/// Replace `line` with the dump of `bytes`, found at `offset`, colored by class
+/// if `colored`.
+fn format_line(line: &mut String, offset: u64, bytes: &[u8], layout: Layout, colored: bool) {
+    use std::fmt::Write;
+
+    line.clear();
+    let _ = write!(line, "{:08x} ", offset);
+    let mut current = None;
+    for i in 0..layout.width {
+        if i % layout.group == 0 {
+            line.push(' ');
+        }
+        match bytes.get(i) {
+            Some(&byte) => {
+                if colored {
+                    set_color(line, &mut current, color(byte));
+                }
+                let _ = write!(line, "{:02x} ", byte);
+            }
+            None => {
+                if current.take().is_some() {
+                    line.push_str(RESET);
+                }
+                line.push_str("   ");
+            }
+        }
+    }
+    if current.take().is_some() {
+        line.push_str(RESET);
+    }
+    line.push_str(" |");
+    for &byte in bytes {
+        if colored {
+            set_color(line, &mut current, color(byte));
+        }
+        line.push(printable(byte));
+    }
+    if current.is_some() {
+        line.push_str(RESET);
+    }
+    line.push_str("|\n");
+}
+
+/// Switch `line` to `color`, unless already `current`.
+fn set_color(line: &mut String, current: &mut Option<&'static str>, color: &'static str) {
+    if *current != Some(color) {
+        line.push_str(color);
+        *current = Some(color);
+    }
+}
+
+/// Terminal color of `byte`, by class, as by `hexyl`: gray for NUL, cyan for
+/// printable ASCII, green for whitespace, magenta for other control
+/// characters, and yellow for bytes with the high bit set.
+pub fn color(byte: u8) -> &'static str {
+    match byte {
+        0 => "\x1b[90m",
+        b' ' | b'\t' | b'\n' | b'\r' | 0x0b | 0x0c => "\x1b[32m",
+        byte if byte.is_ascii_graphic() => "\x1b[36m",
+        byte if byte.is_ascii() => "\x1b[35m",
+        _ => "\x1b[33m",
+    }
+}
+
+/// The character shown for `byte` in the ASCII panel.
+pub fn printable(byte: u8) -> char {
+    if byte.is_ascii_graphic() || byte == b' ' {
+        byte as char
+    } else {
+        '.'
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dump(input: &[u8], start: u64) -> String {
+        let mut output = Vec::new();
+        hex(
+            &mut &input[..],
+            &mut output,
+            start,
+            Layout::default(),
+            &[],
+            false,
+        )
+        .unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    /// Verify the layout of full and partial lines, and the final offset.
+    fn test_hex() {
+        let exp = concat!(
+            "00000000  30 31 32 33 34 35 36 37  38 39 61 62 63 64 65 66  |0123456789abcdef|\n",
+            "00000010  68 65 6c 6c 6f 00 0a ff  7f                       |hello....|\n",
+            "00000019\n",
+        );
+        assert_eq!(dump(b"0123456789abcdefhello\0\n\xff\x7f", 0), exp);
+    }
+
+    #[test]
+    /// Verify that offsets count from the start given, and that nothing is
+    /// dumped for empty input.
+    fn test_hex_start() {
+        let exp = concat!(
+            "00001000  41 42 20 43                                       |AB C|\n",
+            "00001004\n",
+        );
+        assert_eq!(dump(b"AB C", 0x1000), exp);
+        assert_eq!(dump(b"", 0), "");
+    }
+
+    #[test]
+    /// Verify lines of other widths and groupings, including partial lines.
+    fn test_hex_layout() {
+        let dump = |width, group| {
+            let mut output = Vec::new();
+            let layout = Layout { width, group };
+            hex(&mut &b"0123456789"[..], &mut output, 0, layout, &[], false).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+        let exp = concat!(
+            "00000000  30 31 32 33  34 35 36 37  |01234567|\n",
+            "00000008  38 39                     |89|\n",
+            "0000000a\n",
+        );
+        assert_eq!(dump(8, 4), exp);
+        // the remaining 14 bytes of the line are blank, with no extra space
+        let exp = format!(
+            "00000000  30 31 32 33 34 35 36 37 38 39 {}|0123456789|\n0000000a\n",
+            " ".repeat(14 * 3 + 1)
+        );
+        assert_eq!(dump(24, 32), exp);
+    }
+
+    #[test]
+    /// Verify that marks are labelled before the line holding their offset,
+    /// except those before the start.
+    fn test_hex_marks() {
+        let marks = [
+            (0x0, "header".to_string()),
+            (0x14, "text".to_string()),
+            (0x1f, "data".to_string()),
+        ];
+        let mut output = Vec::new();
+        hex(
+            &mut &[b'.'; 0x20][..],
+            &mut output,
+            4,
+            Layout::default(),
+            &marks,
+            false,
+        )
+        .unwrap();
+        let exp = concat!(
+            "00000004  2e 2e 2e 2e 2e 2e 2e 2e  2e 2e 2e 2e 2e 2e 2e 2e  |................|\n",
+            "-- 00000014 text\n",
+            "-- 0000001f data\n",
+            "00000014  2e 2e 2e 2e 2e 2e 2e 2e  2e 2e 2e 2e 2e 2e 2e 2e  |................|\n",
+            "00000024\n",
+        );
+        assert_eq!(String::from_utf8(output).unwrap(), exp);
+    }
+
+    #[test]
+    /// Verify that colors change only between bytes of different classes, and
+    /// are reset after the hex digits and the ASCII panel.
+    fn test_hex_colored() {
+        let mut line = String::new();
+        format_line(&mut line, 0, b"ab\0\xff", Layout::default(), true);
+        let exp = concat!(
+            "00000000  \x1b[36m61 62 \x1b[90m00 \x1b[33mff \x1b[0m",
+            "                                     ",
+            " |\x1b[36mab\x1b[90m.\x1b[33m.\x1b[0m|\n",
+        );
+        assert_eq!(line, exp);
+    }
+}
@@ -0,0 +1,211 @@
+//! Bytes written as source code, as by `xxd -i`, so that they can be embedded
+//! in a program.
+
+use std::io::{self, Read, Write};
+
+/// Language of the array written.
+#[derive(Debug, Clone, Copy)]
+pub enum Syntax {
+    C,
+    Rust,
+    Python,
+}
+
+/// Characters of the base64 alphabet, by value.
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Bytes encoded on each line of base64, making lines of 76 characters as for
+/// MIME.
+const BASE64_LINE: usize = 57;
+
+/// Identifier derived from the name of the file at `path`, as by `xxd -i`:
+/// characters other than ASCII letters and digits become underscores, and a
+/// leading digit is preceded by one.
+pub fn identifier(path: Option<&str>) -> String {
+    let Some(name) = path
+        .filter(|&path| path != "-")
+        .and_then(|path| std::path::Path::new(path).file_name())
+    else {
+        return "data".to_string();
+    };
+    let mut identifier: String = name
+        .to_string_lossy()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if identifier.starts_with(|c: char| c.is_ascii_digit()) {
+        identifier.insert(0, '_');
+    }
+    identifier
+}
+
+/// Write everything read from `input` as an array named `name`, followed by a
+/// constant holding its length.
+pub fn array(
+    input: &mut impl Read,
+    output: &mut impl Write,
+    syntax: Syntax,
+    name: &str,
+) -> io::Result<()> {
+    let (per_line, name) = match syntax {
+        Syntax::C => {
+            writeln!(output, "unsigned char {}[] = {{", name)?;
+            (12, name.to_string())
+        }
+        Syntax::Rust => {
+            let name = name.to_ascii_uppercase();
+            writeln!(output, "pub static {}: &[u8] = &[", name)?;
+            (12, name)
+        }
+        Syntax::Python => {
+            writeln!(output, "{} = (", name)?;
+            (16, name.to_string())
+        }
+    };
+
+    let mut bytes = vec![0u8; per_line];
+    let mut length: u64 = 0;
+    let mut line = String::new();
+    loop {
+        let n = crate::dump::fill(input, &mut bytes)?;
+        if n == 0 {
+            break;
+        }
+        line.clear();
+        match syntax {
+            Syntax::C | Syntax::Rust => {
+                line.push_str("   ");
+                for byte in &bytes[..n] {
+                    line.push_str(&format!(" 0x{:02x},", byte));
+                }
+            }
+            Syntax::Python => {
+                line.push_str("    b\"");
+                for byte in &bytes[..n] {
+                    line.push_str(&format!("\\x{:02x}", byte));
+                }
+                line.push('"');
+            }
+        }
+        writeln!(output, "{}", line)?;
+        length += n as u64;
+    }
+
+    match syntax {
+        Syntax::C => {
+            writeln!(output, "}};")?;
+            writeln!(output, "unsigned int {}_len = {};", name, length)?;
+        }
+        Syntax::Rust => {
+            writeln!(output, "];")?;
+            writeln!(output, "pub const {}_LEN: usize = {};", name, length)?;
+        }
+        Syntax::Python => {
+            if length == 0 {
+                writeln!(output, "    b\"\"")?;
+            }
+            writeln!(output, ")")?;
+            writeln!(output, "{}_len = {}", name, length)?;
+        }
+    }
+    output.flush()
+}
+
+/// Write everything read from `input` in base64, with padding, in lines of 76
+/// characters.
+pub fn base64(input: &mut impl Read, output: &mut impl Write) -> io::Result<()> {
+    let mut bytes = [0u8; BASE64_LINE];
+    let mut line = String::new();
+    loop {
+        let n = crate::dump::fill(input, &mut bytes)?;
+        if n == 0 {
+            break;
+        }
+        line.clear();
+        for group in bytes[..n].chunks(3) {
+            let value = group.iter().enumerate().fold(0u32, |value, (i, &byte)| {
+                value | (byte as u32) << (16 - 8 * i)
+            });
+            for i in 0..4 {
+                if i <= group.len() {
+                    line.push(BASE64[(value >> (18 - 6 * i) & 0x3f) as usize] as char);
+                } else {
+                    line.push('=');
+                }
+            }
+        }
+        writeln!(output, "{}", line)?;
+    }
+    output.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn array(input: &[u8], syntax: Syntax) -> String {
+        let mut output = Vec::new();
+        super::array(&mut &input[..], &mut output, syntax, "hello_bin").unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    /// Verify arrays in each language, with their length constants.
+    fn test_array() {
+        let input = b"0123456789abcdef";
+        let exp = concat!(
+            "unsigned char hello_bin[] = {\n",
+            "    0x30, 0x31, 0x32, 0x33, 0x34, 0x35, 0x36, 0x37, 0x38, 0x39, 0x61, 0x62,\n",
+            "    0x63, 0x64, 0x65, 0x66,\n",
+            "};\n",
+            "unsigned int hello_bin_len = 16;\n",
+        );
+        assert_eq!(array(input, Syntax::C), exp);
+
+        let exp = concat!(
+            "pub static HELLO_BIN: &[u8] = &[\n",
+            "    0x30, 0x31, 0x32, 0x33, 0x34, 0x35, 0x36, 0x37, 0x38, 0x39, 0x61, 0x62,\n",
+            "    0x63, 0x64, 0x65, 0x66,\n",
+            "];\n",
+            "pub const HELLO_BIN_LEN: usize = 16;\n",
+        );
+        assert_eq!(array(input, Syntax::Rust), exp);
+
+        let exp = concat!(
+            "hello_bin = (\n",
+            "    b\"\\x41\\x00\"\n",
+            ")\n",
+            "hello_bin_len = 2\n",
+        );
+        assert_eq!(array(b"A\0", Syntax::Python), exp);
+        assert_eq!(
+            array(b"", Syntax::Python),
+            "hello_bin = (\n    b\"\"\n)\nhello_bin_len = 0\n"
+        );
+    }
+
+    #[test]
+    /// Verify identifiers derived from file names.
+    fn test_identifier() {
+        assert_eq!(identifier(Some("fw/boot-1.2.bin")), "boot_1_2_bin");
+        assert_eq!(identifier(Some("7z.dat")), "_7z_dat");
+        assert_eq!(identifier(Some("-")), "data");
+        assert_eq!(identifier(None), "data");
+    }
+
+    #[test]
+    /// Verify base64 padding, and the wrapping of long input.
+    fn test_base64() {
+        let base64 = |input: &[u8]| {
+            let mut output = Vec::new();
+            super::base64(&mut &input[..], &mut output).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==\n");
+        assert_eq!(base64(b"fo"), "Zm8=\n");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy\n");
+        let wrapped = base64(&[0xff; 60]);
+        assert_eq!(wrapped, format!("{}\n////\n", "/".repeat(76)));
+    }
+}
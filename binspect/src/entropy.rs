@@ -0,0 +1,147 @@
+//! Shannon entropy of each block of the file, drawn as a bar, so that
+//! compressed or encrypted regions (near 8 bits per byte) stand out from code,
+//! text, and padding.
+
+use std::io::{self, Read, Write};
+
+/// Columns of the bar drawn for the maximum entropy of 8 bits per byte.
+const BAR_WIDTH: usize = 32;
+
+/// Characters filling eighths of a column, from one eighth to a full column.
+const EIGHTHS: [char; 8] = ['▏', '▎', '▍', '▌', '▋', '▊', '▉', '█'];
+
+/// Size of the chunks in which each block is read, however large the block.
+const CHUNK: usize = 64 * 1024;
+
+/// Add the occurrences of each byte value in `bytes` to `counts`.
+fn count(counts: &mut [u64; 256], bytes: &[u8]) {
+    for &byte in bytes {
+        counts[byte as usize] += 1;
+    }
+}
+
+/// Entropy, in bits per byte from 0 to 8, of `total` bytes whose values occur
+/// as often as `counts` gives.
+fn entropy_of(counts: &[u64; 256], total: u64) -> f64 {
+    let total = total as f64;
+    counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / total;
+            p * (1.0 / p).log2()
+        })
+        .sum()
+}
+
+/// Bar of length proportional to `bits`, out of 8.
+fn bar(bits: f64) -> String {
+    let eighths = (bits / 8.0 * (BAR_WIDTH * 8) as f64).round() as usize;
+    let mut bar: String = std::iter::repeat_n(EIGHTHS[7], eighths / 8).collect();
+    if !eighths.is_multiple_of(8) {
+        bar.push(EIGHTHS[eighths % 8 - 1]);
+    }
+    bar
+}
+
+/// Terminal color of the bar for `bits`: blue for padding and sparse data, green
+/// for code and text, yellow for dense data, and red for compressed or
+/// encrypted data.
+fn color(bits: f64) -> &'static str {
+    match bits {
+        bits if bits < 3.0 => "\x1b[34m",
+        bits if bits < 6.0 => "\x1b[32m",
+        bits if bits < 7.5 => "\x1b[33m",
+        _ => "\x1b[31m",
+    }
+}
+
+/// Write the offset, entropy, and bar of each block of `block` bytes read from
+/// `input`, numbering bytes from `start`, coloring bars if `colored`.
+///
+/// Blocks are counted as they are read, so may be of any size.
+pub fn report(
+    input: &mut impl Read,
+    output: &mut impl Write,
+    block: u64,
+    start: u64,
+    colored: bool,
+) -> io::Result<()> {
+    let mut chunk = vec![0u8; CHUNK];
+    let mut offset = start;
+    loop {
+        let mut counts = [0u64; 256];
+        let mut n = 0;
+        while n < block {
+            let want = usize::try_from(block - n).map_or(CHUNK, |rest| rest.min(CHUNK));
+            let got = crate::dump::fill(input, &mut chunk[..want])?;
+            count(&mut counts, &chunk[..got]);
+            n += got as u64;
+            if got < want {
+                break;
+            }
+        }
+        if n == 0 {
+            break;
+        }
+        let bits = entropy_of(&counts, n);
+        if colored {
+            writeln!(
+                output,
+                "{:08x}  {:.2}  {}{}\x1b[0m",
+                offset,
+                bits,
+                color(bits),
+                bar(bits)
+            )?;
+        } else {
+            writeln!(output, "{:08x}  {:.2}  {}", offset, bits, bar(bits))?;
+        }
+        offset += n;
+    }
+    output.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entropy(bytes: &[u8]) -> f64 {
+        let mut counts = [0u64; 256];
+        count(&mut counts, bytes);
+        entropy_of(&counts, bytes.len() as u64)
+    }
+
+    #[test]
+    /// Verify the entropy of uniform and skewed bytes, and the bars drawn for it.
+    fn test_entropy() {
+        let every: Vec<u8> = (0..=255).collect();
+        assert_eq!(entropy(&[0; 64]), 0.0);
+        assert_eq!(entropy(b"abababab"), 1.0);
+        assert_eq!(entropy(&every), 8.0);
+
+        assert_eq!(bar(0.0), "");
+        assert_eq!(bar(8.0).chars().count(), BAR_WIDTH);
+        assert_eq!(bar(4.0 + 1.0 / 64.0), "████████████████▏");
+    }
+
+    #[test]
+    /// Verify that blocks larger than a chunk, or than the whole input, are
+    /// counted as they are read.
+    fn test_report() {
+        let input: Vec<u8> = (0..=255).cycle().take(3 * CHUNK / 2).collect();
+        let mut output = Vec::new();
+        report(&mut &input[..], &mut output, CHUNK as u64 + 256, 0, false).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("00000000  8.00  "));
+        assert!(lines[1].starts_with(&format!("{:08x}  8.00  ", CHUNK + 256)));
+
+        let mut output = Vec::new();
+        report(&mut &input[..], &mut output, 1 << 40, 0x10, false).unwrap();
+        assert!(String::from_utf8(output)
+            .unwrap()
+            .starts_with("00000010  8.00  "));
+    }
+}
@@ -0,0 +1,576 @@
+//! Summaries of the headers of ELF, PE, and Mach-O executables: their
+//! architecture, entry point, segments, and sections, as a quick `readelf` or
+//! `otool` in the hex viewer.
+
+use std::io::{self, Write};
+
+/// A range of the file or of memory named by the headers, such as a section.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Region {
+    pub name: String,
+    /// Offset of the region within the file
+    pub offset: u64,
+    /// Bytes of the region within the file
+    pub size: u64,
+    /// Address of the region in memory, if loaded
+    pub address: Option<u64>,
+    /// Permissions, such as `r-x`
+    pub flags: String,
+    /// Whether the dump should mark where the region begins
+    pub mark: bool,
+}
+
+#[derive(Debug)]
+pub struct Summary {
+    pub format: String,
+    pub kind: String,
+    pub architecture: String,
+    pub entry: Option<String>,
+    /// Tables of regions, such as segments and sections, by title
+    pub tables: Vec<(&'static str, Vec<Region>)>,
+}
+
+impl Summary {
+    pub fn print(&self, output: &mut impl Write) -> io::Result<()> {
+        writeln!(output, "format        {}", self.format)?;
+        writeln!(output, "type          {}", self.kind)?;
+        writeln!(output, "architecture  {}", self.architecture)?;
+        if let Some(entry) = &self.entry {
+            writeln!(output, "entry point   {}", entry)?;
+        }
+        for (title, regions) in self.tables.iter() {
+            writeln!(output, "\n{} ({})", title, regions.len())?;
+            let width = regions.iter().map(|r| r.name.len()).max().unwrap_or(0);
+            writeln!(
+                output,
+                "  {:width$}  {:8}  {:>8}  {:3}  address",
+                "name",
+                "offset",
+                "size",
+                "rwx",
+                width = width
+            )?;
+            for region in regions.iter() {
+                let address = region
+                    .address
+                    .map_or(String::new(), |address| format!("{:#x}", address));
+                writeln!(
+                    output,
+                    "  {:width$}  {:08x}  {:8}  {:3}  {}",
+                    region.name,
+                    region.offset,
+                    region.size,
+                    region.flags,
+                    address,
+                    width = width
+                )?;
+            }
+        }
+        output.flush()
+    }
+
+    /// Where each marked region begins, by offset.
+    pub fn marks(&self) -> Vec<(u64, String)> {
+        let mut marks: Vec<(u64, String)> = self
+            .tables
+            .iter()
+            .flat_map(|(_, regions)| regions.iter())
+            .filter(|region| region.mark && region.size > 0)
+            .map(|region| {
+                let label = format!("{}, {} bytes", region.name, region.size);
+                (region.offset, label)
+            })
+            .collect();
+        marks.sort();
+        marks
+    }
+}
+
+/// Bounds checked reads of integers in either byte order.
+struct Reader<'a> {
+    data: &'a [u8],
+    big_endian: bool,
+}
+
+impl Reader<'_> {
+    fn bytes(&self, offset: u64, len: usize) -> Result<&[u8], String> {
+        usize::try_from(offset)
+            .ok()
+            .and_then(|offset| self.data.get(offset..offset.checked_add(len)?))
+            .ok_or_else(|| format!("headers extend past the end of the file, at {:#x}", offset))
+    }
+
+    fn u16(&self, offset: u64) -> Result<u16, String> {
+        let bytes = self.bytes(offset, 2)?.try_into().unwrap();
+        Ok(match self.big_endian {
+            true => u16::from_be_bytes(bytes),
+            false => u16::from_le_bytes(bytes),
+        })
+    }
+
+    fn u32(&self, offset: u64) -> Result<u32, String> {
+        let bytes = self.bytes(offset, 4)?.try_into().unwrap();
+        Ok(match self.big_endian {
+            true => u32::from_be_bytes(bytes),
+            false => u32::from_le_bytes(bytes),
+        })
+    }
+
+    fn u64(&self, offset: u64) -> Result<u64, String> {
+        let bytes = self.bytes(offset, 8)?.try_into().unwrap();
+        Ok(match self.big_endian {
+            true => u64::from_be_bytes(bytes),
+            false => u64::from_le_bytes(bytes),
+        })
+    }
+
+    /// An address or offset, of 64 bits if `wide` or else 32.
+    fn word(&self, offset: u64, wide: bool) -> Result<u64, String> {
+        match wide {
+            true => self.u64(offset),
+            false => self.u32(offset).map(u64::from),
+        }
+    }
+
+    /// Text of at most `len` bytes ending at the first NUL, such as a name.
+    fn text(&self, offset: u64, len: usize) -> Result<String, String> {
+        let available = (self.data.len() as u64).saturating_sub(offset) as usize;
+        let bytes = self.bytes(offset, len.min(available))?;
+        let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+        Ok(String::from_utf8_lossy(&bytes[..end]).into_owned())
+    }
+}
+
+/// Permissions as `rwx`, each given by whether the corresponding flag is set.
+fn permissions(read: bool, write: bool, execute: bool) -> String {
+    [(read, 'r'), (write, 'w'), (execute, 'x')]
+        .iter()
+        .map(|&(set, c)| if set { c } else { '-' })
+        .collect()
+}
+
+/// Summarize the headers of `data`, the contents of an executable.
+pub fn summarize(data: &[u8]) -> Result<Summary, String> {
+    match data.get(..4) {
+        Some(b"\x7fELF") => elf(data),
+        Some([b'M', b'Z', ..]) => pe(data),
+        Some(
+            b"\xfe\xed\xfa\xce" | b"\xfe\xed\xfa\xcf" | b"\xce\xfa\xed\xfe" | b"\xcf\xfa\xed\xfe",
+        ) => mach_o(data),
+        Some(b"\xca\xfe\xba\xbe") => fat(data),
+        _ => Err("not an ELF, PE, or Mach-O file".to_string()),
+    }
+}
+
+fn elf(data: &[u8]) -> Result<Summary, String> {
+    let wide = match data.get(4) {
+        Some(1) => false,
+        Some(2) => true,
+        _ => return Err("unknown ELF class".to_string()),
+    };
+    let big_endian = match data.get(5) {
+        Some(1) => false,
+        Some(2) => true,
+        _ => return Err("unknown ELF byte order".to_string()),
+    };
+    let r = Reader { data, big_endian };
+
+    let kind = match r.u16(16)? {
+        1 => "relocatable".to_string(),
+        2 => "executable".to_string(),
+        3 => "shared object".to_string(),
+        4 => "core dump".to_string(),
+        kind => format!("{:#x}", kind),
+    };
+    let architecture = match r.u16(18)? {
+        2 => "SPARC".to_string(),
+        3 => "x86".to_string(),
+        8 => "MIPS".to_string(),
+        20 => "PowerPC".to_string(),
+        21 => "PowerPC64".to_string(),
+        22 => "S/390".to_string(),
+        40 => "ARM".to_string(),
+        62 => "x86-64".to_string(),
+        183 => "AArch64".to_string(),
+        243 => "RISC-V".to_string(),
+        258 => "LoongArch".to_string(),
+        machine => format!("machine {}", machine),
+    };
+    let (entry, phoff, shoff, rest) = match wide {
+        true => (r.u64(24)?, r.u64(32)?, r.u64(40)?, 48),
+        false => (
+            r.word(24, false)?,
+            r.word(28, false)?,
+            r.word(32, false)?,
+            36,
+        ),
+    };
+    let phentsize = r.u16(rest + 6)? as u64;
+    let phnum = r.u16(rest + 8)? as u64;
+    let shentsize = r.u16(rest + 10)? as u64;
+    let shnum = r.u16(rest + 12)? as u64;
+    let shstrndx = r.u16(rest + 14)? as u64;
+
+    let mut segments = Vec::new();
+    for i in 0..phnum {
+        let at = phoff.saturating_add(i * phentsize);
+        let (kind, flags, offset, address, size) = match wide {
+            true => (
+                r.u32(at)?,
+                r.u32(at + 4)?,
+                r.u64(at + 8)?,
+                r.u64(at + 16)?,
+                r.u64(at + 32)?,
+            ),
+            false => (
+                r.u32(at)?,
+                r.u32(at + 24)?,
+                r.word(at + 4, false)?,
+                r.word(at + 8, false)?,
+                r.word(at + 16, false)?,
+            ),
+        };
+        let name = match kind {
+            0 => continue,
+            1 => "LOAD".to_string(),
+            2 => "DYNAMIC".to_string(),
+            3 => "INTERP".to_string(),
+            4 => "NOTE".to_string(),
+            6 => "PHDR".to_string(),
+            7 => "TLS".to_string(),
+            0x6474e550 => "GNU_EH_FRAME".to_string(),
+            0x6474e551 => "GNU_STACK".to_string(),
+            0x6474e552 => "GNU_RELRO".to_string(),
+            0x6474e553 => "GNU_PROPERTY".to_string(),
+            kind => format!("{:#x}", kind),
+        };
+        segments.push(Region {
+            name,
+            offset,
+            size,
+            address: Some(address),
+            flags: permissions(flags & 4 != 0, flags & 2 != 0, flags & 1 != 0),
+            mark: false,
+        });
+    }
+
+    // (name within the string table, type, flags, address, offset, size)
+    let mut headers = Vec::new();
+    for i in 0..shnum {
+        let at = shoff.saturating_add(i * shentsize);
+        headers.push((
+            r.u32(at)? as u64,
+            r.u32(at + 4)?,
+            r.word(at + 8, wide)?,
+            r.word(at + if wide { 16 } else { 12 }, wide)?,
+            r.word(at + if wide { 24 } else { 16 }, wide)?,
+            r.word(at + if wide { 32 } else { 20 }, wide)?,
+        ));
+    }
+    let names = headers.get(shstrndx as usize).map(|header| header.4);
+    let mut sections = Vec::new();
+    for &(name, kind, flags, address, offset, size) in headers.iter() {
+        const NULL: u32 = 0;
+        const NOBITS: u32 = 8;
+        const WRITE: u64 = 1;
+        const ALLOC: u64 = 2;
+        const EXECINSTR: u64 = 4;
+        if kind == NULL {
+            continue;
+        }
+        let name = match names {
+            Some(names) => r.text(names.saturating_add(name), 256)?,
+            None => String::new(),
+        };
+        sections.push(Region {
+            name,
+            offset,
+            size,
+            address: (flags & ALLOC != 0).then_some(address),
+            flags: permissions(
+                flags & ALLOC != 0,
+                flags & WRITE != 0,
+                flags & EXECINSTR != 0,
+            ),
+            mark: kind != NOBITS,
+        });
+    }
+
+    Ok(Summary {
+        format: format!(
+            "ELF{}, {} endian",
+            if wide { 64 } else { 32 },
+            if big_endian { "big" } else { "little" }
+        ),
+        kind,
+        architecture,
+        entry: (entry != 0).then(|| format!("{:#x}", entry)),
+        tables: vec![("segments", segments), ("sections", sections)],
+    })
+}
+
+fn pe(data: &[u8]) -> Result<Summary, String> {
+    let r = Reader {
+        data,
+        big_endian: false,
+    };
+    let header = r.u32(0x3c)? as u64;
+    if r.bytes(header, 4)? != b"PE\0\0" {
+        return Err("MZ executable without a PE header".to_string());
+    }
+    let architecture = match r.u16(header + 4)? {
+        0x14c => "x86".to_string(),
+        0x8664 => "x86-64".to_string(),
+        0x1c0 => "ARM".to_string(),
+        0x1c4 => "ARM Thumb-2".to_string(),
+        0xaa64 => "ARM64".to_string(),
+        0x5064 => "RISC-V 64".to_string(),
+        machine => format!("machine {:#x}", machine),
+    };
+    let count = r.u16(header + 6)? as u64;
+    let optional_size = r.u16(header + 20)? as u64;
+    let characteristics = r.u16(header + 22)?;
+    let kind = if characteristics & 0x2000 != 0 {
+        "DLL"
+    } else if characteristics & 0x0002 != 0 {
+        "executable"
+    } else {
+        "object"
+    };
+
+    let optional = header + 24;
+    let (wide, format) = match r.u16(optional)? {
+        0x10b => (false, "PE32"),
+        0x20b => (true, "PE32+"),
+        magic => return Err(format!("unknown PE optional header magic {:#x}", magic)),
+    };
+    let entry = r.u32(optional + 16)? as u64;
+    let base = match wide {
+        true => r.u64(optional + 24)?,
+        false => r.u32(optional + 28)? as u64,
+    };
+
+    let mut sections = Vec::new();
+    for i in 0..count {
+        const EXECUTE: u32 = 0x2000_0000;
+        const READ: u32 = 0x4000_0000;
+        const WRITE: u32 = 0x8000_0000;
+        let at = optional + optional_size + i * 40;
+        let flags = r.u32(at + 36)?;
+        let size = r.u32(at + 16)? as u64;
+        sections.push(Region {
+            name: r.text(at, 8)?,
+            offset: r.u32(at + 20)? as u64,
+            size,
+            address: Some(base.wrapping_add(r.u32(at + 12)? as u64)),
+            flags: permissions(flags & READ != 0, flags & WRITE != 0, flags & EXECUTE != 0),
+            mark: true,
+        });
+    }
+
+    Ok(Summary {
+        format: format.to_string(),
+        kind: kind.to_string(),
+        architecture,
+        entry: (entry != 0).then(|| format!("{:#x}", base.wrapping_add(entry))),
+        tables: vec![("sections", sections)],
+    })
+}
+
+/// Name of a Mach-O CPU type.
+fn cpu_type(cpu: u32) -> String {
+    match cpu {
+        7 => "x86".to_string(),
+        0x0100_0007 => "x86-64".to_string(),
+        12 => "ARM".to_string(),
+        0x0100_000c => "ARM64".to_string(),
+        0x0200_000c => "ARM64_32".to_string(),
+        18 => "PowerPC".to_string(),
+        0x0100_0012 => "PowerPC64".to_string(),
+        cpu => format!("CPU type {:#x}", cpu),
+    }
+}
+
+fn mach_o(data: &[u8]) -> Result<Summary, String> {
+    let big_endian = data[0] == 0xfe;
+    let wide = data[0] == 0xcf || data[3] == 0xcf;
+    let r = Reader { data, big_endian };
+
+    let architecture = cpu_type(r.u32(4)?);
+    let kind = match r.u32(12)? {
+        1 => "object".to_string(),
+        2 => "executable".to_string(),
+        4 => "core dump".to_string(),
+        6 => "dynamic library".to_string(),
+        7 => "dynamic linker".to_string(),
+        8 => "bundle".to_string(),
+        10 => "debug symbols".to_string(),
+        11 => "kernel extension".to_string(),
+        kind => format!("{:#x}", kind),
+    };
+    let count = r.u32(16)?;
+
+    let mut segments = Vec::new();
+    let mut sections = Vec::new();
+    let mut entry = None;
+    let mut at: u64 = if wide { 32 } else { 28 };
+    for _ in 0..count {
+        const SEGMENT: u32 = 0x1;
+        const SEGMENT_64: u32 = 0x19;
+        const MAIN: u32 = 0x8000_0028;
+        let (command, size) = (r.u32(at)?, r.u32(at + 4)? as u64);
+        if size < 8 {
+            return Err(format!("malformed load command at {:#x}", at));
+        }
+        match command {
+            SEGMENT | SEGMENT_64 => {
+                let wide = command == SEGMENT_64;
+                let word = if wide { 8 } else { 4 };
+                let fields = at + 24;
+                let protection = r.u32(fields + 4 * word + 4)?;
+                segments.push(Region {
+                    name: r.text(at + 8, 16)?,
+                    offset: r.word(fields + 2 * word, wide)?,
+                    size: r.word(fields + 3 * word, wide)?,
+                    address: Some(r.word(fields, wide)?),
+                    flags: permissions(
+                        protection & 1 != 0,
+                        protection & 2 != 0,
+                        protection & 4 != 0,
+                    ),
+                    mark: false,
+                });
+                let nsects = r.u32(fields + 4 * word + 8)? as u64;
+                let (first, stride) = if wide { (at + 72, 80) } else { (at + 56, 68) };
+                for i in 0..nsects {
+                    let section = first + i * stride;
+                    let flags = r.u32(section + 32 + 2 * word + 16)?;
+                    // zero filled sections have no contents in the file
+                    let zerofill = matches!(flags & 0xff, 0x1 | 0xc | 0x12);
+                    sections.push(Region {
+                        name: format!("{},{}", r.text(section + 16, 16)?, r.text(section, 16)?),
+                        offset: r.u32(section + 32 + 2 * word)? as u64,
+                        size: r.word(section + 32 + word, wide)?,
+                        address: Some(r.word(section + 32, wide)?),
+                        flags: String::new(),
+                        mark: !zerofill,
+                    });
+                }
+            }
+            MAIN => entry = Some(format!("{:#x} (file offset)", r.u64(at + 8)?)),
+            _ => {}
+        }
+        at += size;
+    }
+
+    Ok(Summary {
+        format: format!(
+            "Mach-O {}, {} endian",
+            if wide { 64 } else { 32 },
+            if big_endian { "big" } else { "little" }
+        ),
+        kind,
+        architecture,
+        entry,
+        tables: vec![("segments", segments), ("sections", sections)],
+    })
+}
+
+/// Universal binaries, holding a Mach-O file for each of several architectures.
+fn fat(data: &[u8]) -> Result<Summary, String> {
+    let r = Reader {
+        data,
+        big_endian: true,
+    };
+    let count = r.u32(4)?;
+    // Java class files share the magic number, but give a version here
+    if count == 0 || count > 32 {
+        return Err("not an ELF, PE, or Mach-O file".to_string());
+    }
+    let mut slices = Vec::new();
+    for i in 0..count as u64 {
+        let at = 8 + i * 20;
+        slices.push(Region {
+            name: cpu_type(r.u32(at)?),
+            offset: r.u32(at + 8)? as u64,
+            size: r.u32(at + 12)? as u64,
+            address: None,
+            flags: String::new(),
+            mark: true,
+        });
+    }
+    let architectures: Vec<&str> = slices.iter().map(|s| s.name.as_str()).collect();
+    Ok(Summary {
+        format: "Mach-O universal binary".to_string(),
+        kind: format!("{} architectures", count),
+        architecture: architectures.join(", "),
+        entry: None,
+        tables: vec![("architectures", slices)],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Verify a minimal PE32+ executable, and rejection of other files.
+    fn test_summarize() {
+        let mut data = vec![0u8; 0x400];
+        let mut put = |at: usize, bytes: &[u8]| data[at..at + bytes.len()].copy_from_slice(bytes);
+        put(0, b"MZ");
+        put(0x3c, &0x40u32.to_le_bytes());
+        put(0x40, b"PE\0\0");
+        put(0x44, &0x8664u16.to_le_bytes());
+        put(0x46, &1u16.to_le_bytes());
+        put(0x54, &0xf0u16.to_le_bytes());
+        put(0x56, &0x22u16.to_le_bytes());
+        put(0x58, &0x20bu16.to_le_bytes());
+        put(0x58 + 16, &0x1010u32.to_le_bytes());
+        put(0x58 + 24, &0x1_4000_0000u64.to_le_bytes());
+        put(0x148, b".text\0\0\0");
+        put(0x148 + 12, &0x1000u32.to_le_bytes());
+        put(0x148 + 16, &0x200u32.to_le_bytes());
+        put(0x148 + 20, &0x200u32.to_le_bytes());
+        put(0x148 + 36, &0x6000_0020u32.to_le_bytes());
+
+        let summary = summarize(&data).unwrap();
+        assert_eq!(summary.format, "PE32+");
+        assert_eq!(summary.kind, "executable");
+        assert_eq!(summary.architecture, "x86-64");
+        assert_eq!(summary.entry.as_deref(), Some("0x140001010"));
+        let text = Region {
+            name: ".text".to_string(),
+            offset: 0x200,
+            size: 0x200,
+            address: Some(0x1_4000_1000),
+            flags: "r-x".to_string(),
+            mark: true,
+        };
+        assert_eq!(summary.tables, vec![("sections", vec![text])]);
+        assert_eq!(
+            summary.marks(),
+            vec![(0x200, ".text, 512 bytes".to_string())]
+        );
+
+        assert!(summarize(b"\x7fELF").is_err());
+        assert!(summarize(b"MZ").is_err());
+        assert!(summarize(b"hello, world").is_err());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    /// Verify the ELF headers of the test executable itself.
+    fn test_summarize_elf() {
+        let data = std::fs::read(std::env::current_exe().unwrap()).unwrap();
+        let summary = summarize(&data).unwrap();
+        assert!(summary.format.starts_with("ELF"));
+        let (_, sections) = &summary.tables[1];
+        let text = sections.iter().find(|s| s.name == ".text").unwrap();
+        assert_eq!(text.flags, "r-x");
+        assert!(summary
+            .marks()
+            .iter()
+            .any(|(offset, _)| *offset == text.offset));
+    }
+}
@@ -0,0 +1,211 @@
+//! Search for a byte pattern, given in hex or as text, within the file.
+
+use std::io::{self, Read, Write};
+
+/// Size of the chunks in which the input is searched.
+const CHUNK: usize = 64 * 1024;
+
+/// Encoding of text patterns.
+#[derive(clap::ValueEnum, Debug, Clone, Copy)]
+pub enum Encoding {
+    /// UTF-8, including plain ASCII
+    Utf8,
+    /// UTF-16, in the byte order given by `--endian`
+    Utf16,
+}
+
+/// Bytes written in hex, optionally after `0x` and with spaces between them
+/// (e.g. `7f454c46`, `0x7f454c46`, or `0x7f 45 4c 46`).
+pub fn hex(s: &str) -> Result<Vec<u8>, String> {
+    let hex = s
+        .strip_prefix("0x")
+        .or_else(|| s.strip_prefix("0X"))
+        .unwrap_or(s);
+    let digits: Vec<u8> = hex.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+    if digits.is_empty() || !digits.len().is_multiple_of(2) {
+        return Err(format!("hex {:?} is not a whole number of bytes", s));
+    }
+    digits
+        .chunks(2)
+        .map(|pair| {
+            std::str::from_utf8(pair)
+                .ok()
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .ok_or_else(|| format!("invalid hex {:?}", s))
+        })
+        .collect()
+}
+
+/// Bytes to search for: those written in hex after `0x` (e.g. `0x7f454c46`, or
+/// `0x7f 45 4c 46`), or else the text encoded as `encoding`.
+pub fn pattern(s: &str, encoding: Encoding, big_endian: bool) -> Result<Vec<u8>, String> {
+    if s.starts_with("0x") || s.starts_with("0X") {
+        return hex(s);
+    }
+
+    if s.is_empty() {
+        return Err("empty pattern".to_string());
+    }
+    Ok(match encoding {
+        Encoding::Utf8 => s.as_bytes().to_vec(),
+        Encoding::Utf16 => s
+            .encode_utf16()
+            .flat_map(|unit| match big_endian {
+                true => unit.to_be_bytes(),
+                false => unit.to_le_bytes(),
+            })
+            .collect(),
+    })
+}
+
+/// Write the offset of the first occurrence of `needle` read from `input`, or
+/// of every occurrence if `all` is given, numbering bytes from `start`.
+///
+/// Returns the number of occurrences written.
+pub fn search(
+    input: &mut impl Read,
+    output: &mut impl Write,
+    needle: &[u8],
+    start: u64,
+    all: bool,
+) -> io::Result<u64> {
+    let mut found = 0;
+    // bytes not yet searched, keeping those that may begin a match spanning chunks
+    let mut window = Vec::with_capacity(CHUNK + needle.len());
+    let mut offset = start;
+    loop {
+        let kept = window.len();
+        window.resize(kept + CHUNK, 0);
+        let n = match input.read(&mut window[kept..]) {
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {
+                window.truncate(kept);
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        window.truncate(kept + n);
+        if n == 0 {
+            break;
+        }
+
+        if !scan(&window, output, needle, offset, all, &mut found)? {
+            break;
+        }
+
+        let consumed = window.len().saturating_sub(needle.len() - 1);
+        window.drain(..consumed);
+        offset += consumed as u64;
+    }
+    output.flush()?;
+    Ok(found)
+}
+
+/// Write the offset of the first occurrence of `needle` within `haystack`, or
+/// of every occurrence if `all` is given, numbering bytes from `start`.
+///
+/// Returns the number of occurrences written.
+pub fn search_slice(
+    haystack: &[u8],
+    output: &mut impl Write,
+    needle: &[u8],
+    start: u64,
+    all: bool,
+) -> io::Result<u64> {
+    let mut found = 0;
+    scan(haystack, output, needle, start, all, &mut found)?;
+    output.flush()?;
+    Ok(found)
+}
+
+/// Write the offsets of occurrences of `needle` within `haystack`, found at
+/// `offset`, counting them in `found`.
+///
+/// Returns whether to keep searching, which is only after the first occurrence
+/// if `all` is given.
+fn scan(
+    haystack: &[u8],
+    output: &mut impl Write,
+    needle: &[u8],
+    offset: u64,
+    all: bool,
+    found: &mut u64,
+) -> io::Result<bool> {
+    for (i, candidate) in haystack.windows(needle.len()).enumerate() {
+        if candidate == needle {
+            writeln!(output, "{:08x}", offset + i as u64)?;
+            *found += 1;
+            if !all {
+                return Ok(false);
+            }
+        }
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Verify hex and text patterns in each encoding.
+    fn test_pattern() {
+        assert_eq!(
+            pattern("0x7f454C46", Encoding::Utf8, false),
+            Ok(b"\x7fELF".to_vec())
+        );
+        assert_eq!(
+            pattern("0x7f 45 4c 46", Encoding::Utf8, false),
+            Ok(b"\x7fELF".to_vec())
+        );
+        assert_eq!(pattern("ELF", Encoding::Utf8, false), Ok(b"ELF".to_vec()));
+        assert_eq!(
+            pattern("é", Encoding::Utf8, false),
+            Ok(b"\xc3\xa9".to_vec())
+        );
+        assert_eq!(
+            pattern("hi", Encoding::Utf16, false),
+            Ok(b"h\0i\0".to_vec())
+        );
+        assert_eq!(pattern("hi", Encoding::Utf16, true), Ok(b"\0h\0i".to_vec()));
+        assert!(pattern("0x7f4", Encoding::Utf8, false).is_err());
+        assert!(pattern("0xzz", Encoding::Utf8, false).is_err());
+        assert!(pattern("0x", Encoding::Utf8, false).is_err());
+        assert!(pattern("", Encoding::Utf8, false).is_err());
+    }
+
+    #[test]
+    /// Verify that the first or every match is found, including overlapping
+    /// matches and those spanning chunks.
+    fn test_search() {
+        let search = |input: &[u8], needle: &[u8], all: bool| {
+            let mut output = Vec::new();
+            let found = search(&mut &input[..], &mut output, needle, 0x10, all).unwrap();
+            let mut sliced = Vec::new();
+            let found_sliced = search_slice(input, &mut sliced, needle, 0x10, all).unwrap();
+            assert_eq!((found, &output), (found_sliced, &sliced));
+            (found, String::from_utf8(output).unwrap())
+        };
+
+        assert_eq!(
+            search(b"xaaax", b"aa", false),
+            (1, "00000011\n".to_string())
+        );
+        assert_eq!(
+            search(b"xaaax", b"aa", true),
+            (2, "00000011\n00000012\n".to_string())
+        );
+        assert_eq!(search(b"xyz", b"aa", true), (0, String::new()));
+
+        let mut input = vec![0u8; CHUNK * 2];
+        input[CHUNK - 1..CHUNK + 2].copy_from_slice(b"abc");
+        input[CHUNK * 2 - 3..].copy_from_slice(b"abc");
+        assert_eq!(
+            search(&input, b"abc", true),
+            (
+                2,
+                format!("{:08x}\n{:08x}\n", 0x10 + CHUNK - 1, 0x10 + CHUNK * 2 - 3)
+            )
+        );
+    }
+}
@@ -0,0 +1,145 @@
+//! Digests of the file or of a range of it, as by `sha256sum`, `md5sum`, and
+//! `crc32`, computed in a single pass.
+
+use std::io::{self, Read};
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum Algo {
+    Sha256,
+    Md5,
+    Crc32,
+}
+
+impl Algo {
+    /// Name of the algorithm, as for `sha256sum --tag`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Algo::Sha256 => "SHA256",
+            Algo::Md5 => "MD5",
+            Algo::Crc32 => "CRC32",
+        }
+    }
+
+    fn hasher(self) -> Box<dyn Hasher> {
+        match self {
+            Algo::Sha256 => Box::<sha2::Sha256>::default(),
+            Algo::Md5 => Box::<md5::Md5>::default(),
+            Algo::Crc32 => Box::<crc32fast::Hasher>::default(),
+        }
+    }
+}
+
+trait Hasher {
+    fn update(&mut self, data: &[u8]);
+    fn finish(self: Box<Self>) -> Vec<u8>;
+}
+
+impl Hasher for sha2::Sha256 {
+    fn update(&mut self, data: &[u8]) {
+        sha2::Digest::update(self, data);
+    }
+
+    fn finish(self: Box<Self>) -> Vec<u8> {
+        sha2::Digest::finalize(*self).to_vec()
+    }
+}
+
+impl Hasher for md5::Md5 {
+    fn update(&mut self, data: &[u8]) {
+        md5::Digest::update(self, data);
+    }
+
+    fn finish(self: Box<Self>) -> Vec<u8> {
+        md5::Digest::finalize(*self).to_vec()
+    }
+}
+
+impl Hasher for crc32fast::Hasher {
+    fn update(&mut self, data: &[u8]) {
+        crc32fast::Hasher::update(self, data);
+    }
+
+    fn finish(self: Box<Self>) -> Vec<u8> {
+        self.finalize().to_be_bytes().to_vec()
+    }
+}
+
+/// Digest of everything read from `input` by each of `algos`, in hex.
+pub fn digests(input: &mut impl Read, algos: &[Algo]) -> io::Result<Vec<String>> {
+    let mut hashers: Vec<Box<dyn Hasher>> = algos.iter().map(|algo| algo.hasher()).collect();
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = match input.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        for hasher in hashers.iter_mut() {
+            hasher.update(&buf[..n]);
+        }
+    }
+    Ok(hashers
+        .into_iter()
+        .map(|hasher| {
+            hasher
+                .finish()
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect()
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn digest(input: &[u8], algo: Algo) -> String {
+        digests(&mut &input[..], &[algo]).unwrap().remove(0)
+    }
+
+    #[test]
+    /// Verify each algorithm against published test vectors, including input
+    /// spanning several blocks.
+    fn test_digests() {
+        assert_eq!(
+            digest(b"", Algo::Sha256),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            digest(b"abc", Algo::Sha256),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            digest(
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq",
+                Algo::Sha256
+            ),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+        assert_eq!(
+            digest(&[b'a'; 1000], Algo::Sha256),
+            "41edece42d63e8d9bf515a9ba6932e1c20cbc9f5a5d134645adb5db1b9737ea3"
+        );
+
+        assert_eq!(digest(b"", Algo::Md5), "d41d8cd98f00b204e9800998ecf8427e");
+        assert_eq!(
+            digest(b"abc", Algo::Md5),
+            "900150983cd24fb0d6963f7d28e17f72"
+        );
+        assert_eq!(
+            digest(
+                b"12345678901234567890123456789012345678901234567890123456789012345678901234567890",
+                Algo::Md5
+            ),
+            "57edf4a22be3c955ac49da2e2107b67a"
+        );
+
+        assert_eq!(digest(b"", Algo::Crc32), "00000000");
+        assert_eq!(digest(b"123456789", Algo::Crc32), "cbf43926");
+
+        let all = digests(&mut &b"abc"[..], &[Algo::Crc32, Algo::Md5]).unwrap();
+        assert_eq!(all, ["352441c2", "900150983cd24fb0d6963f7d28e17f72"]);
+    }
+}
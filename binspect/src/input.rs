@@ -0,0 +1,138 @@
+//! The file inspected, mapped into memory where possible, so that starting far
+//! into a large file or disk image costs nothing and only the pages viewed are
+//! ever read, or else read as a stream, as from a pipe.
+
+use std::io::{self, Read, Seek};
+
+enum Source {
+    /// A range of a regular file mapped into memory, and the position read up to
+    Mapped {
+        map: memmap2::Mmap,
+        position: usize,
+        end: usize,
+    },
+    Stream(io::Take<Box<dyn Read>>),
+}
+
+pub struct Input {
+    source: Source,
+}
+
+/// Open the file at `path` for reading, with its name in any error.
+fn open_file(path: &str) -> io::Result<std::fs::File> {
+    std::fs::File::open(path).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path, e)))
+}
+
+/// Map all of `file` into memory, if it is a regular file that is not empty.
+fn map(file: &std::fs::File) -> io::Result<Option<memmap2::Mmap>> {
+    let metadata = file.metadata()?;
+    if !metadata.is_file() || metadata.len() == 0 {
+        return Ok(None);
+    }
+    // SAFETY: the map is only read, though should another process truncate the
+    // file meanwhile, reading past its new end would fault
+    unsafe { memmap2::Mmap::map(file) }.map(Some)
+}
+
+impl Input {
+    /// The file at `path`, or standard input if none or `-`, from `skip` and
+    /// limited to `length` bytes.
+    pub fn open(path: Option<&str>, skip: u64, length: Option<u64>) -> io::Result<Self> {
+        let length = length.unwrap_or(u64::MAX);
+        let stream: Box<dyn Read> = match path {
+            None | Some("-") => {
+                let mut stdin = io::stdin().lock();
+                // pipes cannot seek, so read up to the offset instead
+                io::copy(&mut (&mut stdin).take(skip), &mut io::sink())?;
+                Box::new(stdin)
+            }
+            Some(path) => {
+                let mut file = open_file(path)?;
+                if let Some(map) = map(&file)? {
+                    let position = map.len().min(skip.try_into().unwrap_or(usize::MAX));
+                    let available = (map.len() - position) as u64;
+                    let end = position + length.min(available) as usize;
+                    return Ok(Self {
+                        source: Source::Mapped { map, position, end },
+                    });
+                }
+                // such as a block device, whose size is not known until read
+                file.seek(io::SeekFrom::Start(skip))?;
+                Box::new(io::BufReader::new(file))
+            }
+        };
+        Ok(Self {
+            source: Source::Stream(stream.take(length)),
+        })
+    }
+
+    /// The bytes not yet read, if mapped into memory.
+    pub fn as_slice(&self) -> Option<&[u8]> {
+        match &self.source {
+            Source::Mapped { map, position, end } => Some(&map[*position..*end]),
+            Source::Stream(_) => None,
+        }
+    }
+}
+
+impl Read for Input {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match &mut self.source {
+            Source::Mapped { map, position, end } => {
+                let n = buf.len().min(*end - *position);
+                buf[..n].copy_from_slice(&map[*position..*position + n]);
+                *position += n;
+                Ok(n)
+            }
+            Source::Stream(stream) => stream.read(buf),
+        }
+    }
+}
+
+/// All of the file at `path`, or of standard input if none or `-`, mapped into
+/// memory where possible.
+pub fn load(path: Option<&str>) -> io::Result<Box<dyn AsRef<[u8]>>> {
+    if let Some(path) = path.filter(|&path| path != "-") {
+        let file = open_file(path)?;
+        if let Some(map) = map(&file)? {
+            return Ok(Box::new(map));
+        }
+        let mut data = Vec::new();
+        io::BufReader::new(file).read_to_end(&mut data)?;
+        return Ok(Box::new(data));
+    }
+    let mut data = Vec::new();
+    io::stdin().lock().read_to_end(&mut data)?;
+    Ok(Box::new(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Verify that a range of a mapped file is read, and nothing past its end.
+    fn test_open_mapped() {
+        let path = std::env::temp_dir().join(format!("binspect-{}", std::process::id()));
+        std::fs::write(&path, b"0123456789").unwrap();
+        let path = path.to_str().unwrap();
+
+        let read = |skip, length| {
+            let mut input = Input::open(Some(path), skip, length).unwrap();
+            assert!(input.as_slice().is_some());
+            let mut data = Vec::new();
+            input.read_to_end(&mut data).unwrap();
+            data
+        };
+        assert_eq!(read(0, None), b"0123456789");
+        assert_eq!(read(3, Some(4)), b"3456");
+        assert_eq!(read(8, Some(4)), b"89");
+        assert_eq!(read(20, None), b"");
+
+        let mut input = Input::open(Some(path), 2, Some(6)).unwrap();
+        let mut buf = [0; 4];
+        assert_eq!(input.read(&mut buf).unwrap(), 4);
+        assert_eq!(input.as_slice(), Some(&b"67"[..]));
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -1,4 +1,21 @@
-use clap::Parser;
+use clap::{CommandFactory, Parser};
+use input::Input;
+use std::io::{IsTerminal, Write};
+use std::process::ExitCode;
+
+mod diff;
+mod dump;
+mod embed;
+mod entropy;
+mod exe;
+mod find;
+mod hash;
+mod input;
+mod patch;
+mod reverse;
+mod size;
+mod template;
+mod typed;
 
 #[derive(clap::ValueEnum, Debug, Clone, Copy)]
 enum Endian {
@@ -7,6 +24,37 @@ enum Endian {
     Native,
 }
 
+impl Endian {
+    fn is_big(self) -> bool {
+        match self {
+            Endian::Big => true,
+            Endian::Little => false,
+            Endian::Native => cfg!(target_endian = "big"),
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy)]
+enum Color {
+    Auto,
+    Always,
+    Never,
+}
+
+impl Color {
+    /// Whether to color output: if asked to, or when writing to a terminal
+    /// unless `NO_COLOR` is set.
+    fn enabled(self) -> bool {
+        match self {
+            Color::Always => true,
+            Color::Never => false,
+            Color::Auto => {
+                std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+            }
+        }
+    }
+}
+
 #[derive(clap::ValueEnum, Debug, Clone, Copy)]
 enum Format {
     U8,
@@ -25,14 +73,103 @@ enum Format {
     Utf8,
     Utf16,
     Utf32, // character encoding
+    CArray,
+    RustArray,
+    PythonBytes,
+    Base64, // source code
+}
+
+impl Format {
+    /// Type of the values shown, for numeric formats.
+    fn numeric(self) -> Option<typed::Type> {
+        Some(match self {
+            Format::U8 => typed::Type::U8,
+            Format::U16 => typed::Type::U16,
+            Format::U32 => typed::Type::U32,
+            Format::U64 => typed::Type::U64,
+            Format::I8 => typed::Type::I8,
+            Format::I16 => typed::Type::I16,
+            Format::I32 => typed::Type::I32,
+            Format::I64 => typed::Type::I64,
+            Format::F32 => typed::Type::F32,
+            Format::F64 => typed::Type::F64,
+            _ => return None,
+        })
+    }
+}
+
+// What to do instead of inspecting a file (not a doc comment, which clap would
+// take as the description of binspect itself)
+#[derive(clap::Subcommand, Debug)]
+enum Action {
+    /// Compare two files, showing only the lines where they differ side by side,
+    /// and a summary of the differing ranges; exits with status 1 if they differ
+    Diff {
+        /// First file, or `-` for standard input
+        a: String,
+        /// Second file, or `-` for standard input
+        b: String,
+    },
+    /// Compute digests of the file, or of the range given by --skip and
+    /// --length, one line for each algorithm as by `sha256sum --tag`
+    Hash {
+        /// File to hash, or `-` for standard input
+        file: String,
+        #[arg(long, value_enum, value_delimiter = ',', default_value = "sha256")]
+        /// Algorithms to compute, separated by commas
+        algo: Vec<hash::Algo>,
+    },
+    /// Write bytes in place over those of the file from an offset within it
+    Patch {
+        /// File to change
+        file: String,
+        /// Offset of the first byte to write
+        #[arg(long, value_name = "OFFSET", value_parser = size::parse)]
+        at: u64,
+        /// Bytes to write, in hex (e.g. `90 90` or `0x9090`)
+        #[arg(
+            long,
+            value_name = "HEX",
+            required_unless_present = "from_file",
+            conflicts_with = "from_file"
+        )]
+        bytes: Option<String>,
+        /// Write the contents of this file instead
+        #[arg(long, value_name = "PATH")]
+        from_file: Option<std::path::PathBuf>,
+        /// Show the bytes before and after instead of writing them
+        #[arg(long)]
+        dry_run: bool,
+        /// First copy the file to its name with this suffix appended
+        #[arg(
+            long,
+            value_name = "SUFFIX",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = ".bak",
+            conflicts_with = "dry_run"
+        )]
+        backup: Option<String>,
+    },
 }
 
 #[derive(Parser, Debug, Default)]
-#[command(author, version, about, long_about = None, propagate_version = true)]
+#[command(
+    author,
+    version,
+    about,
+    long_about = None,
+    propagate_version = true,
+    disable_help_subcommand = true
+)]
 struct Config {
+    #[command(subcommand)]
+    action: Option<Action>,
+
     #[arg()]
-    /// Filename to inspect
-    file: String,
+    /// Filename to inspect, or `-` for standard input [default: standard input
+    /// when not a terminal]
+    file: Option<String>,
 
     /// Load file in interactive
     #[arg(short, long)]
@@ -45,9 +182,343 @@ struct Config {
     /// Specify endianness of the data
     #[arg(short, long, value_enum, default_value = "native")]
     endian: Option<Endian>,
+
+    /// Values shown on each line by numeric formats [default: 16 bytes' worth]
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u32).range(1..=typed::MAX_PER_LINE as i64)
+    )]
+    per_line: Option<u32>,
+
+    /// Bytes shown on each line of the hex dump
+    #[arg(
+        short,
+        long,
+        value_name = "BYTES",
+        default_value = "16",
+        value_parser = clap::value_parser!(u16).range(1..=256)
+    )]
+    width: u16,
+
+    /// Bytes in each group of the hex dump, separated by an extra space
+    #[arg(
+        short,
+        long,
+        value_name = "BYTES",
+        default_value = "8",
+        value_parser = clap::value_parser!(u16).range(1..=256)
+    )]
+    group: u16,
+
+    /// When to color bytes by class, and other highlights
+    #[arg(long, value_enum, default_value = "auto", global = true)]
+    color: Option<Color>,
+
+    /// Start at this offset into the file (e.g. 512, 0x200, 4KiB)
+    #[arg(short, long, value_parser = size::parse, default_value = "0", global = true)]
+    skip: u64,
+
+    /// Show at most this many bytes (e.g. 64, 0x40, 1MiB)
+    #[arg(short = 'n', long, value_parser = size::parse, global = true)]
+    length: Option<u64>,
+
+    /// Print the offset of a pattern instead: hex bytes after `0x` (e.g.
+    /// 0x7f454c46), or else text
+    #[arg(long, value_name = "PATTERN")]
+    find: Option<String>,
+
+    /// With --find, print the offset of every match rather than the first
+    #[arg(long, requires = "find")]
+    all: bool,
+
+    /// Encoding of text patterns for --find
+    #[arg(long, value_enum, default_value = "utf8", requires = "find")]
+    encoding: Option<find::Encoding>,
+
+    /// Show the entropy of each block of this many bytes instead, to spot
+    /// compressed or encrypted regions
+    #[arg(
+        long,
+        value_name = "BLOCK_SIZE",
+        value_parser = size::parse,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "1KiB",
+        conflicts_with = "find"
+    )]
+    entropy: Option<u64>,
+
+    /// Identifier of the array written by the c-array, rust-array, and
+    /// python-bytes formats [default: derived from the file name]
+    #[arg(long, value_name = "IDENT")]
+    name: Option<String>,
+
+    /// Read a hex dump instead, and write the bytes it shows to stdout
+    #[arg(short, long, conflicts_with_all = ["find", "entropy", "skip", "length"])]
+    reverse: bool,
+
+    /// Decode records described by this template instead, a file with a field
+    /// on each line as `NAME TYPE[COUNT] [be|le] [@OFFSET]`, where TYPE is a
+    /// numeric format, `bytes`, or `char`
+    #[arg(
+        short,
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["find", "entropy", "reverse"]
+    )]
+    template: Option<std::path::PathBuf>,
+
+    /// With --template, the number of consecutive records to decode
+    #[arg(
+        long,
+        value_name = "N",
+        default_value = "1",
+        requires = "template",
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    records: u32,
+
+    /// Summarize the headers of an ELF, PE, or Mach-O executable instead: its
+    /// architecture, entry point, segments, and sections
+    #[arg(long, conflicts_with_all = ["find", "entropy", "reverse", "template"])]
+    summary: bool,
+
+    /// Mark where each section of an ELF, PE, or Mach-O executable begins in the
+    /// hex dump
+    #[arg(long, conflicts_with_all = ["find", "entropy", "reverse", "template", "summary"])]
+    sections: bool,
+}
+
+fn run(config: &Config) -> std::io::Result<ExitCode> {
+    let colored = config.color.unwrap_or(Color::Auto).enabled();
+    let layout = dump::Layout {
+        width: config.width as usize,
+        group: config.group as usize,
+    };
+    if let Some(Action::Diff { a, b }) = &config.action {
+        if a == "-" && b == "-" {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "only one file can be standard input",
+            ));
+        }
+        let differs = diff::diff(
+            &mut Input::open(Some(a), config.skip, config.length)?,
+            &mut Input::open(Some(b), config.skip, config.length)?,
+            &mut std::io::BufWriter::new(std::io::stdout().lock()),
+            config.skip,
+            colored,
+        )?;
+        return Ok(if differs {
+            ExitCode::FAILURE
+        } else {
+            ExitCode::SUCCESS
+        });
+    }
+    if let Some(Action::Hash { file, algo }) = &config.action {
+        let digests = hash::digests(
+            &mut Input::open(Some(file), config.skip, config.length)?,
+            algo,
+        )?;
+        let mut output = std::io::stdout().lock();
+        for (algo, digest) in algo.iter().zip(digests) {
+            writeln!(output, "{} ({}) = {}", algo.name(), file, digest)?;
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+    if let Some(Action::Patch {
+        file,
+        at,
+        bytes,
+        from_file,
+        dry_run,
+        backup,
+    }) = &config.action
+    {
+        let bytes = match (bytes, from_file) {
+            (Some(bytes), _) => find::hex(bytes)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?,
+            (None, Some(path)) => std::fs::read(path)
+                .map_err(|e| std::io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?,
+            (None, None) => unreachable!("clap requires --bytes or --from-file"),
+        };
+        if bytes.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "no bytes to write",
+            ));
+        }
+        if *dry_run {
+            let output = &mut std::io::BufWriter::new(std::io::stdout().lock());
+            patch::preview(file, *at, &bytes, output, layout, colored)?;
+        } else {
+            patch::patch(file, *at, &bytes, backup.as_deref())?;
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if config.interactive {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "interactive mode is not yet supported",
+        ));
+    }
+    if let Some(pattern) = &config.find {
+        let big_endian = config.endian.unwrap_or(Endian::Native).is_big();
+        let needle = find::pattern(
+            pattern,
+            config.encoding.unwrap_or(find::Encoding::Utf8),
+            big_endian,
+        )
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        let mut input = Input::open(config.file.as_deref(), config.skip, config.length)?;
+        let output = &mut std::io::stdout().lock();
+        let found = match input.as_slice() {
+            Some(data) => find::search_slice(data, output, &needle, config.skip, config.all)?,
+            None => find::search(&mut input, output, &needle, config.skip, config.all)?,
+        };
+        if found == 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("pattern {:?} not found", pattern),
+            ));
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if let Some(block) = config.entropy {
+        if block == 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "invalid entropy block size 0",
+            ));
+        }
+        entropy::report(
+            &mut Input::open(config.file.as_deref(), config.skip, config.length)?,
+            &mut std::io::BufWriter::new(std::io::stdout().lock()),
+            block,
+            config.skip,
+            colored,
+        )?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if config.reverse {
+        reverse::reverse(
+            &mut std::io::BufReader::new(Input::open(config.file.as_deref(), 0, None)?),
+            &mut std::io::BufWriter::new(std::io::stdout().lock()),
+        )?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if let Some(path) = &config.template {
+        let invalid = |e| std::io::Error::new(std::io::ErrorKind::InvalidData, e);
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| std::io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+        let template = template::Template::parse(&text)
+            .map_err(|e| invalid(format!("{}: {}", path.display(), e)))?;
+        template::dump(
+            &mut Input::open(config.file.as_deref(), config.skip, config.length)?,
+            &mut std::io::BufWriter::new(std::io::stdout().lock()),
+            &template,
+            config.records as usize,
+            config.endian.unwrap_or(Endian::Native).is_big(),
+            config.skip,
+        )?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if config.summary || config.sections {
+        let data = input::load(config.file.as_deref())?;
+        let data = (*data).as_ref();
+        let summary = exe::summarize(data)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        let output = &mut std::io::BufWriter::new(std::io::stdout().lock());
+        if config.summary {
+            summary.print(output)?;
+        } else {
+            let start = data.len().min(config.skip.try_into().unwrap_or(usize::MAX));
+            let length = config
+                .length
+                .and_then(|n| n.try_into().ok())
+                .unwrap_or(usize::MAX);
+            let selected = &data[start..][..length.min(data.len() - start)];
+            dump::hex(
+                &mut &selected[..],
+                output,
+                config.skip,
+                layout,
+                &summary.marks(),
+                colored,
+            )?;
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let input = &mut Input::open(config.file.as_deref(), config.skip, config.length)?;
+    let output = &mut std::io::BufWriter::new(std::io::stdout().lock());
+    let name = config
+        .name
+        .clone()
+        .unwrap_or_else(|| embed::identifier(config.file.as_deref()));
+    let format = config.format.unwrap_or(Format::Hex);
+    if let Some(ty) = format.numeric() {
+        let big_endian = config.endian.unwrap_or(Endian::Native).is_big();
+        let per_line = config.per_line.map(|n| n as usize);
+        typed::dump(input, output, ty, big_endian, per_line, config.skip)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    match format {
+        Format::Hex => dump::hex(input, output, config.skip, layout, &[], colored)?,
+        Format::CArray => embed::array(input, output, embed::Syntax::C, &name)?,
+        Format::RustArray => embed::array(input, output, embed::Syntax::Rust, &name)?,
+        Format::PythonBytes => embed::array(input, output, embed::Syntax::Python, &name)?,
+        Format::Base64 => embed::base64(input, output)?,
+        format => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                format!("format {:?} is not yet supported", format),
+            ))
+        }
+    }
+    Ok(ExitCode::SUCCESS)
 }
 
-fn main() {
+fn main() -> ExitCode {
     let config = Config::parse();
-    println!("{:?}", &config);
+    if config.action.is_none() && config.file.is_none() && std::io::stdin().is_terminal() {
+        Config::command()
+            .error(
+                clap::error::ErrorKind::MissingRequiredArgument,
+                "no file given, and standard input is a terminal",
+            )
+            .exit();
+    }
+
+    match run(&config) {
+        Ok(code) => code,
+        // the reader of the dump, such as `head`, has seen enough
+        Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("binspect: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Verify that values shown on a line are limited to what may be read at once.
+    fn test_per_line() {
+        let parse = |n: &str| Config::try_parse_from(["binspect", "file", "--per-line", n]);
+        assert_eq!(parse("1").unwrap().per_line, Some(1));
+        assert_eq!(parse("4096").unwrap().per_line, Some(typed::MAX_PER_LINE));
+        assert!(parse("0").is_err());
+        assert!(parse("4097").is_err());
+        assert!(parse("4294967295").is_err());
+    }
 }
@@ -0,0 +1,114 @@
+//! Bytes written in place over part of a file, for a quick fix without a hex
+//! editor.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use crate::dump::Layout;
+
+/// Open the file at `path` with `options`, with its name in any error.
+fn open(path: &str, options: &OpenOptions) -> io::Result<File> {
+    options
+        .open(path)
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path, e)))
+}
+
+/// The file at `path`, checking that `len` bytes from offset `at` lie within it.
+fn open_range(path: &str, at: u64, len: usize, write: bool) -> io::Result<File> {
+    let file = open(path, OpenOptions::new().read(true).write(write))?;
+    let size = file.metadata()?.len();
+    if at.saturating_add(len as u64) > size {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "{} bytes at {:#x} would extend past the end of {} ({:#x} bytes)",
+                len, at, path, size
+            ),
+        ));
+    }
+    Ok(file)
+}
+
+/// Write `bytes` over those of the file at `path` from offset `at`, which must
+/// lie within the file, first copying the file to its path with `backup`
+/// appended if given.
+pub fn patch(path: &str, at: u64, bytes: &[u8], backup: Option<&str>) -> io::Result<()> {
+    let mut file = open_range(path, at, bytes.len(), true)?;
+    if let Some(suffix) = backup {
+        let copy = format!("{}{}", path, suffix);
+        // never overwrite an earlier backup, which may be the only original
+        let mut copy_file = open(&copy, OpenOptions::new().write(true).create_new(true))?;
+        io::copy(&mut file, &mut copy_file)?;
+        copy_file.sync_all()?;
+    }
+    file.seek(SeekFrom::Start(at))?;
+    file.write_all(bytes)?;
+    file.sync_all()
+}
+
+/// Dump the bytes that [`patch`] would overwrite, and then `bytes`, without
+/// changing the file.
+pub fn preview(
+    path: &str,
+    at: u64,
+    bytes: &[u8],
+    output: &mut impl Write,
+    layout: Layout,
+    colored: bool,
+) -> io::Result<()> {
+    let mut file = open_range(path, at, bytes.len(), false)?;
+    let mut before = vec![0u8; bytes.len()];
+    file.seek(SeekFrom::Start(at))?;
+    file.read_exact(&mut before)?;
+    writeln!(output, "before:")?;
+    crate::dump::hex(&mut &before[..], output, at, layout, &[], colored)?;
+    writeln!(output, "after:")?;
+    crate::dump::hex(&mut &bytes[..], output, at, layout, &[], colored)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Verify that bytes are previewed, written in place, backed up, and never
+    /// written past the end of the file.
+    fn test_patch() {
+        let path = std::env::temp_dir().join(format!("binspect-patch-{}", std::process::id()));
+        std::fs::write(&path, b"0123456789").unwrap();
+        let path = path.to_str().unwrap();
+        let backup = format!("{}.bak", path);
+        let _ = std::fs::remove_file(&backup);
+
+        let mut output = Vec::new();
+        preview(path, 2, b"ab", &mut output, Layout::default(), false).unwrap();
+        assert_eq!(std::fs::read(path).unwrap(), b"0123456789");
+        let exp = concat!(
+            "before:\n",
+            "00000002  32 33                                             |23|\n",
+            "00000004\n",
+            "after:\n",
+            "00000002  61 62                                             |ab|\n",
+            "00000004\n",
+        );
+        assert_eq!(String::from_utf8(output).unwrap(), exp);
+
+        patch(path, 2, b"ab", Some(".bak")).unwrap();
+        assert_eq!(std::fs::read(path).unwrap(), b"01ab456789");
+        assert_eq!(std::fs::read(&backup).unwrap(), b"0123456789");
+
+        // the earlier backup is kept
+        assert!(patch(path, 0, b"x", Some(".bak")).is_err());
+        assert_eq!(std::fs::read(path).unwrap(), b"01ab456789");
+
+        patch(path, 9, b"!", None).unwrap();
+        assert_eq!(std::fs::read(path).unwrap(), b"01ab45678!");
+        assert!(patch(path, 9, b"!!", None).is_err());
+        let mut output = Vec::new();
+        assert!(preview(path, u64::MAX, b"!", &mut output, Layout::default(), false).is_err());
+        assert_eq!(std::fs::read(path).unwrap(), b"01ab45678!");
+
+        std::fs::remove_file(path).unwrap();
+        std::fs::remove_file(backup).unwrap();
+    }
+}
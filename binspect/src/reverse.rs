@@ -0,0 +1,114 @@
+//! Binary reassembled from a hex dump, so that a dump can be edited as text and
+//! turned back into a file.
+
+use std::io::{self, BufRead, Read, Write};
+
+/// Largest gap between lines filled with zeros, beyond which an offset is taken
+/// to be a mistake rather than a hole in the dump.
+const MAX_GAP: u64 = 16 << 20;
+
+/// The offset and bytes of a line of a dump, or `None` if it has no bytes, as
+/// with the final line holding only the length, or is a label beginning with
+/// `--`.
+///
+/// Bytes are taken from whitespace separated hex digits after the offset and
+/// before any ASCII panel, which begins with `|`. Digits may be grouped, as
+/// in `6865 6c6c`.
+fn parse_line(line: &str) -> Result<Option<(u64, Vec<u8>)>, String> {
+    if line.starts_with("--") {
+        return Ok(None);
+    }
+    let hex = line.split('|').next().unwrap_or_default();
+    let mut tokens = hex.split_whitespace();
+    let Some(offset) = tokens.next() else {
+        return Ok(None);
+    };
+    let offset = u64::from_str_radix(offset.trim_end_matches(':'), 16)
+        .map_err(|_| format!("invalid offset {:?}", offset))?;
+
+    let mut bytes = Vec::new();
+    for token in tokens {
+        if !token.len().is_multiple_of(2) || !token.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(format!("invalid hex bytes {:?}", token));
+        }
+        for i in (0..token.len()).step_by(2) {
+            bytes.push(u8::from_str_radix(&token[i..i + 2], 16).unwrap());
+        }
+    }
+    Ok((!bytes.is_empty()).then_some((offset, bytes)))
+}
+
+/// Write the bytes of the dump read from `input` to `output`.
+///
+/// The offset of the first line is taken as the start of the output, and any
+/// gaps between the offsets of later lines, of up to `MAX_GAP` bytes, are
+/// filled with zeros.
+pub fn reverse(input: &mut impl BufRead, output: &mut impl Write) -> io::Result<()> {
+    let mut next: Option<u64> = None;
+    for (number, line) in input.lines().enumerate() {
+        let invalid = |e: String| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("line {}: {}", number + 1, e),
+            )
+        };
+        let Some((offset, bytes)) = parse_line(&line?).map_err(invalid)? else {
+            continue;
+        };
+        let expected = *next.get_or_insert(offset);
+        if offset < expected {
+            return Err(invalid(format!(
+                "offset {:08x} overlaps the previous line, which ends at {:08x}",
+                offset, expected
+            )));
+        }
+        if offset - expected > MAX_GAP {
+            return Err(invalid(format!(
+                "offset {:08x} leaves a gap of {} bytes after the previous line",
+                offset,
+                offset - expected
+            )));
+        }
+        let end = offset
+            .checked_add(bytes.len() as u64)
+            .ok_or_else(|| invalid(format!("offset {:08x} is too large", offset)))?;
+        io::copy(&mut io::repeat(0).take(offset - expected), output)?;
+        output.write_all(&bytes)?;
+        next = Some(end);
+    }
+    output.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reverse(input: &str) -> io::Result<Vec<u8>> {
+        let mut output = Vec::new();
+        super::reverse(&mut input.as_bytes(), &mut output).map(|()| output)
+    }
+
+    #[test]
+    /// Verify that a dump is reassembled whatever its ASCII panel says, and that
+    /// gaps are filled.
+    fn test_reverse() {
+        let dump = concat!(
+            "00001000  30 31 32 33 34 35 36 37  38 39 61 62 63 64 65 66  |0123456789abcdef|\n",
+            "00001010  68 65 6c 6c 6f 00 0a ff  7f                       |hello|with|bars|\n",
+            "\n",
+            "00001020  41                                                |A|\n",
+            "00001021\n",
+        );
+        let mut exp = b"0123456789abcdefhello\0\n\xff\x7f".to_vec();
+        exp.extend([0; 7]);
+        exp.push(b'A');
+        assert_eq!(reverse(dump).unwrap(), exp);
+
+        assert_eq!(reverse("00000000: 6865 6c6c 6f\n").unwrap(), b"hello");
+        assert!(reverse("00000010  41\n00000000  42\n").is_err());
+        assert!(reverse("00000000  4g\n").is_err());
+        assert!(reverse("offset  41\n").is_err());
+        assert!(reverse("ffffffffffffffff  41 42\n").is_err());
+        assert!(reverse("00000000  41\nffff000000000000  42\n").is_err());
+    }
+}
@@ -0,0 +1,58 @@
+//! Offsets and lengths given on the command line, such as `4096`, `0x1000`, or
+//! `4KiB`.
+
+/// Parse a decimal number with an optional size suffix, or a `0x` prefixed
+/// hexadecimal number.
+///
+/// Suffixes `K`, `M`, `G`, and `T` (optionally followed by `iB`) are powers of
+/// 1024, while `KB`, `MB`, `GB`, and `TB` are powers of 1000, as for `dd`.
+pub fn parse(s: &str) -> Result<u64, String> {
+    if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        return u64::from_str_radix(hex, 16).map_err(|e| format!("invalid hex number: {}", e));
+    }
+
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, suffix) = s.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("invalid size {:?}", s))?;
+    let multiplier: u64 = match suffix.to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kib" => 1 << 10,
+        "m" | "mib" => 1 << 20,
+        "g" | "gib" => 1 << 30,
+        "t" | "tib" => 1 << 40,
+        "kb" => 1_000,
+        "mb" => 1_000_000,
+        "gb" => 1_000_000_000,
+        "tb" => 1_000_000_000_000,
+        _ => return Err(format!("unknown size suffix {:?}", suffix)),
+    };
+    number
+        .checked_mul(multiplier)
+        .ok_or_else(|| format!("size {:?} is too large", s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Verify decimal, hexadecimal, and suffixed sizes, and rejection of others.
+    fn test_parse() {
+        assert_eq!(parse("4096"), Ok(4096));
+        assert_eq!(parse("0x1000"), Ok(4096));
+        assert_eq!(parse("0XfF"), Ok(255));
+        assert_eq!(parse("4KiB"), Ok(4096));
+        assert_eq!(parse("4k"), Ok(4096));
+        assert_eq!(parse("2MB"), Ok(2_000_000));
+        assert_eq!(parse("1G"), Ok(1 << 30));
+        assert_eq!(parse("16b"), Ok(16));
+        assert!(parse("").is_err());
+        assert!(parse("KiB").is_err());
+        assert!(parse("4XB").is_err());
+        assert!(parse("0x").is_err());
+        assert!(parse("-1").is_err());
+        assert!(parse("99999999T").is_err());
+    }
+}
@@ -0,0 +1,284 @@
+//! Records decoded field by field, as described by a template file, so that a
+//! header can be inspected without writing a parser for it.
+//!
+//! Each line of a template describes a field as `NAME TYPE[COUNT] [be|le]
+//! [@OFFSET]`, for example:
+//!
+//! ```text
+//! # ELF identification
+//! magic    bytes[4]
+//! class    u8
+//! data     u8
+//! entry    u64 le @0x18
+//! ```
+//!
+//! `TYPE` is one of the numeric formats (`u8` to `f64`), `bytes` shown in hex,
+//! or `char` shown as text. Fields follow one another unless given an offset
+//! within the record, and use the byte order of `--endian` unless given one.
+//! Anything after `#` is a comment. Records may be up to 16MiB.
+
+use std::io::{self, Read, Write};
+
+use crate::typed::Type;
+
+/// Largest record a template may describe, as each is read whole into memory.
+const MAX_RECORD: usize = 16 << 20;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Number(Type),
+    Bytes,
+    Char,
+}
+
+impl Kind {
+    fn size(self) -> usize {
+        match self {
+            Kind::Number(ty) => ty.size(),
+            Kind::Bytes | Kind::Char => 1,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Field {
+    name: String,
+    kind: Kind,
+    count: Option<usize>,
+    big_endian: Option<bool>,
+    offset: usize,
+}
+
+impl Field {
+    /// Size of the field, which `parse_field` ensures fits within a record.
+    fn size(&self) -> usize {
+        self.kind.size() * self.count.unwrap_or(1)
+    }
+
+    /// The value of the field held by `bytes`, exactly its size.
+    fn format(&self, bytes: &[u8], big_endian: bool) -> String {
+        match self.kind {
+            Kind::Number(ty) => {
+                let big_endian = self.big_endian.unwrap_or(big_endian);
+                let values: Vec<String> = bytes
+                    .chunks_exact(ty.size())
+                    .map(|value| ty.format(value, big_endian))
+                    .collect();
+                match self.count {
+                    Some(_) => format!("[{}]", values.join(", ")),
+                    None => values.join(""),
+                }
+            }
+            Kind::Bytes => {
+                let hex: Vec<String> = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+                hex.join(" ")
+            }
+            Kind::Char => {
+                let text = String::from_utf8_lossy(bytes);
+                format!("{:?}", text.trim_end_matches('\0'))
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Template {
+    fields: Vec<Field>,
+}
+
+impl Template {
+    /// Parse the text of a template, reporting the line of any error.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut fields = Vec::new();
+        let mut next = 0;
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default();
+            let mut tokens = line.split_whitespace();
+            let Some(name) = tokens.next() else {
+                continue;
+            };
+            let field = parse_field(name, tokens, next)
+                .map_err(|e| format!("line {}: {}", number + 1, e))?;
+            next = field.offset + field.size();
+            fields.push(field);
+        }
+        if fields.is_empty() {
+            return Err("template has no fields".to_string());
+        }
+        Ok(Self { fields })
+    }
+
+    /// Size of each record, up to the end of the last field.
+    pub fn size(&self) -> usize {
+        self.fields
+            .iter()
+            .map(|field| field.offset + field.size())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Write each field of the `record` found at `offset`, using the byte order
+    /// given unless the template gives another.
+    fn decode(
+        &self,
+        output: &mut impl Write,
+        record: &[u8],
+        offset: u64,
+        big_endian: bool,
+    ) -> io::Result<()> {
+        let width = self.fields.iter().map(|f| f.name.len()).max().unwrap_or(0);
+        for field in self.fields.iter() {
+            let value = match record.get(field.offset..field.offset + field.size()) {
+                Some(bytes) => field.format(bytes, big_endian),
+                None => "(past the end of the input)".to_string(),
+            };
+            writeln!(
+                output,
+                "{:08x}  {:width$}  {}",
+                offset + field.offset as u64,
+                field.name,
+                value,
+                width = width
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_field<'a>(
+    name: &str,
+    mut tokens: impl Iterator<Item = &'a str>,
+    next: usize,
+) -> Result<Field, String> {
+    let spec = tokens
+        .next()
+        .ok_or_else(|| format!("field {:?} has no type", name))?;
+    let (ty, count) = match spec.split_once('[') {
+        Some((ty, count)) => {
+            let count = count
+                .strip_suffix(']')
+                .and_then(|count| count.parse::<usize>().ok())
+                .filter(|&count| count > 0)
+                .ok_or_else(|| format!("invalid count in {:?}", spec))?;
+            (ty, Some(count))
+        }
+        None => (spec, None),
+    };
+    let kind = match ty {
+        "bytes" => Kind::Bytes,
+        "char" => Kind::Char,
+        ty => Kind::Number(ty.parse()?),
+    };
+
+    let mut field = Field {
+        name: name.to_string(),
+        kind,
+        count,
+        big_endian: None,
+        offset: next,
+    };
+    for token in tokens {
+        match token {
+            "be" => field.big_endian = Some(true),
+            "le" => field.big_endian = Some(false),
+            token => {
+                let offset = token
+                    .strip_prefix('@')
+                    .ok_or_else(|| format!("unexpected {:?}", token))?;
+                field.offset = crate::size::parse(offset)?
+                    .try_into()
+                    .map_err(|_| format!("offset {:?} is too large", offset))?;
+            }
+        }
+    }
+    kind.size()
+        .checked_mul(field.count.unwrap_or(1))
+        .and_then(|size| size.checked_add(field.offset))
+        .filter(|&end| end <= MAX_RECORD)
+        .ok_or_else(|| {
+            format!(
+                "field {:?} extends past {} bytes, the largest record supported",
+                name, MAX_RECORD
+            )
+        })?;
+    Ok(field)
+}
+
+/// Decode up to `records` consecutive records read from `input`, numbering
+/// bytes from `start`, until the input ends.
+pub fn dump(
+    input: &mut impl Read,
+    output: &mut impl Write,
+    template: &Template,
+    records: usize,
+    big_endian: bool,
+    start: u64,
+) -> io::Result<()> {
+    let mut record = vec![0u8; template.size()];
+    let mut offset = start;
+    for i in 0..records {
+        let n = crate::dump::fill(input, &mut record)?;
+        if n == 0 {
+            break;
+        }
+        if i > 0 {
+            writeln!(output)?;
+        }
+        template.decode(output, &record[..n], offset, big_endian)?;
+        offset += n as u64;
+    }
+    output.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Verify fields of each kind, their offsets, and their byte order.
+    fn test_template() {
+        let template = Template::parse(concat!(
+            "# header\n",
+            "magic  bytes[4]\n",
+            "\n",
+            "name   char[6]   # padded\n",
+            "sizes  u16[2] be\n",
+            "flags  u8 @0x10\n",
+        ))
+        .unwrap();
+        assert_eq!(template.size(), 0x11);
+
+        let record = b"\x7fELFboot\0\0\x01\x02\x00\x10..\xff";
+        let mut output = Vec::new();
+        dump(&mut &record[..], &mut output, &template, 2, false, 0x100).unwrap();
+        let exp = concat!(
+            "00000100  magic  7f 45 4c 46\n",
+            "00000104  name   \"boot\"\n",
+            "0000010a  sizes  [258, 16]\n",
+            "00000110  flags  255\n",
+        );
+        assert_eq!(String::from_utf8(output).unwrap(), exp);
+
+        let mut output = Vec::new();
+        dump(&mut &record[..8], &mut output, &template, 1, false, 0).unwrap();
+        assert!(String::from_utf8(output)
+            .unwrap()
+            .ends_with("00000010  flags  (past the end of the input)\n"));
+    }
+
+    #[test]
+    /// Verify that errors give the line of the template.
+    fn test_template_errors() {
+        let error = |text: &str| Template::parse(text).unwrap_err();
+        assert_eq!(error(""), "template has no fields");
+        assert!(error("a u8\nb\n").starts_with("line 2: "));
+        assert!(error("a u9").contains("u9"));
+        assert!(error("a u8[0]").contains("count"));
+        assert!(error("a u8 sideways").contains("sideways"));
+        assert!(error("a u8 @nowhere").starts_with("line 1: "));
+        assert!(error("a u8\nb u8 @0xffffffffffffffff").starts_with("line 2: "));
+        assert!(error("a u64[99999999999999]").contains("largest record"));
+        assert!(error("a u8 @16MiB").contains("largest record"));
+        assert!(Template::parse("a u8 @0xffffff").is_ok());
+    }
+}
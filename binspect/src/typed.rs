@@ -0,0 +1,193 @@
+//! The file as a table of integers or floating point numbers of one type, as by
+//! `od -t`.
+
+use std::io::{self, Read, Write};
+
+/// Bytes shown on each line by default, as by the hex dump.
+const DEFAULT_LINE_BYTES: usize = 16;
+
+/// Most values that may be shown on a line, whose bytes are read at once.
+pub const MAX_PER_LINE: u32 = 4096;
+
+/// Numeric type of the values shown.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Type {
+    U8,
+    U16,
+    U32,
+    U64,
+    I8,
+    I16,
+    I32,
+    I64,
+    F32,
+    F64,
+}
+
+/// Decode `bytes`, exactly the size of `$t`, in the given byte order.
+macro_rules! decode {
+    ($t:ty, $bytes:expr, $big_endian:expr) => {{
+        let bytes = $bytes.try_into().unwrap();
+        if $big_endian {
+            <$t>::from_be_bytes(bytes)
+        } else {
+            <$t>::from_le_bytes(bytes)
+        }
+    }};
+}
+
+impl Type {
+    /// Size of each value in bytes.
+    pub fn size(self) -> usize {
+        match self {
+            Type::U8 | Type::I8 => 1,
+            Type::U16 | Type::I16 => 2,
+            Type::U32 | Type::I32 | Type::F32 => 4,
+            Type::U64 | Type::I64 | Type::F64 => 8,
+        }
+    }
+
+    /// Columns taken by the widest value, so that values line up.
+    fn width(self) -> usize {
+        match self {
+            Type::U8 => 3,
+            Type::U16 => 5,
+            Type::U32 => 10,
+            Type::U64 => 20,
+            Type::I8 => 4,
+            Type::I16 => 6,
+            Type::I32 => 11,
+            Type::I64 => 20,
+            Type::F32 => 15,
+            Type::F64 => 24,
+        }
+    }
+
+    /// The value held by `bytes`, exactly the size of the type.
+    pub fn format(self, bytes: &[u8], big_endian: bool) -> String {
+        match self {
+            Type::U8 => bytes[0].to_string(),
+            Type::U16 => decode!(u16, bytes, big_endian).to_string(),
+            Type::U32 => decode!(u32, bytes, big_endian).to_string(),
+            Type::U64 => decode!(u64, bytes, big_endian).to_string(),
+            Type::I8 => (bytes[0] as i8).to_string(),
+            Type::I16 => decode!(i16, bytes, big_endian).to_string(),
+            Type::I32 => decode!(i32, bytes, big_endian).to_string(),
+            Type::I64 => decode!(i64, bytes, big_endian).to_string(),
+            Type::F32 => format!("{:e}", decode!(f32, bytes, big_endian)),
+            Type::F64 => format!("{:e}", decode!(f64, bytes, big_endian)),
+        }
+    }
+}
+
+impl std::str::FromStr for Type {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "u8" => Type::U8,
+            "u16" => Type::U16,
+            "u32" => Type::U32,
+            "u64" => Type::U64,
+            "i8" => Type::I8,
+            "i16" => Type::I16,
+            "i32" => Type::I32,
+            "i64" => Type::I64,
+            "f32" => Type::F32,
+            "f64" => Type::F64,
+            _ => return Err(format!("unknown type {:?}", s)),
+        })
+    }
+}
+
+/// Write the values of type `ty` read from `input`, `per_line` to a line if
+/// given, or else a line's worth of bytes, numbering bytes from `start`.
+///
+/// Bytes left over at the end, too few to make a value, are shown in hex.
+pub fn dump(
+    input: &mut impl Read,
+    output: &mut impl Write,
+    ty: Type,
+    big_endian: bool,
+    per_line: Option<usize>,
+    start: u64,
+) -> io::Result<()> {
+    let per_line = per_line.unwrap_or(DEFAULT_LINE_BYTES / ty.size());
+    let mut bytes = vec![0u8; per_line * ty.size()];
+    let mut line = String::new();
+    let mut offset = start;
+    loop {
+        let n = crate::dump::fill(input, &mut bytes)?;
+        if n == 0 {
+            break;
+        }
+        line.clear();
+        line.push_str(&format!("{:08x} ", offset));
+        let values = bytes[..n].chunks_exact(ty.size());
+        let partial = values.remainder();
+        for value in values {
+            line.push_str(&format!(
+                " {:>width$}",
+                ty.format(value, big_endian),
+                width = ty.width()
+            ));
+        }
+        if !partial.is_empty() {
+            line.push_str("  (");
+            let hex: Vec<String> = partial.iter().map(|b| format!("{:02x}", b)).collect();
+            line.push_str(&hex.join(" "));
+            line.push(')');
+        }
+        line.push('\n');
+        output.write_all(line.as_bytes())?;
+        offset += n as u64;
+    }
+    if offset != start {
+        writeln!(output, "{:08x}", offset)?;
+    }
+    output.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dump(input: &[u8], ty: Type, big_endian: bool, per_line: Option<usize>) -> String {
+        let mut output = Vec::new();
+        super::dump(&mut &input[..], &mut output, ty, big_endian, per_line, 0).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    /// Verify values of each kind in either byte order.
+    fn test_format() {
+        assert_eq!(Type::U16.format(&[0x01, 0x02], false), "513");
+        assert_eq!(Type::U16.format(&[0x01, 0x02], true), "258");
+        assert_eq!(Type::I8.format(&[0xff], false), "-1");
+        assert_eq!(Type::I32.format(&[0xfe, 0xff, 0xff, 0xff], false), "-2");
+        assert_eq!(Type::U64.format(&[0xff; 8], true), u64::MAX.to_string());
+        assert_eq!(Type::F32.format(&1.5f32.to_be_bytes(), true), "1.5e0");
+        assert_eq!(
+            Type::F64.format(&(-0.25f64).to_le_bytes(), false),
+            "-2.5e-1"
+        );
+    }
+
+    #[test]
+    /// Verify the layout of lines, and bytes left over at the end.
+    fn test_dump() {
+        let input: Vec<u8> = (1..=7).collect();
+        let exp = concat!(
+            "00000000    513  1027\n",
+            "00000004   1541  (07)\n",
+            "00000007\n",
+        );
+        assert_eq!(dump(&input, Type::U16, false, Some(2)), exp);
+
+        let exp = concat!(
+            "00000000     1    2    3    4    5    6    7\n",
+            "00000007\n",
+        );
+        assert_eq!(dump(&input, Type::I8, true, None), exp);
+    }
+}
@@ -0,0 +1,1782 @@
+//! Width-limiting and wrapping engine behind the `chop` utility.
+//!
+//! Lines are cut to fit a column limit (the terminal width by default), either
+//! truncating or wrapping the remainder onto following lines. [`Chopper`] exposes
+//! this as an iterator adapter so other programs can reuse it without shelling out.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+#[derive(clap::Args, Default, Debug, Clone)]
+pub struct Config {
+    #[arg(short, long)]
+    /// Wrap lines at boundary instead of truncating
+    pub wrap: Option<bool>,
+
+    #[arg(short, long)]
+    /// Chop after given number of columns instead of screen width (falls back to `$COLUMNS`, then 80)
+    pub columns: Option<usize>,
+
+    #[arg(short, long)]
+    /// Chop after the last of a given delimiter in a line, limited by terminal width (or `--columns`)
+    pub delimiter: Option<String>,
+
+    #[arg(short, long)]
+    /// Set chop boundary the greatest multiple available, limited by terminal width (or `--columns`)
+    pub multiple: Option<usize>,
+
+    #[arg(short, long)]
+    /// Adjust the chop multiple boundary by a given offset
+    pub offset: Option<usize>,
+
+    #[arg(short, long, default_value = "2.0")]
+    /// Minimum interval to requery if terminal size has been adjusted; ignored when `--columns` is specified
+    pub update: Option<f32>,
+
+    #[arg(long)]
+    /// Join wrapped segments of a line with the given string instead of a newline
+    pub output_delimiter: Option<String>,
+
+    #[arg(long)]
+    /// Re-open active terminal attributes (e.g., colors) on wrapped continuation lines
+    pub reopen: bool,
+
+    #[arg(long)]
+    /// Treat East Asian ambiguous-width characters as two columns [default: detected from locale]
+    pub ambiguous_wide: Option<bool>,
+
+    #[arg(long)]
+    /// Repeat leading diff/quote markers (`+`, `-`, `>`, `| `) on wrapped continuation lines
+    pub diff: bool,
+
+    #[arg(long, value_name = "N[:SEP]")]
+    /// Take each line's limit from its Nth field (split by SEP, default whitespace), then strip the field
+    pub width_field: Option<WidthField>,
+
+    #[arg(
+        long,
+        value_name = "BUCKET",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "10",
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+    )]
+    /// Report a histogram of line widths, bucketed by the given number of columns, instead of chopping
+    pub histogram: Option<usize>,
+
+    #[arg(long, requires = "histogram")]
+    /// Render histogram counts as bars scaled to terminal width (or `--columns`)
+    pub bars: bool,
+
+    #[arg(long)]
+    /// Only output lines exceeding the limit, suppressing those that already fit
+    pub only_long: bool,
+
+    #[arg(long)]
+    /// Prefix each output line with its input line number
+    pub line_numbers: bool,
+
+    #[arg(long, value_name = "REGEX")]
+    /// Chop at the start of the last match of a pattern, limited by terminal width (or `--columns`)
+    pub break_at: Option<regex::Regex>,
+
+    #[arg(long, requires = "break_at")]
+    /// Chop at the end of `--break-at` matches instead of their start
+    pub break_at_end: bool,
+
+    #[arg(long, value_name = "CHAR", num_args = 0..=1, default_missing_value = "\u{2502}")]
+    /// Append a guide character at the chop boundary of every line, padding shorter lines
+    pub guide: Option<String>,
+
+    #[arg(long)]
+    /// Measure backspace overstrike sequences (e.g., man page bold and underline) as single cells
+    pub overstrike: bool,
+
+    #[arg(long, alias = "max-lines", value_name = "N")]
+    /// Stop after emitting the given number of output lines, counting each wrapped segment
+    pub head: Option<usize>,
+
+    #[arg(long, value_name = "START:END")]
+    /// Only chop input lines within the given range (1-based, inclusive; either bound optional)
+    pub lines: Option<LineRange>,
+
+    #[arg(long, requires = "lines")]
+    /// Pass lines outside of `--lines` through unchanged instead of skipping them
+    pub pass_through: bool,
+
+    #[arg(long)]
+    /// Balance wrapped segments of a line to roughly equal widths, rather than filling each in turn
+    pub even: bool,
+
+    #[arg(long)]
+    /// Remove all ANSI escape sequences (e.g., colors) from the output
+    pub strip_ansi: bool,
+}
+
+/// Field of each line holding that line's width limit, given as `N[:SEP]`.
+///
+/// Fields are numbered from 1 and separated by `SEP`, or by runs of whitespace
+/// when no separator is given.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WidthField {
+    pub index: usize,
+    pub separator: Option<String>,
+}
+
+impl std::str::FromStr for WidthField {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (index, separator) = match s.split_once(':') {
+            Some((_, "")) => return Err("empty field separator".to_string()),
+            Some((index, sep)) => (index, Some(sep.to_string())),
+            None => (s, None),
+        };
+
+        match index.parse() {
+            Ok(0) | Err(_) => Err(format!("invalid field number: {:?}", index)),
+            Ok(index) => Ok(Self { index, separator }),
+        }
+    }
+}
+
+impl WidthField {
+    /// Byte ranges of each field within `line`.
+    fn ranges(&self, line: &str) -> Vec<(usize, usize)> {
+        let mut ranges = Vec::new();
+        match self.separator {
+            Some(ref sep) => {
+                let mut start = 0;
+                for (idx, _) in line.match_indices(sep.as_str()) {
+                    ranges.push((start, idx));
+                    start = idx + sep.len();
+                }
+                ranges.push((start, line.len()));
+            }
+            None => {
+                let mut start = None;
+                for (idx, c) in line.char_indices() {
+                    match (start, c.is_whitespace()) {
+                        (None, false) => start = Some(idx),
+                        (Some(s), true) => {
+                            ranges.push((s, idx));
+                            start = None;
+                        }
+                        _ => {}
+                    }
+                }
+                if let Some(s) = start {
+                    ranges.push((s, line.len()));
+                }
+            }
+        }
+        ranges
+    }
+
+    /// Width limit held by the field, along with the line stripped of that field
+    /// (and one adjacent separator); `None` if the field is missing or not a number.
+    pub fn extract(&self, line: &str) -> Option<(usize, String)> {
+        let ranges = self.ranges(line);
+        let (start, end) = *ranges.get(self.index - 1)?;
+        let limit = line[start..end].trim().parse().ok()?;
+
+        let (cut_start, cut_end) = match (ranges.get(self.index), self.index.checked_sub(2)) {
+            (Some(&(next, _)), None) => (0, next), // including any leading whitespace
+            (Some(&(next, _)), Some(_)) => (start, next),
+            (None, Some(prev)) => (ranges[prev].1, end),
+            (None, None) => (start, end),
+        };
+
+        let mut stripped = String::with_capacity(line.len());
+        stripped.push_str(&line[..cut_start]);
+        stripped.push_str(&line[cut_end..]);
+        Some((limit, stripped))
+    }
+}
+
+/// Inclusive range of input line numbers, given as `START:END` with either bound optional.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LineRange {
+    pub start: usize,
+    pub end: Option<usize>,
+}
+
+impl std::str::FromStr for LineRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |bound: &str| match bound.parse::<usize>() {
+            Ok(0) | Err(_) => Err(format!("invalid line number: {:?}", bound)),
+            Ok(n) => Ok(n),
+        };
+
+        let (start, end) = s.split_once(':').unwrap_or((s, s));
+        let start = if start.is_empty() { 1 } else { parse(start)? };
+        let end = if end.is_empty() {
+            None
+        } else {
+            Some(parse(end)?)
+        };
+
+        match end {
+            Some(end) if end < start => Err(format!("line range ends before it starts: {:?}", s)),
+            _ => Ok(Self { start, end }),
+        }
+    }
+}
+
+impl LineRange {
+    pub fn contains(&self, lineno: usize) -> bool {
+        lineno >= self.start && self.end.is_none_or(|end| lineno <= end)
+    }
+}
+
+struct TimedCache {
+    value: usize,
+    prev_timestamp: SystemTime,
+    timeout: Duration,
+}
+impl TimedCache {
+    fn new(timeout: Duration) -> Self {
+        Self {
+            value: 0,
+            prev_timestamp: UNIX_EPOCH,
+            timeout,
+        }
+    }
+
+    fn get(&self) -> Option<usize> {
+        let t = SystemTime::now();
+        match t.duration_since(self.prev_timestamp) {
+            Ok(delta) => {
+                if delta <= self.timeout {
+                    Some(self.value)
+                } else {
+                    None
+                }
+            }
+            Err(_) => None,
+        }
+    }
+    fn set(&mut self, value: usize) {
+        self.value = value;
+        self.prev_timestamp = SystemTime::now();
+    }
+}
+
+/// Determines the column limit for each chopped segment.
+pub struct Limiter {
+    config: Config,
+    get_termsize: fn() -> Option<termsize::Size>,
+    cache: TimedCache,
+}
+
+impl Limiter {
+    pub fn new(mut config: Config) -> Self {
+        config
+            .ambiguous_wide
+            .get_or_insert_with(locale_prefers_wide);
+        let nanos = (config.update.unwrap_or(2.0) / 1e9) as u64;
+        Limiter {
+            config,
+            get_termsize: query_termsize,
+            cache: TimedCache::new(Duration::from_nanos(nanos)),
+        }
+    }
+
+    pub fn get_limit(&mut self) -> usize {
+        let default = {
+            match self.config.columns {
+                Some(sz) => sz,
+                None => match self.cache.get() {
+                    Some(sz) => sz,
+                    None => match (self.get_termsize)() {
+                        Some(x) => {
+                            let cols = x.cols as usize;
+                            self.cache.set(cols);
+                            cols
+                        }
+                        None => columns_from_env().unwrap_or(80),
+                    },
+                },
+            }
+        };
+
+        match self.config.multiple {
+            Some(0) => default,
+            Some(mult) => {
+                // an offset past the width still picks the boundaries, and
+                // without a boundary within the width, the width is kept
+                let offs = self.config.offset.unwrap_or(0) % mult;
+                default
+                    .checked_sub(offs)
+                    .map_or(default, |rest| (rest / mult) * mult + offs)
+            }
+            None => default,
+        }
+    }
+}
+
+/// Query the terminal size, preferring the console backend on Windows.
+fn query_termsize() -> Option<termsize::Size> {
+    #[cfg(windows)]
+    if let Some(size) = console::size() {
+        return Some(size);
+    }
+
+    termsize::get()
+}
+
+/// Fallback width exported by shells and CI environments when no terminal is attached.
+fn columns_from_env() -> Option<usize> {
+    parse_columns(std::env::var("COLUMNS").ok()?.as_str())
+}
+
+fn parse_columns(value: &str) -> Option<usize> {
+    match value.trim().parse() {
+        Ok(0) | Err(_) => None,
+        Ok(cols) => Some(cols),
+    }
+}
+
+#[cfg(windows)]
+mod console {
+    use std::ptr::null_mut;
+    use winapi::um::fileapi::{CreateFileA, OPEN_EXISTING};
+    use winapi::um::handleapi::{CloseHandle, INVALID_HANDLE_VALUE};
+    use winapi::um::wincon::{GetConsoleScreenBufferInfo, CONSOLE_SCREEN_BUFFER_INFO};
+    use winapi::um::winnt::{FILE_SHARE_WRITE, GENERIC_READ, GENERIC_WRITE};
+
+    /// Size of the visible console window, queried through `CONOUT$` so that
+    /// it is found even when stdout is redirected (e.g., within PowerShell pipelines).
+    pub fn size() -> Option<termsize::Size> {
+        unsafe {
+            let handle = CreateFileA(
+                b"CONOUT$\0".as_ptr() as *const i8,
+                GENERIC_READ | GENERIC_WRITE,
+                FILE_SHARE_WRITE,
+                null_mut(),
+                OPEN_EXISTING,
+                0,
+                null_mut(),
+            );
+            if handle == INVALID_HANDLE_VALUE {
+                return None;
+            }
+
+            let mut info: CONSOLE_SCREEN_BUFFER_INFO = std::mem::zeroed();
+            let ok = GetConsoleScreenBufferInfo(handle, &mut info);
+            CloseHandle(handle);
+            if ok == 0 {
+                return None;
+            }
+
+            let window = info.srWindow;
+            Some(termsize::Size {
+                rows: (window.Bottom - window.Top + 1) as u16,
+                cols: (window.Right - window.Left + 1) as u16,
+            })
+        }
+    }
+}
+
+/// Select Graphic Rendition sequence restoring default terminal attributes.
+pub const SGR_RESET: &str = "\x1b[0m";
+
+/// Length in bytes of the ANSI escape sequence at the start of `s`, or zero if none.
+fn escape_len(s: &str) -> usize {
+    let bytes = s.as_bytes();
+    if bytes.first() != Some(&0x1b) {
+        return 0;
+    }
+
+    match bytes.get(1) {
+        // CSI: parameters and intermediates, terminated by a final byte
+        Some(b'[') => bytes[2..]
+            .iter()
+            .position(|b| (0x40..=0x7e).contains(b))
+            .map_or(s.len(), |i| i + 3),
+        // OSC: terminated by BEL or ST
+        Some(b']') => {
+            let mut i = 2;
+            while i < bytes.len() {
+                match bytes[i] {
+                    0x07 => return i + 1,
+                    0x1b if bytes.get(i + 1) == Some(&b'\\') => return i + 2,
+                    _ => i += 1,
+                }
+            }
+            s.len()
+        }
+        Some(b) if b.is_ascii() => 2,
+        _ => 1,
+    }
+}
+
+/// Copy of `s` with all ANSI escape sequences removed.
+pub fn strip_ansi(s: &str) -> String {
+    let mut stripped = String::with_capacity(s.len());
+    let mut idx = 0;
+    while idx < s.len() {
+        let len = escape_len(&s[idx..]);
+        if len > 0 {
+            idx += len;
+            continue;
+        }
+
+        let c = s[idx..].chars().next().unwrap_or_default();
+        stripped.push(c);
+        idx += c.len_utf8();
+    }
+    stripped
+}
+
+/// Update the list of active SGR sequences with those found within `s`.
+fn track_sgr(active: &mut Vec<String>, s: &str) {
+    let mut idx = 0;
+    while idx < s.len() {
+        let len = escape_len(&s[idx..]);
+        if len == 0 {
+            idx += s[idx..].chars().next().map_or(1, char::len_utf8);
+            continue;
+        }
+
+        let seq = &s[idx..idx + len];
+        if let Some(params) = seq.strip_prefix("\x1b[").and_then(|p| p.strip_suffix('m')) {
+            if params.is_empty() || params == "0" || params.starts_with("0;") {
+                active.clear();
+            }
+            if !params.is_empty() && params != "0" {
+                active.push(seq.to_string());
+            }
+        }
+        idx += len;
+    }
+}
+
+/// Whether the current locale is one (Chinese, Japanese, Korean) whose terminals
+/// render East Asian ambiguous-width characters as two columns.
+pub fn locale_prefers_wide() -> bool {
+    ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.is_empty())
+        .is_some_and(|locale| is_cjk_locale(&locale))
+}
+
+fn is_cjk_locale(locale: &str) -> bool {
+    let locale = locale.to_ascii_lowercase();
+    ["zh", "ja", "ko"]
+        .iter()
+        .any(|lang| locale.starts_with(lang))
+}
+
+/// Display width of a grapheme, counting ambiguous-width characters as two
+/// columns when `ambiguous_wide` is set.
+fn grapheme_width(g: &str, ambiguous_wide: bool) -> usize {
+    if ambiguous_wide {
+        g.width_cjk()
+    } else {
+        g.width()
+    }
+}
+
+/// Next cell of `s`: a grapheme, extended over any backspace overstrike sequences
+/// (e.g., `X\x08X` bold or `_\x08X` underline) when `overstrike` is set.
+///
+/// Returns the cell along with the grapheme it displays.
+fn next_cell(s: &str, overstrike: bool) -> (&str, &str) {
+    let mut graphemes = s.grapheme_indices(true);
+    let mut shown = graphemes.next().map_or("", |(_, g)| g);
+    let mut len = shown.len();
+
+    while overstrike && s[len..].starts_with('\x08') {
+        match graphemes.nth(1) {
+            Some((idx, g)) => {
+                shown = g;
+                len = idx + g.len();
+            }
+            None => break,
+        }
+    }
+    (&s[..len], shown)
+}
+
+/// Display width of `s` in columns, excluding ANSI escape sequences.
+pub fn display_width(s: &str, config: &Config) -> usize {
+    let ambiguous_wide = config.ambiguous_wide.unwrap_or(false);
+    let mut width = 0;
+    let mut idx = 0;
+    while idx < s.len() {
+        let esc = escape_len(&s[idx..]);
+        if esc > 0 {
+            idx += esc;
+            continue;
+        }
+
+        let (cell, shown) = next_cell(&s[idx..], config.overstrike);
+        width += grapheme_width(shown, ambiguous_wide);
+        idx += cell.len();
+    }
+    width
+}
+
+/// Byte index at which `s` should be chopped to fit within `limit` columns,
+/// preferring the position of the last delimiter or `--break-at` match when configured.
+///
+/// ANSI escape sequences occupy no columns and are never split.
+pub fn get_end(s: &str, limit: usize, config: &Config) -> usize {
+    let delim = &config.delimiter;
+    let ambiguous_wide = config.ambiguous_wide.unwrap_or(false);
+    let s_len = s.len();
+
+    if s_len < limit {
+        return s_len; // already fits in allowed space
+    }
+
+    let mut fit = s_len; // default if no delimiter found
+    let mut trial = None;
+    let mut col: usize = 0;
+    let mut c_idx = 0;
+
+    while c_idx < s_len {
+        let esc = escape_len(&s[c_idx..]);
+        if esc > 0 {
+            c_idx += esc;
+            continue;
+        }
+
+        let (cell, c_val) = next_cell(&s[c_idx..], config.overstrike);
+        let width = grapheme_width(c_val, ambiguous_wide);
+        if col + width > limit {
+            fit = c_idx; // wide characters are pushed over
+            break;
+        }
+
+        col += width;
+
+        if let Some(ref d) = delim {
+            if c_val == d {
+                trial = Some(c_idx + cell.len()); // keep delimiter on this segment
+            }
+        }
+
+        c_idx += cell.len();
+    }
+
+    // a line filling the limit exactly still breaks at its last delimiter, as
+    // it always has, but `--break-at` only applies once the limit is exceeded
+    if fit == s_len && (col < limit || delim.is_none()) {
+        return s_len; // fits within the limit, despite its byte length
+    }
+
+    if let Some(re) = config.break_at.as_ref().filter(|_| fit < s_len) {
+        let at_end = config.break_at_end;
+        let pattern = re
+            .find_iter(s)
+            .map(|m| if at_end { m.end() } else { m.start() })
+            .filter(|&pos| pos > 0 && pos <= fit)
+            .last();
+        trial = trial.max(pattern);
+    }
+
+    match trial.unwrap_or(fit) {
+        // always make progress, even when a single grapheme exceeds the limit
+        0 => s.grapheme_indices(true).nth(1).map_or(s_len, |(i, _)| i),
+        end => end,
+    }
+}
+
+/// Leading diff or quote marker of `line`, to be repeated on wrapped continuations.
+fn diff_marker(line: &str) -> &'static str {
+    ["| ", "+", "-", ">"]
+        .into_iter()
+        .find(|marker| line.starts_with(marker))
+        .unwrap_or("")
+}
+
+/// Iterator adapter yielding the chopped segments of each line produced by `lines`.
+///
+/// When wrapping is disabled only the first segment of each line is yielded.
+/// Trailing whitespace is trimmed, and lines left empty yield no segments.
+/// Segments cut while terminal attributes are active end with [`SGR_RESET`].
+///
+/// ```
+/// use chop_core::{Chopper, Config, Limiter};
+///
+/// let config = Config {
+///     wrap: Some(true),
+///     columns: Some(4),
+///     ..Default::default()
+/// };
+/// let mut limiter = Limiter::new(config);
+/// let segments: Vec<String> = Chopper::new(&mut limiter, "abcdefgh\nij".lines()).collect();
+/// assert_eq!(segments, ["abcd", "efgh", "ij"]);
+/// ```
+pub struct Chopper<'a, I: Iterator> {
+    limiter: &'a mut Limiter,
+    lines: I,
+    line: Option<String>,
+    line_limit: Option<usize>,
+    pos: usize,
+    sgr: Vec<String>,
+    marker: &'static str,
+}
+
+impl<'a, I> Chopper<'a, I>
+where
+    I: Iterator,
+    I::Item: AsRef<str>,
+{
+    pub fn new(limiter: &'a mut Limiter, lines: I) -> Self {
+        Self {
+            limiter,
+            lines,
+            line: None,
+            line_limit: None,
+            pos: 0,
+            sgr: Vec::new(),
+            marker: "",
+        }
+    }
+}
+
+impl<I> Iterator for Chopper<'_, I>
+where
+    I: Iterator,
+    I::Item: AsRef<str>,
+{
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        loop {
+            if let Some(ref line) = self.line {
+                let s = &line[self.pos..];
+                if !s.is_empty() {
+                    let marker = if self.pos > 0 { self.marker } else { "" };
+                    let full = match self.line_limit {
+                        Some(limit) => limit,
+                        None => self.limiter.get_limit(),
+                    };
+                    let mut limit = full.saturating_sub(marker.width());
+
+                    let config = &self.limiter.config;
+                    if config.even && config.wrap.unwrap_or(false) && limit > 0 {
+                        // spread the remainder evenly over the segments it requires
+                        let remaining = display_width(s, config);
+                        let count = remaining.div_ceil(limit);
+                        if count > 1 {
+                            limit = remaining.div_ceil(count);
+                        }
+                    }
+                    let end = get_end(s, limit, &self.limiter.config);
+
+                    let mut segment = marker.to_string();
+                    if self.pos > 0 && self.limiter.config.reopen {
+                        segment.extend(self.sgr.iter().map(String::as_str));
+                    }
+                    segment.push_str(&s[..end]);
+
+                    track_sgr(&mut self.sgr, &s[..end]);
+                    if end < s.len() && !self.sgr.is_empty() {
+                        segment.push_str(SGR_RESET);
+                    }
+
+                    if let Some(ref guide) = self.limiter.config.guide {
+                        let width = display_width(&segment, &self.limiter.config);
+                        segment.push_str(&" ".repeat(full.saturating_sub(width)));
+                        segment.push_str(guide);
+                    }
+
+                    if self.limiter.config.wrap.unwrap_or(false) {
+                        self.pos += end;
+                    } else {
+                        self.line = None;
+                    }
+                    return Some(segment);
+                }
+            }
+
+            let mut line = self.lines.next()?.as_ref().trim_end().to_string();
+            if self.limiter.config.strip_ansi {
+                line = strip_ansi(&line);
+            }
+            self.line_limit = None;
+            if let Some(ref field) = self.limiter.config.width_field {
+                if let Some((limit, stripped)) = field.extract(&line) {
+                    self.line_limit = Some(limit);
+                    line = stripped;
+                }
+            }
+
+            self.marker = match self.limiter.config.diff {
+                true => diff_marker(&line),
+                false => "",
+            };
+            self.line = Some(line);
+            self.pos = 0;
+            self.sgr.clear();
+        }
+    }
+}
+
+/// Chop every line read from `input`, writing the resulting segments to `output`.
+pub fn run(
+    limiter: &mut Limiter,
+    input: &mut impl std::io::BufRead,
+    output: &mut impl std::io::Write,
+) -> std::io::Result<()> {
+    let sep = limiter
+        .config
+        .output_delimiter
+        .clone()
+        .unwrap_or_else(|| "\n".to_string());
+    let only_long = limiter.config.only_long;
+    let line_numbers = limiter.config.line_numbers;
+    let head = limiter.config.head;
+    let lines = limiter.config.lines;
+    let pass_through = limiter.config.pass_through;
+    let strip = limiter.config.strip_ansi;
+
+    let mut lineno: usize = 0;
+    let mut emitted: usize = 0;
+    let mut buffer = String::new();
+    loop {
+        if head.is_some_and(|n| emitted >= n) {
+            return Ok(());
+        }
+
+        buffer.clear();
+        let nread = input.read_line(&mut buffer)?;
+
+        // in detached stdin state (e.g., daemon), treat as okay
+        // TODO: determine if zero-char read should be an error
+        if nread == 0 {
+            return Ok(());
+        }
+
+        lineno += 1;
+        if let Some(range) = lines.filter(|range| !range.contains(lineno)) {
+            let result = match pass_through {
+                true if strip => writeln!(output, "{}", strip_ansi(buffer.trim_end())),
+                true => writeln!(output, "{}", buffer.trim_end_matches(['\r', '\n'])),
+                false if range.end.is_some_and(|end| lineno > end) => return Ok(()),
+                false => continue,
+            };
+            if let Err(e) = result.and_then(|_| output.flush()) {
+                return match e.kind() {
+                    std::io::ErrorKind::BrokenPipe => Ok(()),
+                    _ => Err(e),
+                };
+            }
+            continue;
+        }
+
+        if only_long && display_width(buffer.trim_end(), &limiter.config) <= limiter.get_limit() {
+            continue;
+        }
+
+        let mut prefix = match line_numbers {
+            true => format!("{}:", lineno),
+            false => String::new(),
+        };
+
+        let mut segments = Chopper::new(limiter, std::iter::once(buffer.as_str())).peekable();
+        while let Some(subs) = segments.next() {
+            emitted += 1;
+            let end = match segments.peek() {
+                Some(_) if head != Some(emitted) => sep.as_str(),
+                _ => "\n",
+            };
+            let start = std::mem::take(&mut prefix);
+            let written = write!(output, "{}{}{}", start, subs, end).and_then(|_| output.flush());
+            if let Err(e) = written {
+                match e.kind() {
+                    std::io::ErrorKind::BrokenPipe => {
+                        return Ok(());
+                    }
+                    _ => {
+                        return Err(e);
+                    }
+                }
+            }
+
+            if head == Some(emitted) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Read all lines from `input`, writing a histogram of their display widths to `output`.
+pub fn report_histogram(
+    limiter: &mut Limiter,
+    input: &mut impl std::io::BufRead,
+    output: &mut impl std::io::Write,
+) -> std::io::Result<()> {
+    let bucket = limiter.config.histogram.unwrap_or(10);
+
+    let mut counts: Vec<usize> = Vec::new();
+    let mut buffer = String::new();
+    loop {
+        buffer.clear();
+        if input.read_line(&mut buffer)? == 0 {
+            break;
+        }
+
+        let idx = display_width(buffer.trim_end(), &limiter.config) / bucket;
+        if counts.len() <= idx {
+            counts.resize(idx + 1, 0);
+        }
+        counts[idx] += 1;
+    }
+
+    let label_width = ((counts.len() * bucket).saturating_sub(1))
+        .to_string()
+        .len();
+    let max_count = counts.iter().copied().max().unwrap_or(0);
+    let count_width = max_count.to_string().len();
+    let bar_space = limiter
+        .get_limit()
+        .saturating_sub(2 * label_width + count_width + 3);
+
+    for (idx, count) in counts.iter().enumerate() {
+        let lo = idx * bucket;
+        write!(
+            output,
+            "{:>lw$}-{:<lw$} {:>cw$}",
+            lo,
+            lo + bucket - 1,
+            count,
+            lw = label_width,
+            cw = count_width,
+        )?;
+        if limiter.config.bars && *count > 0 {
+            let bar = (count * bar_space / max_count).max(1);
+            write!(output, " {}", "#".repeat(bar))?;
+        }
+        writeln!(output)?;
+    }
+
+    output.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_termsize_10() -> Option<termsize::Size> {
+        Some(termsize::Size { rows: 0, cols: 10 })
+    }
+
+    fn get_termsize_30() -> Option<termsize::Size> {
+        Some(termsize::Size { rows: 0, cols: 30 })
+    }
+
+    #[test]
+    /// Verify that lines are chopped after terminal bounds,
+    /// assuming terminal is 10 columns wide.
+    fn test_default() {
+        let config = Config::default();
+        let mut limiter = Limiter {
+            config: config.clone(),
+            get_termsize: get_termsize_10,
+            cache: TimedCache::new(Duration::from_secs(1)),
+        };
+
+        let input: String = format!(
+            "{}\n{}\n",
+            "[10char-A][10char-B][10char-C][10char-D]", // line 1
+            "[10char-E][10char-F]",                     // line 2
+        );
+        let exp: String = format!(
+            "{}\n{}\n",
+            "[10char-A]", // line 1
+            "[10char-E]", // line 2
+        );
+
+        let mut output: Vec<u8> = Vec::new();
+        run(&mut limiter, &mut input.as_bytes(), &mut output).unwrap();
+
+        let output_string = String::from_utf8(output).unwrap();
+        assert_eq!(exp, output_string, "\n{}\n", output_string);
+    }
+
+    #[test]
+    /// Verify that `$COLUMNS` values are only accepted when positive integers.
+    fn test_parse_columns() {
+        assert_eq!(Some(120), parse_columns("120"));
+        assert_eq!(Some(40), parse_columns(" 40\n"));
+        assert_eq!(None, parse_columns("0"));
+        assert_eq!(None, parse_columns("-1"));
+        assert_eq!(None, parse_columns("wide"));
+        assert_eq!(None, parse_columns(""));
+    }
+
+    #[test]
+    /// Verify that only Chinese, Japanese, and Korean locales prefer wide ambiguous characters.
+    fn test_is_cjk_locale() {
+        assert!(is_cjk_locale("ja_JP.UTF-8"));
+        assert!(is_cjk_locale("zh_TW.UTF-8"));
+        assert!(is_cjk_locale("KO_KR"));
+        assert!(!is_cjk_locale("en_US.UTF-8"));
+        assert!(!is_cjk_locale("C"));
+    }
+
+    #[test]
+    /// Verify that ambiguous-width characters are counted according to policy.
+    fn test_ambiguous_wide() {
+        let input = "\u{00b1}\u{00b1}\u{00b1}\u{00b1}\u{00b1}\u{00b1}\n"; // six plus-minus signs
+
+        for (ambiguous_wide, exp) in [(false, "\u{00b1}".repeat(4)), (true, "\u{00b1}".repeat(2))] {
+            let config = Config {
+                columns: Some(4),
+                ambiguous_wide: Some(ambiguous_wide),
+                ..Default::default()
+            };
+            let mut limiter = Limiter::new(config);
+
+            let mut output: Vec<u8> = Vec::new();
+            run(&mut limiter, &mut input.as_bytes(), &mut output).unwrap();
+
+            let output_string = String::from_utf8(output).unwrap();
+            assert_eq!(exp + "\n", output_string, "\n{}\n", output_string);
+        }
+    }
+
+    #[test]
+    /// Verify that width fields are parsed and stripped from their lines.
+    fn test_width_field_extract() {
+        let first: WidthField = "1".parse().unwrap();
+        let last: WidthField = "3:,".parse().unwrap();
+        assert_eq!(None, first.separator);
+        assert_eq!(Some(",".to_string()), last.separator);
+        assert!("0".parse::<WidthField>().is_err());
+        assert!("x:,".parse::<WidthField>().is_err());
+        assert!("2:".parse::<WidthField>().is_err());
+
+        assert_eq!(
+            Some((12, "some text".to_string())),
+            first.extract("  12  some text")
+        );
+        assert_eq!(Some((5, "a,b".to_string())), last.extract("a,b,5"));
+        assert_eq!(None, last.extract("a,b"));
+        assert_eq!(None, first.extract("wide text"));
+    }
+
+    #[test]
+    /// Verify that each line is chopped to the width held in its field.
+    fn test_wrap_width_field() {
+        let config = Config {
+            wrap: Some(true),
+            columns: Some(30),
+            width_field: Some("1:\t".parse().unwrap()),
+            ..Default::default()
+        };
+        let mut limiter = Limiter::new(config);
+
+        let input: String = format!(
+            "{}\n{}\n{}\n",
+            "10\t[10char-A][10char-B]",                 // line 1 (hinted)
+            "20\t[10char-C][10char-D][10char-E]",       // line 2 (hinted)
+            "[10char-F][10char-G][10char-H][10char-I]", // line 3 (no hint)
+        );
+
+        let exp: String = format!(
+            "{}\n{}\n{}\n{}\n{}\n{}\n",
+            "[10char-A]",                     // line 1
+            "[10char-B]",                     // line 1 (wrap)
+            "[10char-C][10char-D]",           // line 2
+            "[10char-E]",                     // line 2 (wrap)
+            "[10char-F][10char-G][10char-H]", // line 3
+            "[10char-I]",                     // line 3 (wrap)
+        );
+
+        let mut output: Vec<u8> = Vec::new();
+        run(&mut limiter, &mut input.as_bytes(), &mut output).unwrap();
+
+        let output_string = String::from_utf8(output).unwrap();
+        assert_eq!(exp, output_string, "\n{}\n", output_string);
+    }
+
+    #[test]
+    /// Verify that line ranges accept optional bounds, rejecting invalid ones.
+    fn test_line_range() {
+        let range: LineRange = "3:5".parse().unwrap();
+        assert!(!range.contains(2) && range.contains(3) && range.contains(5) && !range.contains(6));
+        assert_eq!(
+            LineRange {
+                start: 1,
+                end: Some(4)
+            },
+            ":4".parse().unwrap()
+        );
+        assert_eq!(
+            LineRange {
+                start: 4,
+                end: None
+            },
+            "4:".parse().unwrap()
+        );
+        assert_eq!(
+            LineRange {
+                start: 7,
+                end: Some(7)
+            },
+            "7".parse().unwrap()
+        );
+        assert!("0:3".parse::<LineRange>().is_err());
+        assert!("5:3".parse::<LineRange>().is_err());
+        assert!("a:b".parse::<LineRange>().is_err());
+    }
+
+    #[test]
+    /// Verify that lines are wrapped (and continued) at terminal bounds,
+    /// assuming terminal is 30 columns wide.
+    fn test_wrap() {
+        let config = Config {
+            wrap: Some(true),
+            ..Default::default()
+        };
+        let mut limiter = Limiter {
+            config: config.clone(),
+            get_termsize: get_termsize_30,
+            cache: TimedCache::new(Duration::from_secs(1)),
+        };
+
+        let input: String = format!(
+            "{}\n{}\n",
+            "[10char-A][10char-B][10char-C][10char-D]", // line 1
+            "[10char-E][10char-F]",                     // line 2
+        );
+
+        let exp: String = format!(
+            "{}\n{}\n{}\n",
+            "[10char-A][10char-B][10char-C]", // line 1
+            "[10char-D]",                     // line 1 (wrap)
+            "[10char-E][10char-F]",           // line 2
+        );
+
+        let mut output: Vec<u8> = Vec::new();
+        run(&mut limiter, &mut input.as_bytes(), &mut output).unwrap();
+
+        let output_string = String::from_utf8(output).unwrap();
+        assert_eq!(exp, output_string, "\n{}\n", output_string);
+    }
+
+    #[test]
+    /// Verify that supplying a `columns` option overrides terminal bounds
+    /// assuming columns is set larger than terminal size.
+    fn test_wrap_chars_when_larger() {
+        let config = Config {
+            wrap: Some(true),
+            columns: Some(20),
+            ..Default::default()
+        };
+        let mut limiter = Limiter {
+            config: config.clone(),
+            get_termsize: get_termsize_10,
+            cache: TimedCache::new(Duration::from_secs(1)),
+        };
+
+        let input: String = format!(
+            "{}\n{}\n",
+            "[10char-A][10char-B][10char-C][10char-D]", // line 1
+            "[10char-E][10char-F]",                     // line 2
+        );
+
+        let exp: String = format!(
+            "{}\n{}\n{}\n",
+            "[10char-A][10char-B]", // line 1
+            "[10char-C][10char-D]", // line 1 (wrap)
+            "[10char-E][10char-F]", // line 2
+        );
+
+        let mut output: Vec<u8> = Vec::new();
+        run(&mut limiter, &mut input.as_bytes(), &mut output).unwrap();
+
+        let output_string = String::from_utf8(output).unwrap();
+        assert_eq!(exp, output_string, "\n{}\n", output_string);
+    }
+
+    #[test]
+    /// Verify that supplying a `columns` option overrides terminal bounds
+    /// assuming columns is set smaller than terminal size.
+    fn test_wrap_chars_when_smaller() {
+        let config = Config {
+            wrap: Some(true),
+            columns: Some(20),
+            ..Default::default()
+        };
+        let mut limiter = Limiter {
+            config: config.clone(),
+            get_termsize: get_termsize_30,
+            cache: TimedCache::new(Duration::from_secs(1)),
+        };
+
+        let input: String = format!(
+            "{}\n{}\n",
+            "[10char-A][10char-B][10char-C][10char-D]", // line 1
+            "[10char-E][10char-F]",                     // line 2
+        );
+
+        let exp: String = format!(
+            "{}\n{}\n{}\n",
+            "[10char-A][10char-B]", // line 1
+            "[10char-C][10char-D]", // line 1 (wrap)
+            "[10char-E][10char-F]", // line 2
+        );
+
+        let mut output: Vec<u8> = Vec::new();
+        run(&mut limiter, &mut input.as_bytes(), &mut output).unwrap();
+
+        let output_string = String::from_utf8(output).unwrap();
+        assert_eq!(exp, output_string, "\n{}\n", output_string);
+    }
+
+    #[test]
+    /// Verify that supplying a `multiple` flag will wrap at the greatest
+    /// multiple that is strictly less than the specified column limit.
+    fn test_wrap_chars_multiple() {
+        let config = Config {
+            wrap: Some(true),
+            columns: Some(55),
+            multiple: Some(20),
+            ..Default::default()
+        };
+        let mut limiter = Limiter {
+            config: config.clone(),
+            get_termsize: get_termsize_30,
+            cache: TimedCache::new(Duration::from_secs(1)),
+        };
+
+        let input: String = format!(
+            "{}\n{}\n{}\n",
+            "[10char-A][10char-B][10char-C][10char-D][10char-E][10char-F]", // line 1
+            "[10char-G][10char-H][10char-I]",                               // line 2
+            "[10char-J][10char-K][10char-L][10char-M][10char-N]",           // line 3
+        );
+
+        let exp: String = format!(
+            "{}\n{}\n{}\n{}\n{}\n",
+            "[10char-A][10char-B][10char-C][10char-D]", // line 1
+            "[10char-E][10char-F]",                     // line 1 (wrap)
+            "[10char-G][10char-H][10char-I]",           // line 2
+            "[10char-J][10char-K][10char-L][10char-M]", // line 3
+            "[10char-N]",                               // line 3 (wrap)
+        );
+
+        let mut output: Vec<u8> = Vec::new();
+        run(&mut limiter, &mut input.as_bytes(), &mut output).unwrap();
+
+        let output_string = String::from_utf8(output).unwrap();
+        assert_eq!(exp, output_string, "\n{}\n", output_string);
+    }
+
+    #[test]
+    fn test_wrap_chars_multiple_offset() {
+        let config = Config {
+            wrap: Some(true),
+            columns: Some(55),
+            multiple: Some(20),
+            offset: Some(10),
+            ..Default::default()
+        };
+        let mut limiter = Limiter {
+            config: config.clone(),
+            get_termsize: get_termsize_30,
+            cache: TimedCache::new(Duration::from_secs(1)),
+        };
+
+        let input: String = format!(
+            "{}\n{}\n{}\n",
+            "[10char-A][10char-B][10char-C][10char-D][10char-E][10char-F]", // line 1
+            "[10char-G][10char-H][10char-I]",                               // line 2
+            "[10char-J][10char-K][10char-L][10char-M][10char-N]",           // line 3
+        );
+
+        let exp: String = format!(
+            "{}\n{}\n{}\n{}\n",
+            "[10char-A][10char-B][10char-C][10char-D][10char-E]", // line 1
+            "[10char-F]",                                         // line 1 (wrap)
+            "[10char-G][10char-H][10char-I]",                     // line 2
+            "[10char-J][10char-K][10char-L][10char-M][10char-N]", // line 3
+        );
+
+        let mut output: Vec<u8> = Vec::new();
+        run(&mut limiter, &mut input.as_bytes(), &mut output).unwrap();
+
+        let output_string = String::from_utf8(output).unwrap();
+        assert_eq!(exp, output_string, "\n{}\n", output_string);
+    }
+
+    #[test]
+    /// Verify that an offset larger than the width picks boundaries by its
+    /// remainder, rather than reaching past the width.
+    fn test_multiple_offset_past_width() {
+        let limit = |columns, multiple, offset| {
+            Limiter::new(Config {
+                columns: Some(columns),
+                multiple: Some(multiple),
+                offset: Some(offset),
+                ..Default::default()
+            })
+            .get_limit()
+        };
+        assert_eq!(limit(55, 20, 10), 50);
+        assert_eq!(limit(55, 20, 30), 50);
+        assert_eq!(limit(10, 3, 20), 8);
+        assert_eq!(limit(10, 3, 9), 9);
+        assert_eq!(limit(1, 3, 2), 1);
+    }
+
+    #[test]
+    fn test_default_chars_multiple() {
+        let config = Config {
+            wrap: Some(false),
+            columns: Some(55),
+            multiple: Some(20),
+            ..Default::default()
+        };
+        let mut limiter = Limiter {
+            config: config.clone(),
+            get_termsize: get_termsize_30,
+            cache: TimedCache::new(Duration::from_secs(1)),
+        };
+
+        let input: String = format!(
+            "{}\n{}\n{}\n",
+            "[10char-A][10char-B][10char-C][10char-D][10char-E][10char-F]", // line 1
+            "[10char-G][10char-H][10char-I]",                               // line 2
+            "[10char-J][10char-K][10char-L][10char-M][10char-N]",           // line 3
+        );
+
+        let exp: String = format!(
+            "{}\n{}\n{}\n",
+            "[10char-A][10char-B][10char-C][10char-D]", // line 1
+            "[10char-G][10char-H][10char-I]",           // line 2
+            "[10char-J][10char-K][10char-L][10char-M]", // line 3
+        );
+
+        let mut output: Vec<u8> = Vec::new();
+        run(&mut limiter, &mut input.as_bytes(), &mut output).unwrap();
+
+        let output_string = String::from_utf8(output).unwrap();
+        assert_eq!(exp, output_string);
+    }
+
+    #[test]
+    fn test_wrap_delimiter() {
+        let config = Config {
+            wrap: Some(true),
+            delimiter: Some("-".to_string()),
+            ..Default::default()
+        };
+        let mut limiter = Limiter {
+            config: config.clone(),
+            get_termsize: get_termsize_30,
+            cache: TimedCache::new(Duration::from_secs(1)),
+        };
+
+        let input: String = format!(
+            "{}\n{}\n{}\n",
+            "[10char-A][10char-B][10char-C][10char-D][10char-E][10char-F]", // line 1
+            "[10char-G][10char-H][10char-I]",                               // line 2
+            "[10char-J][10char-K][10char-L][10char-M][10char-N]",           // line 3
+        );
+
+        let exp: String = format!(
+            "{}\n{}\n{}\n{}\n{}\n{}\n{}\n",
+            "[10char-A][10char-B][10char-",   // line 1
+            "C][10char-D][10char-E][10char-", // line 1 (wrap)
+            "F]",                             // line 1 (wrap)
+            "[10char-G][10char-H][10char-",   // line 2
+            "I]",                             // line 2 (wrap)
+            "[10char-J][10char-K][10char-",   // line 3
+            "L][10char-M][10char-N]",         // line 3 (wrap)
+        );
+
+        let mut output: Vec<u8> = Vec::new();
+        run(&mut limiter, &mut input.as_bytes(), &mut output).unwrap();
+
+        let output_string = String::from_utf8(output).unwrap();
+        assert_eq!(exp, output_string, "\n{}\n", output_string);
+    }
+
+    #[test]
+    fn test_non_ascii_unicode_wide() {
+        let config = Config::default();
+        let mut limiter = Limiter {
+            config: config.clone(),
+            get_termsize: get_termsize_30,
+            cache: TimedCache::new(Duration::from_secs(1)),
+        };
+
+        let c = '🌈';
+        assert_eq!(2, unicode_width::UnicodeWidthChar::width(c).unwrap());
+
+        let input: String = format!(
+            "{}\n{}\n{}\n{}\n{}\n",
+            "[10char-🌈][10char-B][10char-C]",  // line 1 (wide)
+            "[10char-🌈][10char-E][10char-🌈]", // line 2 (wide)
+            "[10-a̐éö̲-🌈][10-a̐éö̲-E][10-a̐éö̲-🌈]", // line 3 (wide and graphemes)
+            "[10char-🌈]",                      // line 4 (wide)
+            "a̐a̐a̐a̐a̐a̐a̐a̐a̐a̐a̐a̐a̐a̐a̐a̐a̐a̐a̐a̐a̐a̐a̐a̐a̐a̐a̐a̐a̐a̐a̐a̐a̐a̐a̐a̐a̐a̐a̐", // line 5 (wide and graphemes)
+        );
+
+        let exp: String = format!(
+            "{}\n{}\n{}\n{}\n{}\n",
+            "[10char-🌈][10char-B][10char-C", // line 1 (chopped two columns)
+            "[10char-🌈][10char-E][10char-",  // line 2 (chopped three columns)
+            "[10-a̐éö̲-🌈][10-a̐éö̲-E][10-a̐éö̲-",  // line 3 (chopped three columns (still))
+            "[10char-🌈]",                    // line 4
+            "a̐a̐a̐a̐a̐a̐a̐a̐a̐a̐a̐a̐a̐a̐a̐a̐a̐a̐a̐a̐a̐a̐a̐a̐a̐a̐a̐a̐a̐a̐", // line 5 (wide and graphemes)
+        );
+
+        let mut output: Vec<u8> = Vec::new();
+        run(&mut limiter, &mut input.as_bytes(), &mut output).unwrap();
+
+        let output_string = String::from_utf8(output).unwrap();
+        assert_eq!(exp, output_string, "\n{}\n", output_string);
+    }
+
+    #[test]
+    /// Verify that wrapped segments are joined by `output_delimiter`,
+    /// while each input line still ends with a newline.
+    fn test_wrap_output_delimiter() {
+        let config = Config {
+            wrap: Some(true),
+            columns: Some(20),
+            output_delimiter: Some(" | ".to_string()),
+            ..Default::default()
+        };
+        let mut limiter = Limiter::new(config);
+
+        let input: String = format!(
+            "{}\n{}\n",
+            "[10char-A][10char-B][10char-C][10char-D][10char-E]", // line 1
+            "[10char-F]",                                         // line 2
+        );
+
+        let exp: String = format!(
+            "{}\n{}\n",
+            "[10char-A][10char-B] | [10char-C][10char-D] | [10char-E]", // line 1
+            "[10char-F]",                                               // line 2
+        );
+
+        let mut output: Vec<u8> = Vec::new();
+        run(&mut limiter, &mut input.as_bytes(), &mut output).unwrap();
+
+        let output_string = String::from_utf8(output).unwrap();
+        assert_eq!(exp, output_string, "\n{}\n", output_string);
+    }
+
+    #[test]
+    /// Verify that escape sequences are not counted towards the limit, and that
+    /// attributes left active by a chop are reset.
+    fn test_sgr_reset() {
+        let config = Config {
+            columns: Some(10),
+            ..Default::default()
+        };
+        let mut limiter = Limiter::new(config);
+
+        let input: String = format!(
+            "{}\n{}\n",
+            "\x1b[31m[10char-A]\x1b[0m[10char-B]", // line 1 (closed before chop)
+            "\x1b[1m\x1b[32m[10char-C][10char-D]", // line 2 (open at chop)
+        );
+
+        let exp: String = format!(
+            "{}\n{}\n",
+            "\x1b[31m[10char-A]\x1b[0m",        // line 1
+            "\x1b[1m\x1b[32m[10char-C]\x1b[0m", // line 2 (reset)
+        );
+
+        let mut output: Vec<u8> = Vec::new();
+        run(&mut limiter, &mut input.as_bytes(), &mut output).unwrap();
+
+        let output_string = String::from_utf8(output).unwrap();
+        assert_eq!(exp, output_string, "\n{:?}\n", output_string);
+    }
+
+    #[test]
+    /// Verify that `reopen` restores active attributes on wrapped continuations.
+    fn test_wrap_sgr_reopen() {
+        let config = Config {
+            wrap: Some(true),
+            columns: Some(10),
+            reopen: true,
+            ..Default::default()
+        };
+        let mut limiter = Limiter::new(config);
+
+        let input: String = format!(
+            "{}\n",
+            "\x1b[33m[10char-A][10char-B]\x1b[0m[10char-C]", // line 1
+        );
+
+        let exp: String = format!(
+            "{}\n{}\n{}\n",
+            "\x1b[33m[10char-A]\x1b[0m", // line 1
+            "\x1b[33m[10char-B]\x1b[0m", // line 1 (wrap)
+            "[10char-C]",                // line 1 (wrap)
+        );
+
+        let mut output: Vec<u8> = Vec::new();
+        run(&mut limiter, &mut input.as_bytes(), &mut output).unwrap();
+
+        let output_string = String::from_utf8(output).unwrap();
+        assert_eq!(exp, output_string, "\n{:?}\n", output_string);
+    }
+
+    #[test]
+    /// Verify that `diff` repeats leading markers on wrapped continuations,
+    /// counting them towards the limit.
+    fn test_wrap_diff() {
+        let config = Config {
+            wrap: Some(true),
+            columns: Some(11),
+            diff: true,
+            ..Default::default()
+        };
+        let mut limiter = Limiter::new(config);
+
+        let input: String = format!(
+            "{}\n{}\n{}\n{}\n",
+            "+[10char-A][10char-B]", // line 1 (addition)
+            "-[10char-C]",           // line 2 (removal, fits)
+            "| [9char-D][9char-E]",  // line 3 (quote)
+            "[10char-F]+[10char-G]", // line 4 (unmarked)
+        );
+
+        let exp: String = format!(
+            "{}\n{}\n{}\n{}\n{}\n{}\n{}\n",
+            "+[10char-A]", // line 1
+            "+[10char-B]", // line 1 (wrap)
+            "-[10char-C]", // line 2
+            "| [9char-D]", // line 3
+            "| [9char-E]", // line 3 (wrap)
+            "[10char-F]+", // line 4
+            "[10char-G]",  // line 4 (wrap)
+        );
+
+        let mut output: Vec<u8> = Vec::new();
+        run(&mut limiter, &mut input.as_bytes(), &mut output).unwrap();
+
+        let output_string = String::from_utf8(output).unwrap();
+        assert_eq!(exp, output_string, "\n{}\n", output_string);
+    }
+
+    #[test]
+    /// Verify that line widths are bucketed, including empty buckets, with
+    /// optional bars scaled to the limit.
+    fn test_histogram() {
+        let config = Config {
+            columns: Some(20),
+            histogram: Some(10),
+            bars: true,
+            ..Default::default()
+        };
+        let mut limiter = Limiter::new(config);
+
+        let input: String = format!(
+            "{}\n{}\n{}\n{}\n",
+            "short",                          // line 1 (0-9)
+            "tiny",                           // line 2 (0-9)
+            "\x1b[1mlittle\x1b[0m",           // line 3 (0-9, escapes not counted)
+            "[10char-A][10char-B][10char-C]", // line 4 (30-39)
+        );
+
+        let exp: String = format!(
+            "{}\n{}\n{}\n{}\n",
+            " 0-9  3 ############", // 3 of 3, scaled to the remaining 12 columns
+            "10-19 0",
+            "20-29 0",
+            "30-39 1 ####",
+        );
+
+        let mut output: Vec<u8> = Vec::new();
+        report_histogram(&mut limiter, &mut input.as_bytes(), &mut output).unwrap();
+
+        let output_string = String::from_utf8(output).unwrap();
+        assert_eq!(exp, output_string, "\n{}\n", output_string);
+    }
+
+    #[test]
+    /// Verify that `only_long` suppresses lines within the limit, and that
+    /// line numbers refer to the input.
+    fn test_only_long_line_numbers() {
+        let config = Config {
+            columns: Some(10),
+            only_long: true,
+            line_numbers: true,
+            ..Default::default()
+        };
+        let mut limiter = Limiter::new(config);
+
+        let input: String = format!(
+            "{}\n{}\n{}\n{}\n",
+            "[10char-A]",           // line 1 (fits)
+            "[10char-B][10char-C]", // line 2 (long)
+            "short",                // line 3 (fits)
+            "[10char-D]!",          // line 4 (long)
+        );
+
+        let exp: String = format!(
+            "{}\n{}\n",
+            "2:[10char-B]", // line 2
+            "4:[10char-D]", // line 4
+        );
+
+        let mut output: Vec<u8> = Vec::new();
+        run(&mut limiter, &mut input.as_bytes(), &mut output).unwrap();
+
+        let output_string = String::from_utf8(output).unwrap();
+        assert_eq!(exp, output_string, "\n{}\n", output_string);
+    }
+
+    #[test]
+    /// Verify that `break_at` chops at the start (or end) of the last match that fits.
+    fn test_wrap_break_at() {
+        let input: String = format!(
+            "{}\n",
+            "ts=12:00 level=info msg=ready", // line 1
+        );
+
+        let pattern = regex::Regex::new("\\s*[a-z]+=").unwrap();
+        for (break_at_end, exp) in [
+            (false, "ts=12:00\n level=info\n msg=ready\n"),
+            (true, "ts=\n12:00 level=\ninfo msg=\nready\n"),
+        ] {
+            let config = Config {
+                wrap: Some(true),
+                columns: Some(12),
+                break_at: Some(pattern.clone()),
+                break_at_end,
+                ..Default::default()
+            };
+            let mut limiter = Limiter::new(config);
+
+            let mut output: Vec<u8> = Vec::new();
+            run(&mut limiter, &mut input.as_bytes(), &mut output).unwrap();
+
+            let output_string = String::from_utf8(output).unwrap();
+            assert_eq!(exp, output_string, "\n{}\n", output_string);
+        }
+
+        // a line exactly as wide as the limit is not split
+        let config = Config {
+            wrap: Some(true),
+            columns: Some(7),
+            break_at: Some(regex::Regex::new(" [a-z]+=").unwrap()),
+            ..Default::default()
+        };
+        let mut output: Vec<u8> = Vec::new();
+        run(
+            &mut Limiter::new(config),
+            &mut "a=1 b=2\n".as_bytes(),
+            &mut output,
+        )
+        .unwrap();
+        assert_eq!("a=1 b=2\n", String::from_utf8(output).unwrap());
+    }
+
+    #[test]
+    /// Verify that `guide` pads every segment to the limit before appending the guide.
+    fn test_wrap_guide() {
+        let config = Config {
+            wrap: Some(true),
+            columns: Some(12),
+            guide: Some("|".to_string()),
+            ..Default::default()
+        };
+        let mut limiter = Limiter::new(config);
+
+        let input: String = format!(
+            "{}\n{}\n",
+            "[10char-A][10char-B]", // line 1
+            "\x1b[1m[🌈]\x1b[0m",   // line 2 (wide and escapes)
+        );
+
+        let exp: String = format!(
+            "{}\n{}\n{}\n",
+            "[10char-A][1|",               // line 1
+            "0char-B]    |",               // line 1 (wrap)
+            "\x1b[1m[🌈]\x1b[0m        |", // line 2
+        );
+
+        let mut output: Vec<u8> = Vec::new();
+        run(&mut limiter, &mut input.as_bytes(), &mut output).unwrap();
+
+        let output_string = String::from_utf8(output).unwrap();
+        assert_eq!(exp, output_string, "\n{}\n", output_string);
+    }
+
+    #[test]
+    /// Verify that `overstrike` measures bold and underline sequences as single cells,
+    /// never splitting them.
+    fn test_wrap_overstrike() {
+        let config = Config {
+            wrap: Some(true),
+            columns: Some(4),
+            overstrike: true,
+            ..Default::default()
+        };
+        let mut limiter = Limiter::new(config);
+
+        let input: String = format!(
+            "{}\n",
+            "N\x08NA\x08AM\x08ME\x08E _\x08l_\x08s", // line 1 (bold, then underline)
+        );
+
+        let exp: String = format!(
+            "{}\n{}\n",
+            "N\x08NA\x08AM\x08ME\x08E", // line 1
+            " _\x08l_\x08s",            // line 1 (wrap)
+        );
+
+        let mut output: Vec<u8> = Vec::new();
+        run(&mut limiter, &mut input.as_bytes(), &mut output).unwrap();
+
+        let output_string = String::from_utf8(output).unwrap();
+        assert_eq!(exp, output_string, "\n{:?}\n", output_string);
+    }
+
+    #[test]
+    /// Verify that `head` stops output after the given number of segments.
+    fn test_wrap_head() {
+        let config = Config {
+            wrap: Some(true),
+            columns: Some(10),
+            head: Some(3),
+            ..Default::default()
+        };
+        let mut limiter = Limiter::new(config);
+
+        let input: String = format!(
+            "{}\n{}\n{}\n",
+            "[10char-A][10char-B]", // line 1
+            "[10char-C][10char-D]", // line 2
+            "[10char-E]",           // line 3
+        );
+
+        let exp: String = format!(
+            "{}\n{}\n{}\n",
+            "[10char-A]", // line 1
+            "[10char-B]", // line 1 (wrap)
+            "[10char-C]", // line 2
+        );
+
+        let mut output: Vec<u8> = Vec::new();
+        run(&mut limiter, &mut input.as_bytes(), &mut output).unwrap();
+
+        let output_string = String::from_utf8(output).unwrap();
+        assert_eq!(exp, output_string, "\n{}\n", output_string);
+    }
+
+    #[test]
+    /// Verify that only lines within `lines` are chopped, with others skipped
+    /// or passed through unchanged.
+    fn test_lines_pass_through() {
+        let input: String = format!(
+            "{}\n{}\n{}\n",
+            "[10char-A][10char-B]", // line 1
+            "[10char-C][10char-D]", // line 2
+            "[10char-E][10char-F]", // line 3
+        );
+
+        for (pass_through, exp) in [
+            (false, "[10char-C]\n".to_string()),
+            (
+                true,
+                format!(
+                    "{}\n{}\n{}\n",
+                    "[10char-A][10char-B]", // line 1 (unchanged)
+                    "[10char-C]",           // line 2
+                    "[10char-E][10char-F]", // line 3 (unchanged)
+                ),
+            ),
+        ] {
+            let config = Config {
+                columns: Some(10),
+                lines: Some("2:2".parse().unwrap()),
+                pass_through,
+                ..Default::default()
+            };
+            let mut limiter = Limiter::new(config);
+
+            let mut output: Vec<u8> = Vec::new();
+            run(&mut limiter, &mut input.as_bytes(), &mut output).unwrap();
+
+            let output_string = String::from_utf8(output).unwrap();
+            assert_eq!(exp, output_string, "\n{}\n", output_string);
+        }
+    }
+
+    #[test]
+    /// Verify that `even` balances the widths of wrapped segments.
+    fn test_wrap_even() {
+        let config = Config {
+            wrap: Some(true),
+            columns: Some(10),
+            even: true,
+            ..Default::default()
+        };
+        let mut limiter = Limiter::new(config);
+
+        let input: String = format!(
+            "{}\n{}\n",
+            "aaaaaaabbbbbbbccccccc", // line 1 (21 columns)
+            "the quick brown fox",   // line 2 (19 columns)
+        );
+
+        let exp: String = format!(
+            "{}\n{}\n{}\n{}\n{}\n",
+            "aaaaaaa",    // line 1
+            "bbbbbbb",    // line 1 (wrap)
+            "ccccccc",    // line 1 (wrap)
+            "the quick ", // line 2
+            "brown fox",  // line 2 (wrap)
+        );
+
+        let mut output: Vec<u8> = Vec::new();
+        run(&mut limiter, &mut input.as_bytes(), &mut output).unwrap();
+
+        let output_string = String::from_utf8(output).unwrap();
+        assert_eq!(exp, output_string, "\n{}\n", output_string);
+    }
+
+    #[test]
+    /// Verify that `strip_ansi` removes escape sequences, including from
+    /// lines passed through unchanged.
+    fn test_strip_ansi() {
+        let config = Config {
+            columns: Some(10),
+            strip_ansi: true,
+            lines: Some("2:".parse().unwrap()),
+            pass_through: true,
+            ..Default::default()
+        };
+        let mut limiter = Limiter::new(config);
+
+        let input: String = format!(
+            "{}\n{}\n",
+            "\x1b[1m[10char-A]\x1b[0m[10char-B]", // line 1 (passed through)
+            "\x1b]0;title\x07\x1b[31m[10char-C][10char-D]", // line 2
+        );
+
+        let exp: String = format!(
+            "{}\n{}\n",
+            "[10char-A][10char-B]", // line 1
+            "[10char-C]",           // line 2
+        );
+
+        let mut output: Vec<u8> = Vec::new();
+        run(&mut limiter, &mut input.as_bytes(), &mut output).unwrap();
+
+        let output_string = String::from_utf8(output).unwrap();
+        assert_eq!(exp, output_string, "\n{:?}\n", output_string);
+    }
+}
@@ -0,0 +1,201 @@
+//! Chopping of file arguments, in parallel when output does not depend on the terminal.
+
+use chop_core::{run, Config, Limiter};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Mutex};
+
+/// Writer forwarding only complete lines, so that output from concurrent
+/// workers may be interleaved without splitting lines.
+struct LineSender {
+    tx: mpsc::Sender<Vec<u8>>,
+    buffer: Vec<u8>,
+}
+
+impl Write for LineSender {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        if let Some(pos) = self.buffer.iter().rposition(|&b| b == b'\n') {
+            let rest = self.buffer.split_off(pos + 1);
+            let lines = std::mem::replace(&mut self.buffer, rest);
+            self.tx
+                .send(lines)
+                .map_err(|_| std::io::Error::from(std::io::ErrorKind::BrokenPipe))?;
+        }
+        Ok(())
+    }
+}
+
+/// Destination of the chopped contents of `path`, given an optional output suffix.
+fn output_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+fn chop_file(
+    limiter: &mut Limiter,
+    path: &Path,
+    suffix: Option<&str>,
+    output: &mut impl Write,
+) -> std::io::Result<()> {
+    let mut input = BufReader::new(File::open(path)?);
+    match suffix {
+        Some(suffix) => {
+            let mut file = BufWriter::new(File::create(output_path(path, suffix))?);
+            run(limiter, &mut input, &mut file)?;
+            file.flush()
+        }
+        None => run(limiter, &mut input, output),
+    }
+}
+
+/// Chop each of `files`, writing to `<file><suffix>` when a suffix is given,
+/// and to `output` otherwise.
+///
+/// With an explicit `--columns` limit, files are processed by parallel workers,
+/// whose complete lines are interleaved when writing to `output`.
+pub fn run_files(
+    config: &Config,
+    files: &[PathBuf],
+    suffix: Option<&str>,
+    output: &mut impl Write,
+) -> std::io::Result<()> {
+    let workers = std::thread::available_parallelism().map_or(1, |n| n.get());
+    if config.columns.is_none() || files.len() < 2 || workers < 2 {
+        let mut limiter = Limiter::new(config.clone());
+        for path in files {
+            chop_file(&mut limiter, path, suffix, output).map_err(|e| annotate(path, e))?;
+        }
+        return Ok(());
+    }
+
+    let queue = Mutex::new(files.iter());
+    let (tx, rx) = mpsc::channel();
+
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers.min(files.len()))
+            .map(|_| {
+                let tx = tx.clone();
+                let queue = &queue;
+                scope.spawn(move || -> std::io::Result<()> {
+                    let mut limiter = Limiter::new(config.clone());
+                    let mut sender = LineSender {
+                        tx,
+                        buffer: Vec::new(),
+                    };
+                    while let Some(path) = queue.lock().unwrap().next() {
+                        chop_file(&mut limiter, path, suffix, &mut sender)
+                            .map_err(|e| annotate(path, e))?;
+                    }
+                    Ok(())
+                })
+            })
+            .collect();
+        drop(tx);
+
+        let mut result = Ok(());
+        for lines in rx {
+            if let Err(e) = output.write_all(&lines).and_then(|_| output.flush()) {
+                result = match e.kind() {
+                    std::io::ErrorKind::BrokenPipe => Ok(()),
+                    _ => Err(e),
+                };
+                break; // workers stop once the receiver is dropped
+            }
+        }
+
+        for handle in handles {
+            handle.join().expect("chop worker panicked")?;
+        }
+        result
+    })
+}
+
+/// The contents of each of `files` in turn, as one input, ending any last line
+/// left unterminated so that it is not joined to the first line of the next.
+pub fn concat(files: &[PathBuf]) -> std::io::Result<Box<dyn BufRead>> {
+    let mut input: Box<dyn Read> = Box::new(std::io::empty());
+    for path in files {
+        let mut file = File::open(path).map_err(|e| annotate(path, e))?;
+        let terminated = ends_with_newline(&mut file);
+        input = Box::new(input.chain(file));
+        if !terminated {
+            input = Box::new(input.chain(&b"\n"[..]));
+        }
+    }
+    Ok(Box::new(BufReader::new(input)))
+}
+
+/// Whether `file` is empty or ends with a newline, leaving it at its start; files
+/// that cannot seek, such as pipes, are assumed to.
+fn ends_with_newline(file: &mut File) -> bool {
+    let mut last = [b'\n'];
+    let _ = file
+        .seek(SeekFrom::End(-1))
+        .and_then(|_| file.read_exact(&mut last));
+    let _ = file.seek(SeekFrom::Start(0));
+    last[0] == b'\n'
+}
+
+fn annotate(path: &Path, e: std::io::Error) -> std::io::Error {
+    std::io::Error::new(e.kind(), format!("{}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Verify that only complete lines are forwarded by `LineSender`.
+    fn test_line_sender() {
+        let (tx, rx) = mpsc::channel();
+        let mut sender = LineSender {
+            tx,
+            buffer: Vec::new(),
+        };
+
+        write!(sender, "first | ").unwrap();
+        sender.flush().unwrap();
+        write!(sender, "segment\nsecond").unwrap();
+        sender.flush().unwrap();
+        drop(sender);
+
+        let sent: Vec<Vec<u8>> = rx.iter().collect();
+        assert_eq!(vec![b"first | segment\n".to_vec()], sent);
+    }
+
+    #[test]
+    /// Verify that files are read in turn, without joining unterminated last lines.
+    fn test_concat() {
+        let dir = std::env::temp_dir().join(format!("chop-concat-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let files = [dir.join("a"), dir.join("b"), dir.join("c")];
+        std::fs::write(&files[0], "one\ntwo").unwrap();
+        std::fs::write(&files[1], "").unwrap();
+        std::fs::write(&files[2], "three\n").unwrap();
+
+        let lines: Vec<String> = concat(&files)
+            .unwrap()
+            .lines()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(vec!["one", "two", "three"], lines);
+        assert!(concat(&[dir.join("missing")]).is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    /// Verify that output files are named by appending the suffix.
+    fn test_output_path() {
+        assert_eq!(
+            PathBuf::from("logs/app.log.chopped"),
+            output_path(Path::new("logs/app.log"), ".chopped")
+        );
+    }
+}
@@ -0,0 +1,189 @@
+//! Interactive preview for discovering chop settings before use in a pipeline.
+//!
+//! Input is read up front, while keys are read from the controlling terminal,
+//! so that the preview works at the end of a pipe (e.g., `dmesg | chop -i`).
+
+use chop_core::{Chopper, Config, Limiter};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::os::unix::io::AsRawFd;
+
+const ENTER_SCREEN: &str = "\x1b[?1049h\x1b[?25l\x1b[?7l";
+const LEAVE_SCREEN: &str = "\x1b[?7h\x1b[?25h\x1b[?1049l";
+
+enum Key {
+    Left,
+    Right,
+    Up,
+    Down,
+    Enter,
+    Escape,
+    Backspace,
+    Char(char),
+}
+
+/// Controlling terminal in raw mode on an alternate screen, restored when dropped.
+struct Terminal {
+    tty: File,
+    original: libc::termios,
+}
+
+impl Terminal {
+    fn open() -> std::io::Result<Self> {
+        let tty = OpenOptions::new().read(true).write(true).open("/dev/tty")?;
+        let fd = tty.as_raw_fd();
+
+        let mut original: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(fd, &mut original) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+
+        let mut raw = original;
+        unsafe { libc::cfmakeraw(&mut raw) };
+        if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &raw) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+
+        let mut terminal = Self { tty, original };
+        write!(terminal.tty, "{}", ENTER_SCREEN)?;
+        Ok(terminal)
+    }
+
+    /// Rows and columns of the terminal, defaulting to 24x80 if unavailable.
+    fn size(&self) -> (usize, usize) {
+        let mut ws: libc::winsize = unsafe { std::mem::zeroed() };
+        match unsafe { libc::ioctl(self.tty.as_raw_fd(), libc::TIOCGWINSZ, &mut ws) } {
+            0 if ws.ws_row > 0 && ws.ws_col > 0 => (ws.ws_row as usize, ws.ws_col as usize),
+            _ => (24, 80),
+        }
+    }
+
+    fn read_key(&mut self) -> std::io::Result<Key> {
+        let mut buf = [0_u8; 8];
+        let n = self.tty.read(&mut buf)?;
+
+        Ok(match &buf[..n] {
+            [0x1b, b'[', b'D', ..] => Key::Left,
+            [0x1b, b'[', b'C', ..] => Key::Right,
+            [0x1b, b'[', b'A', ..] => Key::Up,
+            [0x1b, b'[', b'B', ..] => Key::Down,
+            [0x1b, ..] => Key::Escape,
+            [b'\r', ..] | [b'\n', ..] => Key::Enter,
+            [0x7f, ..] | [0x08, ..] => Key::Backspace,
+            bytes => match String::from_utf8_lossy(bytes).chars().next() {
+                Some(c) => Key::Char(c),
+                None => Key::Escape,
+            },
+        })
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let _ = write!(self.tty, "{}", LEAVE_SCREEN);
+        let _ = self.tty.flush();
+        unsafe { libc::tcsetattr(self.tty.as_raw_fd(), libc::TCSANOW, &self.original) };
+    }
+}
+
+/// Preview `lines` chopped with adjustable settings, returning the chosen
+/// configuration, or `None` if the preview was abandoned.
+pub fn run(config: Config, lines: &[String]) -> std::io::Result<Option<Config>> {
+    let mut terminal = Terminal::open()?;
+
+    let mut config = config;
+    let mut columns = config.columns.unwrap_or(terminal.size().1);
+    let mut picking_delimiter = false;
+
+    loop {
+        let (rows, _) = terminal.size();
+        config.columns = Some(columns);
+
+        let mut limiter = Limiter::new(config.clone());
+        write!(terminal.tty, "\x1b[H\x1b[2J")?;
+        for segment in Chopper::new(&mut limiter, lines.iter()).take(rows - 1) {
+            write!(terminal.tty, "{}\x1b[0m\r\n", segment)?;
+        }
+
+        let status = if picking_delimiter {
+            "type a delimiter character [backspace] none [esc] cancel".to_string()
+        } else {
+            format!(
+                "columns: {}  wrap: {}  delimiter: {:?}  \
+                 [\u{2190}/\u{2192} -/+] width [\u{2191}/\u{2193}] width by 10 \
+                 [w] wrap [d] delimiter [enter] done [q] quit",
+                columns,
+                if config.wrap.unwrap_or(false) {
+                    "on"
+                } else {
+                    "off"
+                },
+                config.delimiter.as_deref().unwrap_or(""),
+            )
+        };
+        write!(terminal.tty, "\x1b[{};1H\x1b[7m{}\x1b[0m", rows, status)?;
+        terminal.tty.flush()?;
+
+        let key = terminal.read_key()?;
+        if picking_delimiter {
+            match key {
+                Key::Char(c) => config.delimiter = Some(c.to_string()),
+                Key::Backspace => config.delimiter = None,
+                _ => {}
+            }
+            picking_delimiter = false;
+            continue;
+        }
+
+        match key {
+            Key::Left | Key::Char('-') => columns = columns.saturating_sub(1).max(1),
+            Key::Right | Key::Char('+') | Key::Char('=') => columns += 1,
+            Key::Down => columns = columns.saturating_sub(10).max(1),
+            Key::Up => columns += 10,
+            Key::Char('w') => config.wrap = Some(!config.wrap.unwrap_or(false)),
+            Key::Char('d') => picking_delimiter = true,
+            Key::Enter => return Ok(Some(config)),
+            Key::Escape | Key::Char('q') => return Ok(None),
+            _ => {}
+        }
+    }
+}
+
+/// Command-line flags reproducing the settings tunable within the preview.
+pub fn flags(config: &Config) -> String {
+    let mut flags = Vec::new();
+    if let Some(columns) = config.columns {
+        flags.push(format!("--columns {}", columns));
+    }
+    if config.wrap.unwrap_or(false) {
+        flags.push("--wrap true".to_string());
+    }
+    if let Some(ref delimiter) = config.delimiter {
+        flags.push(format!(
+            "--delimiter '{}'",
+            delimiter.replace('\'', "'\\''")
+        ));
+    }
+    flags.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Verify that chosen settings are printed as shell-quoted flags.
+    fn test_flags() {
+        let config = Config {
+            columns: Some(72),
+            wrap: Some(true),
+            delimiter: Some("'".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            "--columns 72 --wrap true --delimiter ''\\'''",
+            flags(&config)
+        );
+        assert_eq!("", flags(&Config::default()));
+    }
+}
@@ -0,0 +1,132 @@
+//! Delimiting banners printed around each run of a command, so that its output
+//! can be told apart from that of earlier runs, along with the terminal bell and
+//! title updates that report runs in background panes.
+
+use crate::Job;
+use std::io::{IsTerminal, Write};
+use std::process::ExitStatus;
+use std::time::{Duration, SystemTime};
+
+const BOLD: &str = "\x1b[1m";
+const GREEN: &str = "\x1b[1;32m";
+const RED: &str = "\x1b[1;31m";
+const RESET: &str = "\x1b[0m";
+
+#[derive(Debug, Clone, Copy)]
+pub struct Banner {
+    enabled: bool,
+    color: bool,
+    /// Ring the bell when a command fails
+    bell: bool,
+    /// Show the command and its outcome in the terminal (and tmux window) title
+    title: bool,
+}
+
+impl Banner {
+    pub fn new(enabled: bool) -> Self {
+        let color = std::io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+        Self {
+            enabled,
+            color,
+            bell: false,
+            title: false,
+        }
+    }
+
+    /// Also ring the bell on failure, and keep the terminal title up to date, as
+    /// enabled; neither applies unless stderr is a terminal.
+    pub fn alert(self, bell: bool, title: bool) -> Self {
+        let terminal = std::io::stderr().is_terminal();
+        Self {
+            bell: bell && terminal,
+            title: title && terminal,
+            ..self
+        }
+    }
+
+    /// Announce that `job` is starting, and why.
+    pub fn start(&self, job: &Job) {
+        if self.title {
+            set_title(&format!("… {}", job.command.join(" ")));
+        }
+        if !self.enabled {
+            return;
+        }
+        let trigger = &job.trigger;
+        let reason = match (trigger.event, trigger.paths.len()) {
+            (None, _) => "startup".to_string(),
+            (Some(event), 0) => event.to_string(),
+            (Some(event), count) => format!(
+                "{} of {} file{}",
+                event,
+                count,
+                if count == 1 { "" } else { "s" }
+            ),
+        };
+        self.print(
+            BOLD,
+            &format!(
+                "[{}] {}: {}",
+                humantime::format_rfc3339_seconds(SystemTime::now()),
+                reason,
+                job.command.join(" ")
+            ),
+        );
+    }
+
+    /// Report how `command` ended; `status` is `None` if it was terminated.
+    pub fn finish(&self, command: &[String], status: Option<ExitStatus>, elapsed: Duration) {
+        if self.bell && status.is_some_and(|s| !s.success()) {
+            eprint!("\x07");
+        }
+        if self.title {
+            let mark = match status {
+                Some(status) if status.success() => "✓",
+                Some(_) => "✗",
+                None => "■",
+            };
+            set_title(&format!(
+                "{} {} ({:.1}s)",
+                mark,
+                command.join(" "),
+                elapsed.as_secs_f32()
+            ));
+        }
+        if !self.enabled {
+            return;
+        }
+        let (color, outcome) = match status {
+            Some(status) if status.success() => (GREEN, "succeeded".to_string()),
+            Some(status) => (RED, format!("failed ({})", status)),
+            None => (BOLD, "terminated".to_string()),
+        };
+        self.print(
+            color,
+            &format!(
+                "[{}] {} after {:.2}s",
+                humantime::format_rfc3339_seconds(SystemTime::now()),
+                outcome,
+                elapsed.as_secs_f32()
+            ),
+        );
+    }
+
+    fn print(&self, color: &str, message: &str) {
+        match self.color {
+            true => eprintln!("{}--- {}{}", color, message, RESET),
+            false => eprintln!("--- {}", message),
+        }
+    }
+}
+
+/// Set the title of the terminal, and of the tmux window if within tmux.
+fn set_title(title: &str) {
+    // keep control characters from ending the sequence early
+    let title: String = title.chars().filter(|c| !c.is_control()).collect();
+    let mut stderr = std::io::stderr().lock();
+    let _ = write!(stderr, "\x1b]0;{}\x07", title);
+    if std::env::var_os("TMUX").is_some() {
+        let _ = write!(stderr, "\x1bk{}\x1b\\", title);
+    }
+    let _ = stderr.flush();
+}
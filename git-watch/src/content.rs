@@ -0,0 +1,39 @@
+//! Hashes of file contents, to tell writes that changed a file apart from
+//! those that merely rewrote identical bytes, as editors and `touch` do.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fs::File;
+use std::hash::Hasher;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Hash of each file as last seen.
+#[derive(Default)]
+pub struct Hashes(HashMap<PathBuf, u64>);
+
+impl Hashes {
+    /// Whether the content of `path` differs from when last checked; files not
+    /// seen before, or not readable (such as when removed), count as changed.
+    pub fn changed(&mut self, path: &Path) -> bool {
+        match hash(path) {
+            Ok(hash) => self.0.insert(path.to_path_buf(), hash) != Some(hash),
+            Err(_) => {
+                self.0.remove(path);
+                true
+            }
+        }
+    }
+}
+
+fn hash(path: &Path) -> std::io::Result<u64> {
+    let mut file = File::open(path)?;
+    let mut hasher = DefaultHasher::new();
+    let mut buf = [0; 64 * 1024];
+    loop {
+        match file.read(&mut buf)? {
+            0 => return Ok(hasher.finish()),
+            n => hasher.write(&buf[..n]),
+        }
+    }
+}
@@ -0,0 +1,179 @@
+//! Control socket through which editor plugins and scripts interact with a
+//! running watcher, one line-based command per connection: `status`,
+//! `trigger`, `pause`, `resume`, or `quit`.
+
+use crate::{pause, Event};
+use std::path::Path;
+use std::process::ExitStatus;
+use std::sync::atomic::{AtomicI32, AtomicUsize, Ordering};
+use std::sync::mpsc::Sender;
+use std::time::Duration;
+
+static RUNS: AtomicUsize = AtomicUsize::new(0);
+static FAILURES: AtomicUsize = AtomicUsize::new(0);
+/// Exit code of the last run, or `NONE` before any run or when killed by a signal
+static LAST_CODE: AtomicI32 = AtomicI32::new(NONE);
+const NONE: i32 = i32::MIN;
+/// How long a client may take to send its command or read the reply
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Account for a finished run, as reported by `status`.
+pub fn record(status: ExitStatus) {
+    RUNS.fetch_add(1, Ordering::SeqCst);
+    if !status.success() {
+        FAILURES.fetch_add(1, Ordering::SeqCst);
+    }
+    LAST_CODE.store(status.code().unwrap_or(NONE), Ordering::SeqCst);
+}
+
+/// Number of runs so far, and how many of them failed.
+pub fn counts() -> (usize, usize) {
+    (RUNS.load(Ordering::SeqCst), FAILURES.load(Ordering::SeqCst))
+}
+
+/// Listen on `path` in the background, acting on commands through `events`.
+///
+/// Each connection is handled in a thread of its own, so a client that is slow
+/// to send its command holds up no other.
+#[cfg(unix)]
+pub fn serve(path: &Path, events: Sender<Event>) -> anyhow::Result<()> {
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    if path.exists() {
+        // a socket that accepts connections belongs to a live watcher
+        anyhow::ensure!(
+            UnixStream::connect(path).is_err(),
+            "another git-watch is listening on {:?}",
+            path
+        );
+        std::fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)
+        .map_err(|e| anyhow::anyhow!("unable to listen on {:?}: {}", path, e))?;
+    log::debug!("Listening on {:?}", path);
+
+    let path = path.to_path_buf();
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let (path, events) = (path.clone(), events.clone());
+            std::thread::spawn(move || {
+                if let Err(e) = handle(stream, &path, &events) {
+                    log::warn!("control socket: {}", e);
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+/// Send `command` to the watcher listening on `path`, returning its reply.
+#[cfg(unix)]
+pub fn send(path: &Path, command: &str) -> anyhow::Result<String> {
+    use std::io::{Read, Write};
+    use std::os::unix::net::UnixStream;
+
+    let mut stream = UnixStream::connect(path)
+        .map_err(|e| anyhow::anyhow!("no watcher listening on {:?}: {}", path, e))?;
+    writeln!(stream, "{}", command)?;
+    let mut reply = String::new();
+    stream.read_to_string(&mut reply)?;
+    Ok(reply.trim_end().to_string())
+}
+
+#[cfg(not(unix))]
+pub fn send(_path: &Path, _command: &str) -> anyhow::Result<String> {
+    anyhow::bail!("control sockets are only supported on Unix")
+}
+
+#[cfg(not(unix))]
+pub fn serve(_path: &Path, _events: Sender<Event>) -> anyhow::Result<()> {
+    anyhow::bail!("control sockets are only supported on Unix")
+}
+
+#[cfg(unix)]
+fn handle(
+    stream: std::os::unix::net::UnixStream,
+    path: &Path,
+    events: &Sender<Event>,
+) -> std::io::Result<()> {
+    use std::io::{BufRead, BufReader, Write};
+
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+    let reply = match line.trim() {
+        "status" => status(),
+        "trigger" => {
+            let _ = events.send(Event::Force);
+            "ok".to_string()
+        }
+        "pause" => {
+            pause::set(true);
+            "ok".to_string()
+        }
+        "resume" => {
+            pause::set(false);
+            "ok".to_string()
+        }
+        "quit" => {
+            let _ = std::fs::remove_file(path);
+            // reply first, as git-watch may exit as soon as it is told to
+            writeln!(&stream, "ok")?;
+            let _ = events.send(Event::Quit);
+            return Ok(());
+        }
+        command => format!("error: unknown command {:?}", command),
+    };
+    writeln!(&stream, "{}", reply)
+}
+
+fn status() -> String {
+    let last = match (
+        RUNS.load(Ordering::SeqCst),
+        LAST_CODE.load(Ordering::SeqCst),
+    ) {
+        (0, _) => "none".to_string(),
+        (_, NONE) => "signal".to_string(),
+        (_, code) => code.to_string(),
+    };
+    format!(
+        "{} runs={} failures={} last={}",
+        if pause::is_paused(None) {
+            "paused"
+        } else {
+            "watching"
+        },
+        RUNS.load(Ordering::SeqCst),
+        FAILURES.load(Ordering::SeqCst),
+        last
+    )
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Verify that commands sent over the socket are acted on and answered, even
+    /// while another client has yet to send its command.
+    fn test_send() {
+        let path = std::env::temp_dir().join(format!("git-watch-control-{}", std::process::id()));
+        let (tx, rx) = std::sync::mpsc::channel();
+        serve(&path, tx).unwrap();
+
+        let _silent = std::os::unix::net::UnixStream::connect(&path).unwrap();
+        assert!(send(&path, "status").unwrap().contains(" runs="));
+        assert_eq!("ok", send(&path, "trigger").unwrap());
+        assert!(matches!(rx.recv_timeout(TIMEOUT), Ok(Event::Force)));
+        assert_eq!(
+            "error: unknown command \"bogus\"",
+            send(&path, "bogus").unwrap()
+        );
+        assert!(serve(&path, std::sync::mpsc::channel().0).is_err());
+
+        assert_eq!("ok", send(&path, "quit").unwrap());
+        assert!(matches!(rx.recv_timeout(TIMEOUT), Ok(Event::Quit)));
+        assert!(!path.exists());
+    }
+}
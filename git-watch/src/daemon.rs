@@ -0,0 +1,95 @@
+//! Running in the background, detached from the terminal, with a pid file
+//! through which `--stop` finds the running instance.
+
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// How long `stop` waits for the daemon to exit.
+const STOP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Process id recorded in `pid_file`, if it names a live process.
+#[cfg(unix)]
+fn running(pid_file: &Path) -> Option<libc::pid_t> {
+    let pid = std::fs::read_to_string(pid_file)
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    // SAFETY: signal 0 only checks for the existence of the process
+    (unsafe { libc::kill(pid, 0) } == 0).then_some(pid)
+}
+
+/// Fork into the background, returning only in the daemon, whose pid is
+/// written to `pid_file` and whose output goes to `log`.
+#[cfg(unix)]
+pub fn start(pid_file: &Path, log: &Path) -> anyhow::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    if let Some(pid) = running(pid_file) {
+        anyhow::bail!("already running as pid {} (see {:?})", pid, pid_file);
+    }
+    let log_file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log)
+        .map_err(|e| anyhow::anyhow!("unable to open {:?}: {}", log, e))?;
+    let null = std::fs::File::open("/dev/null")?;
+
+    // SAFETY: no other threads have been started yet
+    match unsafe { libc::fork() } {
+        -1 => return Err(std::io::Error::last_os_error().into()),
+        0 => {}
+        pid => {
+            // write the pid file before returning, so that `--stop` may follow at once
+            std::fs::write(pid_file, format!("{}\n", pid))
+                .map_err(|e| anyhow::anyhow!("unable to write {:?}: {}", pid_file, e))?;
+            log::info!("Started as pid {}, writing to {:?}", pid, log);
+            std::process::exit(0);
+        }
+    }
+
+    // SAFETY: detaches from the terminal and replaces standard descriptors with owned files
+    unsafe {
+        libc::setsid();
+        libc::dup2(null.as_raw_fd(), libc::STDIN_FILENO);
+        libc::dup2(log_file.as_raw_fd(), libc::STDOUT_FILENO);
+        libc::dup2(log_file.as_raw_fd(), libc::STDERR_FILENO);
+    }
+    Ok(())
+}
+
+/// Terminate the daemon recorded in `pid_file`, waiting for it to exit.
+#[cfg(unix)]
+pub fn stop(pid_file: &Path) -> anyhow::Result<()> {
+    let Some(pid) = running(pid_file) else {
+        let _ = std::fs::remove_file(pid_file);
+        anyhow::bail!("not running (no live process in {:?})", pid_file);
+    };
+
+    // SAFETY: only signals the process
+    unsafe { libc::kill(pid, libc::SIGTERM) };
+    let deadline = Instant::now() + STOP_TIMEOUT;
+    // SAFETY: signal 0 only checks for the existence of the process
+    while unsafe { libc::kill(pid, 0) } == 0 {
+        anyhow::ensure!(
+            Instant::now() < deadline,
+            "pid {} did not exit within {:?}",
+            pid,
+            STOP_TIMEOUT
+        );
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    log::info!("Stopped pid {}", pid);
+    let _ = std::fs::remove_file(pid_file);
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn start(_pid_file: &Path, _log: &Path) -> anyhow::Result<()> {
+    anyhow::bail!("daemon mode is only supported on Unix")
+}
+
+#[cfg(not(unix))]
+pub fn stop(_pid_file: &Path) -> anyhow::Result<()> {
+    anyhow::bail!("daemon mode is only supported on Unix")
+}
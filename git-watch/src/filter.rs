@@ -0,0 +1,86 @@
+//! User-supplied include/exclude globs, applied on top of (or, outside of a
+//! repository, instead of) the ignore rules, and settle times by glob.
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::overrides::{Override, OverrideBuilder};
+use std::path::Path;
+
+/// Globs selecting which paths may trigger the command; gitignore syntax,
+/// relative to the watched root.
+pub struct Filters {
+    overrides: Override,
+}
+
+impl Filters {
+    pub fn new(root: &Path, include: &[String], exclude: &[String]) -> Result<Self, ignore::Error> {
+        let mut builder = OverrideBuilder::new(root);
+        for glob in include {
+            builder.add(glob)?;
+        }
+        for glob in exclude {
+            builder.add(&format!("!{}", glob))?;
+        }
+        Ok(Self {
+            overrides: builder.build()?,
+        })
+    }
+
+    /// Whether `path` matches an exclude glob, or fails to match any include glob.
+    pub fn is_excluded(&self, path: &Path) -> bool {
+        self.overrides.matched(path, path.is_dir()).is_ignore()
+    }
+}
+
+/// `GLOB=SECS`, the time allowed for changes to matching paths to settle.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SettleFor {
+    pub glob: String,
+    pub secs: f32,
+}
+
+impl std::str::FromStr for SettleFor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (glob, secs) = s
+            .rsplit_once('=')
+            .filter(|(glob, _)| !glob.is_empty())
+            .ok_or_else(|| format!("invalid settle time: {:?} (expected GLOB=SECS)", s))?;
+        match secs.parse::<f32>() {
+            Ok(secs) if secs.is_finite() && secs >= 0.0 => Ok(Self {
+                glob: glob.to_string(),
+                secs,
+            }),
+            _ => Err(format!("invalid settle time: {:?}", secs)),
+        }
+    }
+}
+
+/// Settle times by glob, relative to the watched root; the last glob matching a
+/// path takes precedence.
+pub struct SettleTimes {
+    globs: Vec<(Gitignore, f32)>,
+}
+
+impl SettleTimes {
+    pub fn new(root: &Path, settle_for: &[SettleFor]) -> Result<Self, ignore::Error> {
+        let globs = settle_for
+            .iter()
+            .map(|settle| {
+                let mut builder = GitignoreBuilder::new(root);
+                builder.add_line(None, &settle.glob)?;
+                Ok((builder.build()?, settle.secs))
+            })
+            .collect::<Result<_, ignore::Error>>()?;
+        Ok(Self { globs })
+    }
+
+    /// Settle time for changes to `path`, if any glob matches it.
+    pub fn get(&self, path: &Path) -> Option<f32> {
+        self.globs
+            .iter()
+            .rev()
+            .find(|(glob, _)| glob.matched(path, path.is_dir()).is_ignore())
+            .map(|(_, secs)| *secs)
+    }
+}
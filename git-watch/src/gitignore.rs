@@ -0,0 +1,166 @@
+//! In-process evaluation of gitignore rules, avoiding a `git check-ignore`
+//! subprocess per path.
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Ignore rules of a repository: per-directory `.gitignore` files, then
+/// `.git/info/exclude`, then the user's global excludes file.
+///
+/// Nested repositories, such as submodules, are either ignored entirely or
+/// evaluated against their own rules.
+pub struct IgnoreRules {
+    root: PathBuf,
+    excludes: Gitignore,
+    global: Gitignore,
+    dirs: HashMap<PathBuf, Gitignore>,
+    submodules: bool,
+    nested: HashMap<PathBuf, IgnoreRules>,
+}
+
+/// Git directory of the working tree at `root`, following the `gitdir:` file
+/// that stands in for `.git` in linked worktrees and submodules, unless named by
+/// `GIT_DIR`.
+pub fn git_dir(root: &Path) -> PathBuf {
+    if let Some(dir) = std::env::var_os("GIT_DIR") {
+        let dir = PathBuf::from(dir);
+        return dir.canonicalize().unwrap_or(dir);
+    }
+    let dot_git = root.join(".git");
+    let git_dir = match std::fs::read_to_string(&dot_git) {
+        Ok(content) => match content.trim().strip_prefix("gitdir:") {
+            Some(dir) => root.join(dir.trim()),
+            None => dot_git,
+        },
+        Err(_) => dot_git,
+    };
+    git_dir.canonicalize().unwrap_or(git_dir)
+}
+
+/// Directory holding what all worktrees of a repository share, such as refs
+/// and `info/exclude`.
+pub fn common_dir(git_dir: &Path) -> PathBuf {
+    match std::fs::read_to_string(git_dir.join("commondir")) {
+        Ok(dir) => {
+            let common = git_dir.join(dir.trim());
+            common.canonicalize().unwrap_or(common)
+        }
+        Err(_) => git_dir.to_path_buf(),
+    }
+}
+
+/// Innermost repository nested within `root`, such as a submodule, containing `path`.
+pub fn nested_repo(root: &Path, path: &Path) -> Option<PathBuf> {
+    let relative = path.strip_prefix(root).ok()?;
+    let mut current = root.to_path_buf();
+    let mut nested = None;
+    for component in relative.parent()?.components() {
+        current.push(component);
+        if current.join(".git").exists() {
+            nested = Some(current.clone());
+        }
+    }
+    nested
+}
+
+impl IgnoreRules {
+    pub fn new(root: &Path, submodules: bool) -> Self {
+        let mut builder = GitignoreBuilder::new(root);
+        let exclude = common_dir(&git_dir(root)).join("info").join("exclude");
+        if exclude.is_file() {
+            if let Some(e) = builder.add(&exclude) {
+                log::warn!("unable to parse {:?}: {}", exclude, e);
+            }
+        }
+        let excludes = builder.build().unwrap_or_else(|e| {
+            log::warn!("unable to parse {:?}: {}", exclude, e);
+            Gitignore::empty()
+        });
+
+        let (global, error) = Gitignore::global();
+        if let Some(e) = error {
+            log::debug!("unable to parse global excludes file: {}", e);
+        }
+
+        Self {
+            root: root.to_path_buf(),
+            excludes,
+            global,
+            dirs: HashMap::new(),
+            submodules,
+            nested: HashMap::new(),
+        }
+    }
+
+    /// Forget parsed `.gitignore` files, so that edits to them take effect.
+    pub fn reload(&mut self) {
+        *self = Self::new(&self.root, self.submodules);
+    }
+
+    /// Whether `path`, or any of its parent directories, is ignored.
+    pub fn is_ignored(&mut self, path: &Path) -> bool {
+        let Ok(relative) = path.strip_prefix(&self.root) else {
+            return false; // outside of the repository
+        };
+
+        // repository metadata is never part of the working tree
+        if relative.starts_with(".git") {
+            return true;
+        }
+
+        // a path cannot be re-included once any parent directory is excluded
+        let components: Vec<_> = relative.components().collect();
+        let mut current = self.root.clone();
+        for (idx, component) in components.iter().enumerate() {
+            current.push(component);
+            let is_dir = idx + 1 < components.len() || current.is_dir();
+            if self.matches(&current, is_dir) {
+                return true;
+            }
+
+            if idx + 1 < components.len() && current.join(".git").exists() {
+                if !self.submodules {
+                    return true;
+                }
+                let nested = self
+                    .nested
+                    .entry(current.clone())
+                    .or_insert_with(|| Self::new(&current, true));
+                return nested.is_ignored(path);
+            }
+        }
+        false
+    }
+
+    /// Whether `path` itself matches the rules, with deeper `.gitignore` files
+    /// taking precedence over shallower ones.
+    fn matches(&mut self, path: &Path, is_dir: bool) -> bool {
+        for dir in path.ancestors().skip(1) {
+            if !dir.starts_with(&self.root) {
+                break;
+            }
+
+            let gitignore = self.dirs.entry(dir.to_path_buf()).or_insert_with(|| {
+                let (gitignore, error) = Gitignore::new(dir.join(".gitignore"));
+                if let Some(e) = error.filter(|_| dir.join(".gitignore").is_file()) {
+                    log::warn!("unable to parse {:?}: {}", dir.join(".gitignore"), e);
+                }
+                gitignore
+            });
+
+            let matched = gitignore.matched(path, is_dir);
+            if !matched.is_none() {
+                return matched.is_ignore();
+            }
+        }
+
+        for gitignore in [&self.excludes, &self.global] {
+            let matched = gitignore.matched(path, is_dir);
+            if !matched.is_none() {
+                return matched.is_ignore();
+            }
+        }
+        false
+    }
+}
@@ -0,0 +1,66 @@
+//! Git hooks asking a running watcher to run after checkouts, merges, and
+//! commits, which may change files without the watcher noticing, or only
+//! within the git directory.
+
+use std::path::{Path, PathBuf};
+
+const HOOKS: [&str; 3] = ["post-checkout", "post-merge", "post-commit"];
+
+/// Line marking hooks as installed by git-watch, and so safe to replace.
+const MARKER: &str = "# installed by git-watch";
+
+/// Directory of the hooks of the repository at `root`, honoring `core.hooksPath`.
+fn hooks_dir(root: &Path) -> anyhow::Result<PathBuf> {
+    let output = std::process::Command::new("git")
+        .args(["rev-parse", "--git-path", "hooks"])
+        .current_dir(root)
+        .output()
+        .map_err(|e| anyhow::anyhow!("unable to locate git hooks: {}", e))?;
+    anyhow::ensure!(output.status.success(), "unable to locate git hooks");
+    let dir = String::from_utf8(output.stdout)
+        .map_err(|_| anyhow::anyhow!("git hooks directory is not valid UTF-8"))?;
+    Ok(root.join(dir.trim_end_matches('\n')))
+}
+
+/// Install the hooks into the repository at `root`, each sending `trigger` to
+/// `socket`, or else the default socket of the repository. Hooks that git-watch
+/// did not install are only replaced if `force` is given.
+pub fn install(root: &Path, socket: Option<&Path>, force: bool) -> anyhow::Result<()> {
+    let dir = hooks_dir(root)?;
+    let paths: Vec<PathBuf> = HOOKS.iter().map(|hook| dir.join(hook)).collect();
+    for path in paths.iter() {
+        if let Ok(existing) = std::fs::read_to_string(path) {
+            anyhow::ensure!(
+                force || existing.contains(MARKER),
+                "{:?} exists and was not installed by git-watch (use --force to replace it)",
+                path
+            );
+        }
+    }
+
+    let exe = std::env::current_exe()?;
+    let mut command = crate::shell_quote(&exe.to_string_lossy());
+    if let Some(socket) = socket {
+        command.push_str(&format!(
+            " --socket={}",
+            crate::shell_quote(&socket.to_string_lossy())
+        ));
+    }
+    let script = format!(
+        "#!/bin/sh\n{}\n# ask the watcher of this repository, if any, to run\n{} --send trigger >/dev/null 2>&1 || true\n",
+        MARKER, command
+    );
+
+    std::fs::create_dir_all(&dir)?;
+    for path in paths.iter() {
+        std::fs::write(path, &script)
+            .map_err(|e| anyhow::anyhow!("unable to write {:?}: {}", path, e))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))?;
+        }
+        log::info!("Installed {:?}", path);
+    }
+    Ok(())
+}
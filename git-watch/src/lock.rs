@@ -0,0 +1,119 @@
+//! Lock held for as long as git-watch runs, so that a second watcher for the
+//! same repository and command does not silently double every run.
+
+use std::fs::File;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// How long `--takeover` waits for the other watcher to exit.
+const TAKEOVER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// An acquired lock, released when dropped (or when the process exits).
+pub struct Lock {
+    _file: File,
+}
+
+/// Try to lock `file` without blocking.
+#[cfg(unix)]
+fn try_lock(file: &File) -> bool {
+    use std::os::unix::io::AsRawFd;
+
+    // SAFETY: locks a file descriptor owned by `file`
+    unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) == 0 }
+}
+
+/// Lock `path`, recording the pid of this process in it. Should another
+/// watcher hold the lock, either terminate it when taking over, or fail.
+#[cfg(unix)]
+pub fn acquire(path: &Path, takeover: bool) -> anyhow::Result<Lock> {
+    use std::io::Write;
+
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .read(true)
+        .write(true)
+        .open(path)
+        .map_err(|e| anyhow::anyhow!("unable to open lock file {:?}: {}", path, e))?;
+
+    if !try_lock(&file) {
+        let pid: Option<libc::pid_t> = std::fs::read_to_string(path)
+            .ok()
+            .and_then(|pid| pid.trim().parse().ok());
+        let Some(pid) = pid.filter(|_| takeover) else {
+            anyhow::bail!(
+                "already running as pid {} for this repository and command (use --takeover to replace it)",
+                pid.map_or("?".to_string(), |pid| pid.to_string())
+            );
+        };
+        log::info!("Taking over from pid {}", pid);
+        // SAFETY: only signals the process
+        unsafe { libc::kill(pid, libc::SIGTERM) };
+        let deadline = Instant::now() + TAKEOVER_TIMEOUT;
+        while !try_lock(&file) {
+            anyhow::ensure!(
+                Instant::now() < deadline,
+                "pid {} did not exit within {:?}",
+                pid,
+                TAKEOVER_TIMEOUT
+            );
+            std::thread::sleep(Duration::from_millis(50));
+        }
+    }
+
+    file.set_len(0)?;
+    writeln!(file, "{}", std::process::id())?;
+    Ok(Lock { _file: file })
+}
+
+/// Lock `path`, recording the pid of this process in it, or fail should
+/// another watcher hold the lock; taking over is left to Unix.
+#[cfg(not(unix))]
+pub fn acquire(path: &Path, takeover: bool) -> anyhow::Result<Lock> {
+    use std::io::Write;
+
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path)
+        .map_err(|e| anyhow::anyhow!("unable to open lock file {:?}: {}", path, e))?;
+    // LockFileEx on Windows
+    if let Err(e) = file.try_lock() {
+        anyhow::bail!(
+            "already running for this repository and command ({}){}",
+            e,
+            if takeover {
+                ", and --takeover is only supported on Unix"
+            } else {
+                ""
+            }
+        );
+    }
+
+    file.set_len(0)?;
+    writeln!(file, "{}", std::process::id())?;
+    Ok(Lock { _file: file })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Verify that the lock is held until dropped, recording the pid of its holder.
+    fn test_acquire() {
+        let path = std::env::temp_dir().join(format!("git-watch-lock-{}", std::process::id()));
+        let lock = acquire(&path, false).unwrap();
+        assert_eq!(
+            std::process::id().to_string(),
+            std::fs::read_to_string(&path).unwrap().trim()
+        );
+        assert!(acquire(&path, false).is_err());
+
+        drop(lock);
+        let lock = acquire(&path, false).unwrap();
+        drop(lock);
+        std::fs::remove_file(path).unwrap();
+    }
+}
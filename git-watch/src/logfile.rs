@@ -0,0 +1,172 @@
+//! Structured log of changes and runs, appended to a file as JSON lines
+//! regardless of console verbosity, and rotated once it grows too large.
+
+use crate::Job;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+/// Number of rotated files kept, as `PATH.1` (newest) through `PATH.N`.
+const KEEP: usize = 3;
+
+static LOG_FILE: Mutex<Option<LogFile>> = Mutex::new(None);
+
+struct LogFile {
+    path: PathBuf,
+    max_size: u64,
+    file: File,
+    size: u64,
+}
+
+impl LogFile {
+    fn open(path: &Path, max_size: u64) -> std::io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path: path.to_path_buf(),
+            max_size,
+            file,
+            size,
+        })
+    }
+
+    fn write(&mut self, line: &str) -> std::io::Result<()> {
+        if self.size > 0 && self.size + line.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+        self.file.write_all(line.as_bytes())?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    /// Shift `PATH` to `PATH.1`, `PATH.1` to `PATH.2`, and so on, discarding the oldest.
+    fn rotate(&mut self) -> std::io::Result<()> {
+        let rotated = |n: usize| {
+            let mut path = self.path.clone().into_os_string();
+            path.push(format!(".{}", n));
+            PathBuf::from(path)
+        };
+        for n in (1..KEEP).rev() {
+            if rotated(n).exists() {
+                std::fs::rename(rotated(n), rotated(n + 1))?;
+            }
+        }
+        std::fs::rename(&self.path, rotated(1))?;
+        *self = Self::open(&self.path, self.max_size)?;
+        Ok(())
+    }
+}
+
+/// Start logging to `path`, rotating once it would exceed `max_size` bytes.
+pub fn init(path: &Path, max_size: u64) -> std::io::Result<()> {
+    *LOG_FILE.lock().unwrap() = Some(LogFile::open(path, max_size)?);
+    Ok(())
+}
+
+/// Quote `s` as a JSON string.
+pub fn json_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Append a record of `kind` with `fields`, whose values are already JSON.
+fn record(kind: &str, fields: &[(&str, String)]) {
+    let mut guard = LOG_FILE.lock().unwrap();
+    let Some(log_file) = guard.as_mut() else {
+        return;
+    };
+
+    let mut line = format!(
+        "{{\"time\":{},\"type\":{}",
+        json_string(&humantime::format_rfc3339_millis(SystemTime::now()).to_string()),
+        json_string(kind)
+    );
+    for (key, value) in fields {
+        line.push_str(&format!(",{}:{}", json_string(key), value));
+    }
+    line.push_str("}\n");
+
+    if let Err(e) = log_file.write(&line) {
+        log::warn!("unable to write log file {:?}: {}", log_file.path, e);
+    }
+}
+
+/// Record an actionable change.
+pub fn change(path: &Path, event: &str) {
+    record(
+        "change",
+        &[
+            ("path", json_string(&path.to_string_lossy())),
+            ("event", json_string(event)),
+        ],
+    );
+}
+
+/// Record the start of `job`.
+pub fn start(job: &Job) {
+    let paths: Vec<String> = job
+        .trigger
+        .paths
+        .iter()
+        .map(|path| json_string(&path.to_string_lossy()))
+        .collect();
+    record(
+        "start",
+        &[
+            ("command", json_string(&job.command.join(" "))),
+            ("event", json_string(job.trigger.event.unwrap_or("startup"))),
+            ("paths", format!("[{}]", paths.join(","))),
+        ],
+    );
+}
+
+/// Record how `command` ended; `status` is `None` if it was terminated.
+pub fn finish(command: &[String], status: Option<ExitStatus>, elapsed: Duration) {
+    let code = status
+        .and_then(|status| status.code())
+        .map_or("null".to_string(), |code| code.to_string());
+    record(
+        "finish",
+        &[
+            ("command", json_string(&command.join(" "))),
+            (
+                "success",
+                status.is_some_and(|status| status.success()).to_string(),
+            ),
+            ("code", code),
+            ("terminated", status.is_none().to_string()),
+            ("seconds", format!("{:.3}", elapsed.as_secs_f64())),
+        ],
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Verify that JSON strings escape quotes, backslashes, and control characters.
+    fn test_json_string() {
+        assert_eq!(r#""a.rs""#, json_string("a.rs"));
+        assert_eq!(
+            r#""say \"hi\"\\\n\u0001""#,
+            json_string("say \"hi\"\\\n\u{1}")
+        );
+    }
+}
@@ -0,0 +1,125 @@
+//! Least-recently-used cache whose entries also expire a fixed time after
+//! being inserted, so that frequently changed files stay cached while stale
+//! results are still looked up afresh eventually.
+
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+struct Entry<V> {
+    value: V,
+    expires: Instant,
+    /// Position in the recency order, greater being more recent
+    used: u64,
+}
+
+pub struct Lru<K, V> {
+    capacity: usize,
+    ttl: Duration,
+    entries: HashMap<K, Entry<V>>,
+    /// Keys by when they were last used
+    recency: BTreeMap<u64, K>,
+    clock: u64,
+}
+
+impl<K: Hash + Eq + Clone, V: Copy> Lru<K, V> {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            capacity,
+            ttl,
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            clock: 0,
+        }
+    }
+
+    /// Value for `key` unless absent or expired, marking it as most recently used.
+    pub fn get(&mut self, key: &K, now: Instant) -> Option<V> {
+        let entry = self.entries.get_mut(key)?;
+        self.recency.remove(&entry.used);
+        if entry.expires <= now {
+            self.entries.remove(key);
+            return None;
+        }
+        self.clock += 1;
+        entry.used = self.clock;
+        self.recency.insert(self.clock, key.clone());
+        Some(entry.value)
+    }
+
+    /// Cache `value` for `key`, evicting the least recently used entry if full.
+    pub fn insert(&mut self, key: K, value: V, now: Instant) {
+        if let Some(entry) = self.entries.remove(&key) {
+            self.recency.remove(&entry.used);
+        }
+        while self.entries.len() >= self.capacity.max(1) {
+            let Some((_, evicted)) = self.recency.pop_first() else {
+                break;
+            };
+            self.entries.remove(&evicted);
+        }
+        self.clock += 1;
+        self.recency.insert(self.clock, key.clone());
+        self.entries.insert(
+            key,
+            Entry {
+                value,
+                expires: now + self.ttl,
+                used: self.clock,
+            },
+        );
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.recency.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Verify that the cache evicts the least recently used entry, and expires entries.
+    fn test_lru() {
+        let now = Instant::now();
+        let mut cache = Lru::new(2, Duration::from_secs(10));
+        cache.insert("a", true, now);
+        cache.insert("b", false, now);
+        assert_eq!(Some(true), cache.get(&"a", now));
+        cache.insert("c", true, now);
+        assert_eq!(None, cache.get(&"b", now));
+        assert_eq!(Some(true), cache.get(&"a", now));
+        assert_eq!(Some(true), cache.get(&"c", now));
+        assert_eq!(None, cache.get(&"a", now + Duration::from_secs(10)));
+        assert_eq!(1, cache.len());
+    }
+
+    #[test]
+    /// Verify that entries expire a fixed time after being inserted, however
+    /// recently used, and that inserting again renews them.
+    fn test_ttl() {
+        let now = Instant::now();
+        let ttl = Duration::from_secs(10);
+        let mut cache = Lru::new(4, ttl);
+        cache.insert("a", 1, now);
+        cache.insert("b", 2, now + Duration::from_secs(5));
+        assert_eq!(
+            Some(1),
+            cache.get(&"a", now + ttl - Duration::from_millis(1))
+        );
+        assert_eq!(None, cache.get(&"a", now + ttl));
+        assert_eq!(None, cache.get(&"a", now));
+        assert_eq!(Some(2), cache.get(&"b", now + ttl));
+
+        cache.insert("b", 3, now + ttl);
+        assert_eq!(Some(3), cache.get(&"b", now + ttl + Duration::from_secs(5)));
+        assert_eq!(None, cache.get(&"b", now + ttl + ttl));
+        assert_eq!(0, cache.len());
+    }
+}
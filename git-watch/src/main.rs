@@ -1,37 +1,419 @@
+mod banner;
+mod content;
+mod control;
+mod daemon;
+mod filter;
+mod gitignore;
+mod hooks;
+mod lock;
+mod logfile;
+mod lru;
+mod output;
+mod pause;
+mod persist;
+mod priority;
+mod pty;
+mod renames;
+mod rules;
+mod shutdown;
+mod stats;
+mod symlinks;
+mod systemd;
+mod tasks;
+mod tui;
+mod webhook;
+
 use anyhow::Result;
-use clap::Parser;
+use clap::{CommandFactory, FromArgMatches, Parser};
 use notify::{RecursiveMode, Watcher};
 use std::{
-    collections::{HashMap, VecDeque},
-    ffi::OsStr,
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
+    ffi::{OsStr, OsString},
+    hash::{Hash, Hasher},
     io::Write,
-    path::PathBuf,
-    sync::{Arc, Condvar, Mutex},
+    path::{Path, PathBuf},
+    process::{Child, ExitStatus, Stdio},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc,
+    },
     time::{Duration, Instant},
 };
 
+/// Kinds of filesystem events that may trigger the command.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+#[value(rename_all = "snake_case")]
+enum EventFilter {
+    /// A file opened for writing was closed
+    CloseWrite,
+    Create,
+    /// File contents or metadata changed
+    Modify,
+    Remove,
+    Rename,
+}
+
+impl EventFilter {
+    fn matches(&self, kind: &notify::EventKind) -> bool {
+        use notify::event::{AccessKind, AccessMode, ModifyKind};
+        use notify::EventKind;
+
+        match self {
+            Self::CloseWrite => {
+                matches!(
+                    kind,
+                    EventKind::Access(AccessKind::Close(AccessMode::Write))
+                )
+            }
+            Self::Create => matches!(kind, EventKind::Create(_)),
+            Self::Modify => {
+                matches!(kind, EventKind::Modify(m) if !matches!(m, ModifyKind::Name(_)))
+            }
+            Self::Remove => matches!(kind, EventKind::Remove(_)),
+            Self::Rename => matches!(kind, EventKind::Modify(ModifyKind::Name(_))),
+        }
+    }
+}
+
+/// When to run relative to a burst of changes.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Default)]
+enum Edge {
+    /// Upon the first change, ignoring the rest of the burst until settled
+    Leading,
+    /// Once changes have settled
+    #[default]
+    Trailing,
+}
+
+/// How much of the terminal to clear before each run.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+enum ClearMode {
+    /// The visible screen
+    Screen,
+    /// The visible screen and the scrollback buffer
+    Scrollback,
+}
+
+impl ClearMode {
+    fn clear(&self) -> std::io::Result<()> {
+        let sequence = match self {
+            Self::Screen => "\x1b[2J\x1b[H",
+            Self::Scrollback => "\x1b[3J\x1b[2J\x1b[H",
+        };
+        let mut stdout = std::io::stdout();
+        stdout.write_all(sequence.as_bytes())?;
+        stdout.flush()
+    }
+}
+
+/// What to do when the command exits unsuccessfully.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+enum OnError {
+    /// Keep watching for changes
+    #[default]
+    Continue,
+    /// Stop watching, exiting with the command's status
+    Stop,
+    /// Rerun the command up to `attempts` more times, `backoff` seconds apart
+    Retry { attempts: u32, backoff: f32 },
+}
+
+impl std::str::FromStr for OnError {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(':');
+        match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some("continue"), None, _, _) => Ok(Self::Continue),
+            (Some("stop"), None, _, _) => Ok(Self::Stop),
+            (Some("retry"), attempts, backoff, None) => {
+                let attempts = match attempts {
+                    Some(n) => n
+                        .parse()
+                        .map_err(|_| format!("invalid retry count: {:?}", n))?,
+                    None => 3,
+                };
+                let backoff = match backoff {
+                    Some(b) => match b.parse::<f32>() {
+                        Ok(b) if b.is_finite() && b >= 0.0 => b,
+                        _ => return Err(format!("invalid retry backoff: {:?}", b)),
+                    },
+                    None => 1.0,
+                };
+                Ok(Self::Retry { attempts, backoff })
+            }
+            _ => Err(format!(
+                "invalid policy: {:?} (expected stop, continue, or retry[:N[:BACKOFF]])",
+                s
+            )),
+        }
+    }
+}
+
+/// Where the command runs.
+#[derive(Debug, Clone, PartialEq)]
+enum Workdir {
+    /// The root of the repository
+    Root,
+    /// The directory of the (first) changed file
+    EventDir,
+    /// A directory, relative to the root of the repository
+    Path(PathBuf),
+}
+
+impl std::str::FromStr for Workdir {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "root" => Ok(Self::Root),
+            "event-dir" => Ok(Self::EventDir),
+            "" => Err("empty working directory".to_string()),
+            path => Ok(Self::Path(PathBuf::from(path))),
+        }
+    }
+}
+
+impl Workdir {
+    /// Directory to run `job` from.
+    fn resolve(&self, job: &Job) -> PathBuf {
+        match self {
+            Self::Root => job.root.to_path_buf(),
+            Self::EventDir => job
+                .trigger
+                .paths
+                .first()
+                .and_then(|path| path.parent())
+                .unwrap_or(job.root)
+                .to_path_buf(),
+            Self::Path(path) => job.root.join(path),
+        }
+    }
+}
+
+/// `KEY=VAL`, a variable to set in the environment of the command.
+#[derive(Debug, Clone, PartialEq)]
+struct EnvVar {
+    key: String,
+    value: String,
+}
+
+impl std::str::FromStr for EnvVar {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some((key, value)) if !key.is_empty() => Ok(Self {
+                key: key.to_string(),
+                value: value.to_string(),
+            }),
+            _ => Err(format!("invalid variable: {:?} (expected KEY=VAL)", s)),
+        }
+    }
+}
+
+/// Variables kept from the environment of git-watch with `--clean-env`.
+const KEPT_ENV: [&str; 3] = ["PATH", "HOME", "TERM"];
+
+/// Format of changes printed by `--emit`.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+enum EmitFormat {
+    /// Tab-separated timestamp, event, and path
+    Text,
+    /// One JSON object per line
+    Json,
+}
+
+impl EmitFormat {
+    fn print(&self, path: &Path, event: &str) {
+        let time = humantime::format_rfc3339_millis(std::time::SystemTime::now()).to_string();
+        match self {
+            Self::Text => println!("{}\t{}\t{}", time, event, path.display()),
+            Self::Json => println!(
+                "{{\"time\":{},\"event\":{},\"path\":{}}}",
+                logfile::json_string(&time),
+                logfile::json_string(event),
+                logfile::json_string(&path.to_string_lossy())
+            ),
+        }
+    }
+}
+
+// What to do instead of watching (not a doc comment, which clap would take as
+// the description of git-watch itself)
+#[derive(clap::Subcommand, Debug, Clone, PartialEq)]
+enum Action {
+    /// Install post-checkout, post-merge, and post-commit hooks that ask the watcher of
+    /// this repository to run through its control socket (see --socket), then exit
+    InstallHooks {
+        #[arg(long)]
+        /// Replace hooks not installed by git-watch
+        force: bool,
+    },
+}
+
 #[derive(Parser, Default, Debug, Clone)]
-#[command(author, version, about, long_about=None, propagate_version=true)]
+#[command(
+    author,
+    version,
+    about,
+    long_about=None,
+    propagate_version=true,
+    disable_help_subcommand=true
+)]
 struct Config {
-    /// Command(s) to execute
-    #[clap(num_args = 1..)]
+    #[command(subcommand)]
+    action: Option<Action>,
+
+    /// Subtrees to watch instead of the whole root; without `--`, these are taken as the
+    /// command instead
+    #[arg(value_name = "PATH")]
+    paths: Vec<String>,
+
+    /// Command(s) to execute; `{}`/`{files}` and `{dir}` are replaced with the changed paths
+    /// and their directories, and `{event}` with how they changed
+    #[arg(last = true)]
     command: Vec<String>,
 
+    #[arg(
+        long,
+        value_enum,
+        value_name = "FORMAT",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "text",
+        conflicts_with_all = ["command", "rule", "exec"]
+    )]
+    /// Print each actionable change to stdout instead of running a command
+    emit: Option<EmitFormat>,
+
+    #[arg(long, value_name = "GLOB=COMMAND")]
+    /// Run COMMAND through the shell for changes to paths matching GLOB (may be repeated);
+    /// the main command, if any, runs for changes matched by no rule
+    rule: Vec<rules::Rule>,
+
+    #[arg(long, value_name = "CMD", conflicts_with = "restart")]
+    /// Run CMD through the shell before the main command (may be repeated); commands run
+    /// in order, stopping at the first to fail
+    exec: Vec<String>,
+
+    #[arg(long, conflicts_with = "emit")]
+    /// Print which changes would trigger the command, and why others would not, then
+    /// which commands would run, without running them
+    dry_run: bool,
+
     #[arg(short = 'a', long, default_value = "30")]
-    /// Age of cache to be periodically pruned, in seconds
+    /// Age at which cached ignore results expire, in seconds
     age: f32,
 
     #[arg(short = '1', long)]
+    /// Run the command once, then exit with its exit status
     oneshot: bool,
 
+    #[arg(long, value_name = "N", conflicts_with = "oneshot")]
+    /// Run the command N times at most, then exit with the exit status of the last run
+    max_runs: Option<usize>,
+
+    #[arg(short, long)]
+    /// Stop watching and exit with the command's exit status once it fails (same as
+    /// `--on-error stop`)
+    fail_fast: bool,
+
+    #[arg(
+        long,
+        value_name = "POLICY",
+        default_value = "continue",
+        conflicts_with = "fail_fast"
+    )]
+    /// What to do when the command fails: `continue` watching, `stop`, or
+    /// `retry[:N[:BACKOFF]]` up to N times (default 3), BACKOFF seconds apart (default 1);
+    /// retries do not apply with --restart
+    on_error: OnError,
+
+    #[arg(long, value_name = "DIR")]
+    /// Run the command from `root`, the repository root; `event-dir`, the directory of
+    /// the (first) changed file; or DIR, relative to the root; by default, from the
+    /// current directory (or the root, with --repo)
+    workdir: Option<Workdir>,
+
+    #[arg(long, value_name = "KEY=VAL")]
+    /// Set KEY to VAL in the environment of the command (repeatable)
+    env: Vec<EnvVar>,
+
+    #[arg(long)]
+    /// Keep only PATH, HOME, and TERM from the environment of git-watch, besides the
+    /// variables given by --env and those describing the changes
+    clean_env: bool,
+
+    #[arg(
+        long,
+        value_name = "N",
+        allow_hyphen_values = true,
+        value_parser = clap::value_parser!(i32).range(-20..=19)
+    )]
+    /// Run the command at niceness N, from -20 (favored) to 19 (least favored)
+    nice: Option<i32>,
+
+    #[arg(long, value_enum, value_name = "CLASS")]
+    /// Run the command in the I/O scheduling class CLASS (Linux only)
+    ionice: Option<priority::IoClass>,
+
+    #[arg(long, value_name = "CMD", conflicts_with = "restart")]
+    /// Run CMD through the shell after each run that succeeds, as for a notification
+    on_success: Option<String>,
+
+    #[arg(long, value_name = "CMD", conflicts_with = "restart")]
+    /// Run CMD through the shell after each run that fails, with its exit code in
+    /// `GIT_WATCH_STATUS`
+    on_failure: Option<String>,
+
+    #[arg(short, long)]
+    /// Run the command once at startup, before any change is observed
+    initial: bool,
+
     #[arg(short = 'n', long, default_value = "1000")]
-    /// Maximum number of elements to retain in cache
+    /// Maximum number of elements to retain in cache, evicting the least recently used
     size: usize,
 
+    #[arg(long)]
+    /// Neither reuse nor save the ignore decisions kept in the git directory between
+    /// sessions
+    no_saved_ignores: bool,
+
     #[arg(short, long, default_value = "0.2")]
     /// Time allowed for the filesystem to settle before launching command
     settle: f32,
 
+    #[arg(long, value_name = "GLOB=SECS")]
+    /// Time allowed for changes to paths matching GLOB to settle instead (may be repeated),
+    /// for generators that write in long bursts
+    settle_for: Vec<filter::SettleFor>,
+
+    #[arg(long, value_name = "SECS", default_value = "0")]
+    /// Fixed time to wait once the filesystem has settled before launching command,
+    /// including any further changes meanwhile without waiting for them to settle
+    delay: f32,
+
+    #[arg(long)]
+    /// Ignore writes that leave a file's content unchanged, judged by its hash
+    hash_check: bool,
+
+    #[arg(
+        long,
+        value_name = "SECS",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "0",
+        conflicts_with_all = ["queue", "restart"]
+    )]
+    /// Discard changes made while the command runs, and for SECS after, so that
+    /// outputs of the command that are not ignored cannot retrigger it
+    ignore_self: Option<f32>,
+
+    #[arg(long, value_enum, default_value = "trailing")]
+    /// Whether to run upon the first change of a burst, or once it has settled
+    edge: Edge,
+
     #[arg(short, long)]
     /// Disable most output
     quiet: bool,
@@ -39,216 +421,2643 @@ struct Config {
     #[arg(short, long)]
     /// Enable verbose output (overrides --quiet)
     verbose: bool,
+
+    #[arg(long, conflicts_with_all = ["emit", "clear"])]
+    /// Show a live dashboard of changes, runs, and the output of the latest run
+    tui: bool,
+
+    #[arg(long)]
+    /// Omit the banners delimiting each run of the command (implied by --quiet)
+    no_banner: bool,
+
+    #[arg(long, conflicts_with_all = ["tui", "no_git"])]
+    /// Print the git status and diffstat of the changed files before each run of
+    /// the command
+    summary: bool,
+
+    #[arg(long)]
+    /// Ring the terminal bell when the command fails
+    bell: bool,
+
+    #[arg(long)]
+    /// Show the command and the status and duration of its last run in the terminal title
+    /// (and tmux window name)
+    set_title: bool,
+
+    #[arg(long, value_name = "PATH")]
+    /// Append a JSON-lines log of changes and runs to PATH, regardless of verbosity
+    log_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "BYTES",
+        default_value = "10485760",
+        requires = "log_file"
+    )]
+    /// Size at which the log file is rotated, keeping three older generations
+    log_size: u64,
+
+    #[arg(long, value_name = "PATH")]
+    /// On exit, also write the summary of the session (events, runs, settle time, cache hit
+    /// rate) to PATH as JSON
+    stats_json: Option<PathBuf>,
+
+    #[arg(short = 'c', long)]
+    /// Run the command through `$SHELL -c`, allowing pipes, `&&` chains, and redirections
+    shell: bool,
+
+    #[arg(long)]
+    /// Run the command in a pseudo-terminal, so that it keeps colored and progress output
+    pty: bool,
+
+    #[arg(long, conflicts_with = "tui")]
+    /// Prefix each line the command outputs with the time and "out" or "err"
+    prefix: bool,
+
+    #[arg(long, value_name = "URL")]
+    /// POST a JSON summary of each trigger and the outcome of its run to URL (using curl)
+    webhook: Option<String>,
+
+    #[arg(long, conflicts_with = "tui")]
+    /// Fork into the background, writing a pid file and sending all output to a log file
+    daemon: bool,
+
+    #[arg(long, conflicts_with = "daemon")]
+    /// Stop the daemon watching this repository, then exit
+    stop: bool,
+
+    #[arg(long, conflicts_with = "daemon")]
+    /// Stop, as on SIGTERM, once standard input closes, such as when the program that spawned
+    /// git-watch exits; anything written to standard input is discarded
+    exit_on_stdin_close: bool,
+
+    #[arg(long)]
+    /// Terminate any other git-watch running the same command for this repository, instead
+    /// of refusing to start
+    takeover: bool,
+
+    #[arg(long, value_name = "COMMAND", conflicts_with = "daemon")]
+    /// Send COMMAND to the control socket of the watcher of this repository, print its
+    /// reply, then exit
+    send: Option<String>,
+
+    #[arg(long, value_name = "PATH")]
+    /// Pid file of the daemon, by default under $XDG_RUNTIME_DIR and named after the repository
+    pid_file: Option<PathBuf>,
+
+    #[arg(long, value_name = "PATH", requires = "daemon")]
+    /// Log file of the daemon, by default next to its pid file
+    daemon_log: Option<PathBuf>,
+
+    #[arg(long, value_name = "PATH", num_args = 0..=1, require_equals = true)]
+    /// Accept `status`, `trigger`, `pause`, `resume`, and `quit` commands on a Unix socket,
+    /// by default under $XDG_RUNTIME_DIR and named after the repository
+    socket: Option<Option<PathBuf>>,
+
+    #[arg(long, value_name = "PATH")]
+    /// Discard changes while PATH exists, as when paused by SIGUSR1 (until SIGUSR2)
+    toggle_file: Option<PathBuf>,
+
+    #[arg(long, conflicts_with = "restart")]
+    /// Settle changes that arrive while the command runs into separate batches, each
+    /// processed in order, instead of coalescing them into the next run
+    queue: bool,
+
+    #[arg(long, conflicts_with = "restart")]
+    /// Run the command once per changed file, rather than once for all of them
+    per_file: bool,
+
+    #[arg(long)]
+    /// Append the changed files to the command's arguments, so that it only
+    /// operates on those
+    append_paths: bool,
+
+    #[arg(long, value_name = "N")]
+    /// Pass at most N changed files to each run of the command, splitting larger
+    /// batches into several runs
+    max_args: Option<usize>,
+
+    #[arg(short, long, value_name = "N")]
+    /// Maximum number of commands run at once; defaults to the number of CPUs with
+    /// --per-file, and 1 otherwise
+    jobs: Option<usize>,
+
+    #[arg(short, long)]
+    /// Terminate and relaunch the command if it is still running when new changes arrive
+    restart: bool,
+
+    #[arg(short, long, default_value = "2.0")]
+    /// Time allowed for the command to exit after SIGTERM (on restart) or a forwarded
+    /// SIGINT or SIGTERM (on shutdown) before being killed, in seconds
+    grace: f32,
+
+    #[arg(
+        long,
+        value_enum,
+        value_name = "MODE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "screen"
+    )]
+    /// Clear the terminal before each run
+    clear: Option<ClearMode>,
+
+    #[arg(short, long)]
+    /// Minimum interval between runs, in seconds; changes in between are coalesced into the next run
+    throttle: Option<f32>,
+
+    #[arg(long, value_name = "SECS", conflicts_with = "restart")]
+    /// Time after each run finishes during which changes are collected but cannot start
+    /// another run
+    cooldown: Option<f32>,
+
+    #[arg(
+        long,
+        value_name = "MAX",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "60",
+        conflicts_with = "restart"
+    )]
+    /// After each failure with no success since, double the minimum interval between
+    /// runs, from 1 second up to MAX seconds (default 60)
+    backoff: Option<f32>,
+
+    #[arg(long, value_name = "SECS")]
+    /// Poll the filesystem every SECS seconds instead of relying on native events, for
+    /// network filesystems and bind mounts; close_write is observed as modify
+    poll: Option<f32>,
+
+    #[arg(long, conflicts_with_all = ["no_git", "path"])]
+    /// Also trigger on changes to HEAD and branches (checkouts, commits, merges), reported
+    /// as event `ref`
+    refs: bool,
+
+    #[arg(long, conflicts_with_all = ["no_git", "path"])]
+    /// Also trigger when files are staged or unstaged, reported as event `index`
+    on_index: bool,
+
+    #[arg(long, conflicts_with_all = ["no_git", "path"])]
+    /// Descend into submodules and other nested repositories, applying their own ignore
+    /// rules, instead of ignoring them
+    submodules: bool,
+
+    #[arg(long, overrides_with = "no_follow_symlinks")]
+    /// Descend into symlinked directories, reporting changes by their real path when within
+    /// the repository so that each triggers once (the default)
+    follow_symlinks: bool,
+
+    #[arg(long, overrides_with = "follow_symlinks")]
+    /// Disregard changes reached through symlinked directories that lead outside the
+    /// repository
+    no_follow_symlinks: bool,
+
+    #[arg(long, conflicts_with = "no_git")]
+    /// Run the command only if the changed files differ from HEAD, or are untracked,
+    /// skipping changes that merely restore their committed content
+    diff_gate: bool,
+
+    #[arg(long)]
+    /// Evaluate ignore rules with `git check-ignore` instead of in-process, for exotic configurations
+    strict_git: bool,
+
+    #[arg(long, value_name = "DIR")]
+    /// Watch DIR instead of the enclosing git repository (implies --no-git)
+    path: Option<PathBuf>,
+
+    #[arg(long, value_name = "DIR", conflicts_with_all = ["path", "no_git", "repo"])]
+    /// Take DIR as the root of the working tree instead of asking git, as for a bare
+    /// repository given by GIT_DIR, whose working tree is elsewhere
+    root: Option<PathBuf>,
+
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["path", "no_git"])]
+    /// Watch the git repository containing PATH instead of the current one (may be
+    /// repeated), each with its own ignore rules and rules; commands run from the root of
+    /// the repository whose changes triggered them
+    repo: Vec<PathBuf>,
+
+    #[arg(long, conflicts_with = "strict_git")]
+    /// Watch the current directory without requiring a git repository, ignoring no paths
+    /// besides those excluded by --include/--exclude
+    no_git: bool,
+
+    #[arg(long, value_name = "GLOB")]
+    /// Only paths matching GLOB trigger the command (may be repeated)
+    include: Vec<String>,
+
+    #[arg(long, value_name = "EXT", value_delimiter = ',')]
+    /// Only files with extension EXT trigger the command, as if by --include "*.EXT"
+    ext: Vec<String>,
+
+    #[arg(long, value_name = "GLOB")]
+    /// Paths matching GLOB never trigger the command (may be repeated)
+    exclude: Vec<String>,
+
+    #[arg(
+        short,
+        long,
+        value_enum,
+        value_delimiter = ',',
+        default_value = "close_write"
+    )]
+    /// Kinds of filesystem events that trigger the command
+    events: Vec<EventFilter>,
 }
 
-struct Cache {
-    config: Config,
-    filenames: HashMap<PathBuf, bool>,
-    eviction_times: VecDeque<CacheMeta>,
+impl Config {
+    /// Parse arguments, taking positional arguments as the command when not separated
+    /// from paths by `--`, unless `--emit`, `--rule`, or `--exec` gives the work to do.
+    ///
+    /// The matches are returned as well, to tell which settings were given explicitly.
+    fn parse_args<I, T>(args: I) -> (Self, clap::ArgMatches)
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let matches = Self::command().get_matches_from(args);
+        let mut config = Self::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+        if config.command.is_empty()
+            && config.emit.is_none()
+            && config.rule.is_empty()
+            && config.exec.is_empty()
+        {
+            config.command = std::mem::take(&mut config.paths);
+        }
+        (config, matches)
+    }
+}
+
+/// Messages to the dispatcher, which settles changes into triggers, from the
+/// watchers, the control socket, and signal handling.
+enum Event {
+    /// A path changed, as observed by a watcher
+    Change {
+        path: PathBuf,
+        event: &'static str,
+        observed: Instant,
+    },
+    /// The file at `from` was renamed to `to`; the changes to both paths are
+    /// reported as well, if renames are `monitored`
+    Rename {
+        from: PathBuf,
+        to: PathBuf,
+        observed: Instant,
+        monitored: bool,
+    },
+    /// A run was requested regardless of changes
+    Force,
+    /// Watching should stop
+    Quit,
 }
-struct CacheMeta {
-    eviction_time: Instant,
-    path: PathBuf,
+
+/// Changes handed to a single run of the command.
+#[derive(Default, Debug, Clone)]
+struct Trigger {
+    paths: Vec<PathBuf>,
+    event: Option<&'static str>,
+    /// How each path changed over the batch, as given by `change_kind`
+    kinds: HashMap<PathBuf, &'static str>,
+    /// Paths renamed, from and to
+    renames: Vec<(PathBuf, PathBuf)>,
 }
 
-impl Cache {
-    fn new(config: Config) -> Self {
-        Self {
-            config,
-            filenames: HashMap::new(),
-            eviction_times: VecDeque::new(),
+/// A command, and whether to run it through the shell.
+type Step<'a> = (&'a [String], bool);
+
+/// Commands to run in sequence from a root, for some changes.
+type Group<'a> = (&'a Path, Vec<Step<'a>>, Vec<PathBuf>);
+
+/// A command to run, along with the changes it is responsible for.
+struct Job<'a> {
+    /// Root of the repository whose changes these are
+    root: &'a Path,
+    command: &'a [String],
+    shell: bool,
+    trigger: Trigger,
+    /// Command to run next, for the same changes, should this one succeed
+    then: Option<Box<Job<'a>>>,
+}
+
+impl<'a> Job<'a> {
+    /// Chain `steps` into one job, or `None` if there are no steps.
+    fn sequence(root: &'a Path, steps: &[Step<'a>], trigger: &Trigger) -> Option<Self> {
+        steps.iter().rev().fold(None, |then, &(command, shell)| {
+            Some(Job {
+                root,
+                command,
+                shell,
+                trigger: trigger.clone(),
+                then: then.map(Box::new),
+            })
+        })
+    }
+}
+
+impl Event {
+    fn change(path: &Path, event: &'static str) -> Self {
+        stats::event();
+        Self::Change {
+            path: path.to_path_buf(),
+            event,
+            observed: Instant::now(),
+        }
+    }
+}
+
+impl Trigger {
+    fn push(&mut self, path: PathBuf, event: &'static str) {
+        self.event = Some(event);
+        let kind = change_kind(event);
+        match self.kinds.get_mut(&path) {
+            // a file created, then written, is still new to the command
+            Some(earlier) if *earlier == "created" && kind == "modified" => {}
+            Some(earlier) => *earlier = kind,
+            None => {
+                self.kinds.insert(path.clone(), kind);
+            }
+        }
+        if !self.paths.contains(&path) {
+            self.paths.push(path);
         }
     }
 
-    fn is_actionable(&mut self, path: &PathBuf) -> bool {
-        !self.is_ignored(path)
+    /// Disregard the changes to `path`.
+    fn forget(&mut self, path: &Path) {
+        self.paths.retain(|p| p != path);
+        self.kinds.remove(path);
     }
 
-    fn is_ignored(&mut self, path: &PathBuf) -> bool {
-        let now = Instant::now();
+    /// How `path` changed.
+    fn kind(&self, path: &Path) -> &'static str {
+        self.kinds.get(path).copied().unwrap_or("modified")
+    }
 
-        // evict cache entries when tracking too many
-        while self.eviction_times.len() >= self.config.size {
-            if let Some(cache_meta) = self.eviction_times.pop_front() {
-                self.filenames.remove(&cache_meta.path);
-            }
+    /// Each path, and how it changed.
+    fn path_kinds(&self) -> Vec<(&Path, &'static str)> {
+        self.paths
+            .iter()
+            .map(|path| (path.as_path(), self.kind(path)))
+            .collect()
+    }
+
+    /// How all the paths changed, `changed` if not all alike, or the event if
+    /// there are no paths (such as at startup).
+    fn change(&self) -> &'static str {
+        let mut kinds = self.paths.iter().map(|path| self.kind(path));
+        match kinds.next() {
+            Some(first) if kinds.all(|kind| kind == first) => first,
+            Some(_) => "changed",
+            None => self.event.unwrap_or(""),
         }
+    }
 
-        // evict cache entries when entries are deemed too old
-        loop {
-            if let Some(cache_meta) = self.eviction_times.front() {
-                if cache_meta.eviction_time < now {
-                    self.filenames.remove(&cache_meta.path);
-                    let evicted = self.eviction_times.pop_front().unwrap();
-                    log::debug!("Stale cache evicted for file {:?}", evicted.path);
-                    continue; // potentially more to evict
-                }
-            }
-            break; // nothing more to evict
+    /// Each path preceded by how it changed, one per line.
+    fn describe(&self) -> OsString {
+        let mut described = OsString::new();
+        for path in self.paths.iter() {
+            described.push(self.kind(path));
+            described.push(" ");
+            described.push(path);
+            described.push("\n");
         }
+        described
+    }
 
-        // use prior cache value
-        if let Some(&is_ignored) = self.filenames.get(path) {
-            log::debug!(
-                "Using cached result {:?} for file {:?}",
-                if is_ignored { "ignored" } else { "actionable" },
-                path
-            );
-            return is_ignored;
+    /// Each rename, from and to separated by a tab, one per line.
+    fn describe_renames(&self) -> OsString {
+        let mut described = OsString::new();
+        for (from, to) in self.renames.iter() {
+            described.push(from);
+            described.push("\t");
+            described.push(to);
+            described.push("\n");
         }
+        described
+    }
+}
 
-        // determine if the file is trackable (error return code means not ignored)
-        let git_output = std::process::Command::new("git")
-            .args([
-                OsStr::new("check-ignore"),
-                OsStr::new("--quiet"),
-                path.as_os_str(),
-            ])
-            .output()
-            .expect("failed to execute git");
+/// Event name of changes to HEAD or branches.
+const REF_EVENT: &str = "ref";
 
-        let is_ignored = git_output.status.success();
+/// Event name of runs requested through the control socket.
+const MANUAL_EVENT: &str = "manual";
 
-        // cache results
-        self.filenames.insert(path.clone(), is_ignored);
-        self.eviction_times.push_back(CacheMeta {
-            eviction_time: now + Duration::from_secs_f32(self.config.age),
-            path: path.clone(),
-        });
+/// Event name of changes to the index.
+const INDEX_EVENT: &str = "index";
 
-        log::debug!(
-            "Determined new result {:?} for file {:?}",
-            if is_ignored { "ignored" } else { "actionable" },
-            path
-        );
+/// Whether changes with `event` are within the git directory, so bypass ignore rules.
+fn is_git_event(event: &str) -> bool {
+    event == REF_EVENT || event == INDEX_EVENT
+}
 
-        is_ignored
+/// Event for a change to `relative`, within the git directory, if of interest.
+fn git_event(relative: &Path, refs: bool, index: bool) -> Option<&'static str> {
+    if index && relative == Path::new("index") {
+        Some(INDEX_EVENT)
+    } else if refs && is_ref(relative) {
+        Some(REF_EVENT)
+    } else {
+        None
     }
 }
 
-fn init_logger(config: &Config) {
-    let level = if config.verbose {
-        log::LevelFilter::Debug
-    } else if config.quiet {
-        log::LevelFilter::Error
-    } else {
-        log::LevelFilter::Info
-    };
+/// Path of `path` relative to the git directory, for files of a single worktree
+/// (such as HEAD and the index), or the common directory, for files shared by all
+/// worktrees (such as branches).
+fn git_relative<'a>(path: &'a Path, git_dir: &Path, common_dir: &Path) -> Option<&'a Path> {
+    if let Ok(relative) = path.strip_prefix(git_dir) {
+        return Some(relative);
+    }
+    path.strip_prefix(common_dir)
+        .ok()
+        .filter(|relative| relative.starts_with("refs") || *relative == Path::new("packed-refs"))
+}
 
-    env_logger::Builder::new()
-        .format_level(false)
-        .format(|buf, record| writeln!(buf, "{}", record.args()))
-        .filter(None, level)
-        .init();
+/// Whether `path`, relative to the git directory, is HEAD or a branch.
+fn is_ref(relative: &Path) -> bool {
+    if relative.extension() == Some(OsStr::new("lock")) {
+        return false; // written first, then renamed into place
+    }
+    relative == Path::new("HEAD")
+        || relative == Path::new("packed-refs")
+        || relative.starts_with("refs/heads")
 }
 
-fn run_command(config: &Config) -> Result<()> {
-    // Quick test to execute the command
-    let user_command = std::process::Command::new(&config.command[0])
-        .args(&config.command[1..])
-        .status();
+/// How a file changed, as `{event}` exposes it to the command: `created`,
+/// `modified`, `removed`, or `renamed`; git and manual events keep their name.
+fn change_kind(event: &'static str) -> &'static str {
+    match event {
+        "create" => "created",
+        "remove" => "removed",
+        "rename" => "renamed",
+        "close_write" | "modify" | "access" | "other" => "modified",
+        event => event,
+    }
+}
 
-    let status = match user_command {
-        Ok(s) => s,
-        Err(_) => {
-            // Error if the command could not be found
-            anyhow::bail!("command not found: {}", &config.command[0])
-        }
-    };
+/// Short name of an event kind, as exposed to the command.
+fn event_name(kind: &notify::EventKind) -> &'static str {
+    use notify::event::{AccessKind, AccessMode, ModifyKind};
+    use notify::EventKind;
 
-    if status.success() {
-        log::debug!("Command success: {:?}", config.command);
-    } else {
-        log::debug!("Command failure: {:?}", config.command);
+    match kind {
+        EventKind::Access(AccessKind::Close(AccessMode::Write)) => "close_write",
+        EventKind::Access(_) => "access",
+        EventKind::Create(_) => "create",
+        EventKind::Modify(ModifyKind::Name(_)) => "rename",
+        EventKind::Modify(_) => "modify",
+        EventKind::Remove(_) => "remove",
+        EventKind::Any | EventKind::Other => "other",
     }
+}
 
-    // Success if command was found and run, regardless of return code
-    Ok(())
+/// Source of truth for whether a path is ignored.
+enum Ignores {
+    /// Gitignore rules evaluated in-process
+    Rules(Box<gitignore::IgnoreRules>),
+    /// Gitignore rules evaluated by `git check-ignore`
+    Git { root: PathBuf, submodules: bool },
+    /// Not a repository; nothing is ignored
+    Nothing,
 }
 
-fn main() -> Result<()> {
-    let config = Config::parse();
-    init_logger(&config);
+struct Cache {
+    root: PathBuf,
+    /// Whether each recently seen path is ignored
+    filenames: lru::Lru<PathBuf, bool>,
+    ignores: Ignores,
+    /// Decisions of the ignore rules, kept between sessions
+    decisions: Option<persist::Decisions>,
+    filters: filter::Filters,
+    settle_times: filter::SettleTimes,
+}
 
-    log::debug!("{:#?}", config);
+impl Cache {
+    fn new(config: Config, root: &Path, git: bool) -> Result<Self> {
+        let ignores = match (git, config.strict_git) {
+            (false, _) => Ignores::Nothing,
+            (true, true) => Ignores::Git {
+                root: root.to_path_buf(),
+                submodules: config.submodules,
+            },
+            (true, false) => Ignores::Rules(Box::new(gitignore::IgnoreRules::new(
+                root,
+                config.submodules,
+            ))),
+        };
+        let mut include = config.include.clone();
+        include.extend(
+            config
+                .ext
+                .iter()
+                .map(|ext| format!("*.{}", ext.trim_start_matches('.'))),
+        );
+        let filters = filter::Filters::new(root, &include, &config.exclude)?;
+        let settle_times = filter::SettleTimes::new(root, &config.settle_for)?;
+        let decisions = (git && !config.no_saved_ignores)
+            .then(|| persist::Decisions::load(root, config.submodules));
+        Ok(Self {
+            root: root.to_path_buf(),
+            filenames: lru::Lru::new(config.size, Duration::from_secs_f32(config.age)),
+            ignores,
+            decisions,
+            filters,
+            settle_times,
+        })
+    }
 
-    anyhow::ensure!(!config.command.is_empty(), "no command argument provided");
-    // let work_queue = Arc::new(Mutex::new(VecDeque::new()));
-    let work_trigger = Arc::new((Mutex::new(0_usize), Condvar::new()));
+    /// Discard all cached results, such as after ignore rules have changed.
+    fn invalidate(&mut self) {
+        self.filenames.clear();
+        if let Ignores::Rules(ref mut rules) = self.ignores {
+            rules.reload();
+        }
+        if let Some(ref mut decisions) = self.decisions {
+            decisions.invalidate();
+        }
+    }
 
-    let root = std::process::Command::new("git")
-        .args(["rev-parse", "--show-toplevel"])
-        .output()
-        .expect("unable to determine git root")
-        .stdout;
-    let root = String::from_utf8(root).expect("unable to parse root path");
-    let root = root.trim();
-    let root = std::path::Path::new(root);
+    /// Determine which of `paths` the ignore rules exclude, reusing the decisions
+    /// of earlier sessions where those still apply.
+    fn consult_ignores(&mut self, paths: &[&PathBuf]) -> Vec<bool> {
+        let saved: Vec<Option<bool>> = paths
+            .iter()
+            .map(|path| self.decisions.as_mut().and_then(|d| d.get(path)))
+            .collect();
+        let unknown: Vec<&PathBuf> = paths
+            .iter()
+            .zip(&saved)
+            .filter(|(_, saved)| saved.is_none())
+            .map(|(path, _)| *path)
+            .collect();
+        if unknown.len() < paths.len() {
+            log::debug!(
+                "Reusing {} saved ignore decisions",
+                paths.len() - unknown.len()
+            );
+        }
+        let decided = match self.ignores {
+            _ if unknown.is_empty() => Vec::new(),
+            Ignores::Rules(ref mut rules) => {
+                unknown.iter().map(|path| rules.is_ignored(path)).collect()
+            }
+            Ignores::Git {
+                ref root,
+                submodules,
+            } => git_check_ignore_nested(root, &unknown, submodules),
+            Ignores::Nothing => vec![false; unknown.len()],
+        };
+        if let Some(ref mut decisions) = self.decisions {
+            for (path, &ignored) in unknown.iter().zip(&decided) {
+                decisions.insert(path, ignored);
+            }
+        }
 
-    log::info!("Running with root: {:?}", root);
+        let mut decided = decided.into_iter();
+        saved
+            .into_iter()
+            .map(|saved| saved.or_else(|| decided.next()).unwrap_or(false))
+            .collect()
+    }
 
-    let mut cache = Cache::new(config.clone());
+    /// Determine which of `paths` are ignored, querying only those not cached.
+    fn are_ignored(&mut self, paths: &[&PathBuf]) -> Vec<bool> {
+        let now = Instant::now();
 
-    // Automatically select the best implementation for your platform.
-    let work_trigger2 = Arc::clone(&work_trigger);
-    let mut watcher = notify::recommended_watcher(move |result: notify::Result<notify::Event>| {
-        use notify::event::AccessKind;
-        use notify::event::AccessMode;
+        // use prior cache values
+        let mut results: Vec<Option<bool>> = paths
+            .iter()
+            .map(|path| {
+                let cached = self.filenames.get(*path, now);
+                stats::cache(cached.is_some());
+                if let Some(is_ignored) = cached {
+                    log::debug!(
+                        "Using cached result {:?} for file {:?}",
+                        if is_ignored { "ignored" } else { "actionable" },
+                        path
+                    );
+                }
+                cached
+            })
+            .collect();
 
-        use notify::EventKind;
+        let unknown: Vec<&PathBuf> = paths
+            .iter()
+            .zip(&results)
+            .filter(|(_, cached)| cached.is_none())
+            .map(|(path, _)| *path)
+            .collect();
+        if unknown.is_empty() {
+            return results.into_iter().flatten().collect();
+        }
 
-        let mut monitored: bool = false;
+        // filters are cheap, so only consult ignore rules for paths they let through
+        let excluded: Vec<bool> = unknown
+            .iter()
+            .map(|path| self.filters.is_excluded(path))
+            .collect();
+        let candidates: Vec<&PathBuf> = unknown
+            .iter()
+            .zip(&excluded)
+            .filter(|(_, excluded)| !**excluded)
+            .map(|(path, _)| *path)
+            .collect();
+        let ignored = self.consult_ignores(&candidates);
+        let mut ignored = ignored.into_iter();
+        let determined: Vec<bool> = excluded
+            .into_iter()
+            .map(|excluded| excluded || ignored.next().unwrap_or(false))
+            .collect();
 
-        if let Ok(event) = result {
-            if let EventKind::Access(AccessKind::Close(AccessMode::Write)) = event.kind {
-                monitored = true;
+        let mut determined = determined.into_iter();
+        for (path, result) in paths.iter().zip(results.iter_mut()) {
+            if result.is_some() {
+                continue;
             }
+            let is_ignored = determined.next().unwrap_or(false);
+            *result = Some(is_ignored);
 
-            if monitored {
-                for path in event.paths.iter() {
-                    if cache.is_actionable(path) {
-                        (*work_trigger2.0.lock().unwrap()) += 1;
-                        work_trigger2.1.notify_one();
-                    }
-                }
-            }
+            // cache results
+            self.filenames.insert((*path).clone(), is_ignored, now);
+
+            log::debug!(
+                "Determined new result {:?} for file {:?}",
+                if is_ignored { "ignored" } else { "actionable" },
+                path
+            );
         }
-    })?;
 
-    // Add a path to be watched. All files and directories at that path and
-    // below will be monitored for changes.
-    watcher.watch(root, RecursiveMode::Recursive)?;
+        let (hits, misses) = stats::cache_counts();
+        log::debug!(
+            "Cache holds {} entries; {} hits, {} misses so far",
+            self.filenames.len(),
+            hits,
+            misses
+        );
+        results.into_iter().flatten().collect()
+    }
 
-    // skip top-level git directory
-    if watcher.unwatch(&root.join(".git")).is_err() {
-        log::warn!("top level \".git\" directory not found and not ignored");
+    /// Why `path`, found to be ignored, is.
+    fn why_ignored(&self, path: &Path) -> &'static str {
+        match self.ignores {
+            _ if self.filters.is_excluded(path) => "excluded by --include/--exclude",
+            Ignores::Nothing => "excluded",
+            _ => "ignored by git",
+        }
     }
+}
 
-    let (lock, cond) = &*work_trigger;
-    let mut prev = 0_usize;
-    let mut curr = lock.lock().unwrap();
-    loop {
-        curr = cond.wait(curr).unwrap();
-        if prev != *curr {
-            loop {
-                let settle_check = cond
-                    .wait_timeout(curr, Duration::from_secs_f32(config.settle))
-                    .unwrap();
-                curr = settle_check.0;
-                if settle_check.1.timed_out() {
-                    log::debug!("Filesystem settled");
-                    break; // filesystem has settled
-                }
-            }
+/// Caches of the watched repositories, each consulted for the paths within it.
+struct Caches(Vec<Cache>);
 
-            run_command(&config)?;
-        }
-        prev = *curr;
+impl Caches {
+    /// The cache of the innermost repository containing `path`.
+    fn index(&self, path: &Path) -> usize {
+        innermost(self.0.iter().map(|cache| cache.root.as_path()), path).unwrap_or(0)
+    }
 
-        if config.oneshot {
-            break;
+    fn roots(&self) -> impl Iterator<Item = &Path> + Clone {
+        self.0.iter().map(|cache| cache.root.as_path())
+    }
+
+    /// Discard the cached results of the repository containing `path`.
+    fn invalidate(&mut self, path: &Path) {
+        let idx = self.index(path);
+        self.0[idx].invalidate();
+    }
+
+    /// Determine which of `paths` are ignored, each by the rules of its repository.
+    fn are_ignored(&mut self, paths: &[&PathBuf]) -> Vec<bool> {
+        let mut results = vec![false; paths.len()];
+        for idx in 0..self.0.len() {
+            let (positions, within): (Vec<usize>, Vec<&PathBuf>) = paths
+                .iter()
+                .enumerate()
+                .filter(|(_, path)| self.index(path) == idx)
+                .map(|(pos, path)| (pos, *path))
+                .unzip();
+            if within.is_empty() {
+                continue;
+            }
+            let ignored = self.0[idx].are_ignored(&within);
+            for (pos, ignored) in positions.into_iter().zip(ignored) {
+                results[pos] = ignored;
+            }
         }
+        results
+    }
+
+    fn why_ignored(&self, path: &Path) -> &'static str {
+        self.0[self.index(path)].why_ignored(path)
+    }
+
+    /// Time to let changes to `path` settle, if overridden by `--settle-for`.
+    fn settle_for(&self, path: &Path) -> Option<Duration> {
+        let secs = self.0[self.index(path)].settle_times.get(path)?;
+        Some(Duration::from_secs_f32(secs))
+    }
+}
+
+/// Print whether a change would trigger the command, or the `reason` it would not.
+fn report(path: &Path, event: &str, reason: Option<&str>) {
+    match reason {
+        None => println!("trigger  {:<11} {}", event, path.display()),
+        Some(reason) => println!("skip     {:<11} {} ({})", event, path.display(), reason),
     }
+}
 
-    Ok(())
+/// Determine which of `paths` are ignored with `git check-ignore`, asking each
+/// nested repository about its own paths if descending into `submodules`, and
+/// otherwise ignoring them.
+fn git_check_ignore_nested(root: &Path, paths: &[&PathBuf], submodules: bool) -> Vec<bool> {
+    let mut results = vec![false; paths.len()];
+    let mut repos: Vec<(PathBuf, Vec<usize>)> = Vec::new();
+    for (idx, path) in paths.iter().enumerate() {
+        let repo = match gitignore::nested_repo(root, path) {
+            Some(_) if !submodules => {
+                results[idx] = true;
+                continue;
+            }
+            Some(repo) => repo,
+            None => root.to_path_buf(),
+        };
+        match repos.iter_mut().find(|(r, _)| *r == repo) {
+            Some((_, indices)) => indices.push(idx),
+            None => repos.push((repo, vec![idx])),
+        }
+    }
+
+    for (repo, indices) in repos {
+        let batch: Vec<&PathBuf> = indices.iter().map(|&idx| paths[idx]).collect();
+        for (idx, is_ignored) in indices.into_iter().zip(git_check_ignore(&repo, &batch)) {
+            results[idx] = is_ignored;
+        }
+    }
+    results
+}
+
+/// Determine which of `paths` are ignored with a single `git check-ignore` process
+/// run within `repo`.
+fn git_check_ignore(repo: &Path, paths: &[&PathBuf]) -> Vec<bool> {
+    use std::process::Stdio;
+
+    let child = std::process::Command::new("git")
+        .current_dir(repo)
+        .args([
+            "check-ignore",
+            "--stdin",
+            "-z",
+            "--verbose",
+            "--non-matching",
+        ])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(e) => {
+            log::warn!("failed to execute git: {}", e);
+            return vec![false; paths.len()];
+        }
+    };
+
+    // feed paths from another thread, so that a large batch cannot fill both pipes
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let mut input = Vec::new();
+    for path in paths {
+        input.extend_from_slice(path.as_os_str().as_encoded_bytes());
+        input.push(0);
+    }
+    let writer = std::thread::spawn(move || stdin.write_all(&input));
+
+    let output = match child.wait_with_output() {
+        Ok(output) => output,
+        Err(e) => {
+            log::warn!("failed to execute git: {}", e);
+            return vec![false; paths.len()];
+        }
+    };
+    let _ = writer.join();
+
+    // each path yields: source, line number, pattern, and path (all empty but path if not matched)
+    let fields: Vec<&[u8]> = output.stdout.split(|&b| b == 0).collect();
+    let mut results: Vec<bool> = fields
+        .chunks_exact(4)
+        .map(|record| !record[0].is_empty() && !record[2].starts_with(b"!"))
+        .collect();
+
+    if results.len() != paths.len() {
+        log::warn!("unexpected output from git check-ignore; treating paths as actionable");
+        results = vec![false; paths.len()];
+    }
+    results
+}
+
+/// What `git status` and `git diff --stat` report for `paths` within `repo`.
+fn git_summary(repo: &Path, paths: &[PathBuf]) -> String {
+    let mut summary = String::new();
+    for args in [&["status", "--porcelain"][..], &["diff", "--stat"]] {
+        let output = std::process::Command::new("git")
+            .current_dir(repo)
+            .args(args)
+            .arg("--")
+            .args(paths)
+            .stderr(Stdio::null())
+            .output();
+        match output {
+            Ok(output) => summary.push_str(&String::from_utf8_lossy(&output.stdout)),
+            Err(e) => log::warn!("failed to execute git: {}", e),
+        }
+    }
+    summary
+}
+
+/// Whether any of `paths` within `repo` differs from HEAD, or is untracked; if
+/// git fails, they are assumed to differ.
+fn differs_from_head(repo: &Path, paths: &[PathBuf]) -> bool {
+    let git = |args: &[&str]| {
+        std::process::Command::new("git")
+            .current_dir(repo)
+            .args(args)
+            .arg("--")
+            .args(paths)
+            .stderr(Stdio::null())
+            .output()
+    };
+    match git(&["diff", "--quiet", "HEAD"]) {
+        Ok(output) if output.status.success() => {}
+        Ok(_) => return true, // differences, or no commit yet
+        Err(e) => {
+            log::warn!("failed to execute git: {}", e);
+            return true;
+        }
+    }
+    match git(&["ls-files", "--others", "--exclude-standard"]) {
+        Ok(output) => !output.stdout.is_empty(),
+        Err(_) => true,
+    }
+}
+
+/// Top-level directory of the working tree of the enclosing git repository, or
+/// of that named by `GIT_DIR` and `GIT_WORK_TREE`.
+fn git_root(dir: &Path) -> Result<PathBuf> {
+    let output = std::process::Command::new("git")
+        .args(["rev-parse", "--show-toplevel"])
+        .current_dir(dir)
+        .output()
+        .map_err(|e| anyhow::anyhow!("unable to determine git root: {}", e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let reason = stderr
+            .lines()
+            .last()
+            .map(|line| line.trim_start_matches("fatal: "))
+            .filter(|line| !line.is_empty())
+            .unwrap_or("not within a git repository");
+        anyhow::bail!(
+            "{} (use --root to name the working tree, or --no-git or --path to watch any directory)",
+            reason
+        );
+    }
+    let root = String::from_utf8(output.stdout)
+        .map_err(|_| anyhow::anyhow!("git root is not valid UTF-8 (use --root to name it)"))?;
+    let root = root.trim_end_matches('\n');
+    anyhow::ensure!(
+        !root.is_empty(),
+        "not within a working tree (use --root to name it)"
+    );
+    Ok(PathBuf::from(root))
+}
+
+/// Per-repository file under `$XDG_RUNTIME_DIR` (or the temporary directory),
+/// named after `root` and disambiguated by a hash of its path.
+fn runtime_path(root: &Path, extension: &str) -> PathBuf {
+    let mut hasher = DefaultHasher::new();
+    root.hash(&mut hasher);
+    let name = root
+        .file_name()
+        .map_or("root".into(), |name| name.to_string_lossy());
+    let dir = std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir);
+    dir.join(format!(
+        "git-watch-{}-{:08x}.{}",
+        name,
+        hasher.finish() as u32,
+        extension
+    ))
+}
+
+/// Lock file of watchers of `root` running the commands of `config`.
+fn lock_path(root: &Path, config: &Config) -> PathBuf {
+    let mut hasher = DefaultHasher::new();
+    (&config.command, &config.exec).hash(&mut hasher);
+    for rule in config.rule.iter() {
+        (&rule.glob, &rule.command).hash(&mut hasher);
+    }
+    runtime_path(root, &format!("{:08x}.lock", hasher.finish() as u32))
+}
+
+fn init_logger(config: &Config) {
+    let level = if config.verbose {
+        log::LevelFilter::Debug
+    } else if config.quiet {
+        log::LevelFilter::Error
+    } else {
+        log::LevelFilter::Info
+    };
+
+    env_logger::Builder::new()
+        .format_level(false)
+        .format(|buf, record| writeln!(buf, "{}", record.args()))
+        .filter(None, level)
+        .target(env_logger::Target::Pipe(Box::new(tui::LogWriter)))
+        .init();
+}
+
+/// Replace placeholders within `args` with the changes of `trigger`.
+///
+/// An argument consisting solely of `{}` or `{files}` expands to one argument per
+/// path, and `{dir}` to one argument per unique parent directory; placeholders
+/// within a larger argument are replaced with the space-separated list. `{event}`
+/// is replaced with how the paths changed, as given by `Trigger::change`.
+///
+/// In `shell` mode, arguments are joined into a single script with each path quoted.
+fn substitute(args: &[String], trigger: &Trigger, shell: bool, append: bool) -> Vec<OsString> {
+    let paths = &trigger.paths;
+    let mut dirs: Vec<&Path> = Vec::new();
+    for dir in paths.iter().filter_map(|p| p.parent()) {
+        if !dirs.contains(&dir) {
+            dirs.push(dir);
+        }
+    }
+    let files: Vec<&Path> = paths.iter().map(PathBuf::as_path).collect();
+
+    let join = |items: &[&Path]| {
+        let items: Vec<String> = items
+            .iter()
+            .map(|p| match shell {
+                true => shell_quote(&p.to_string_lossy()),
+                false => p.to_string_lossy().into_owned(),
+            })
+            .collect();
+        items.join(" ")
+    };
+    let replace = |arg: &str| {
+        let files = join(&files);
+        arg.replace("{files}", &files)
+            .replace("{}", &files)
+            .replace("{dir}", &join(&dirs))
+            .replace("{event}", trigger.change())
+    };
+
+    if shell {
+        let mut script = replace(&args.join(" "));
+        if append && !files.is_empty() {
+            script = format!("{} {}", script, join(&files));
+        }
+        return vec![script.into()];
+    }
+
+    let mut expanded = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "{}" | "{files}" => expanded.extend(files.iter().map(|p| p.as_os_str().to_owned())),
+            "{dir}" => expanded.extend(dirs.iter().map(|p| p.as_os_str().to_owned())),
+            _ if ["{}", "{files}", "{dir}", "{event}"]
+                .iter()
+                .any(|placeholder| arg.contains(placeholder)) =>
+            {
+                expanded.push(replace(arg).into());
+            }
+            _ => expanded.push(arg.into()),
+        }
+    }
+    if append {
+        expanded.extend(files.iter().map(|p| p.as_os_str().to_owned()));
+    }
+    expanded
+}
+
+/// Set up the environment of `command` as given by `--clean-env` and `--env`.
+fn configure_env(config: &Config, command: &mut std::process::Command) {
+    if config.clean_env {
+        command.env_clear();
+        for key in KEPT_ENV {
+            if let Some(value) = std::env::var_os(key) {
+                command.env(key, value);
+            }
+        }
+    }
+    for EnvVar { key, value } in config.env.iter() {
+        command.env(key, value);
+    }
+}
+
+/// Quote `s` as a single word for POSIX shells.
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+/// Command to run `args`, through `$SHELL -c` (or `cmd /C` on Windows) in shell mode.
+///
+/// Errors if there are no `args`, as when the command is only a placeholder for
+/// paths and there are none, such as for `--initial`.
+fn build_command(shell: bool, args: &[OsString]) -> Result<std::process::Command> {
+    if !shell {
+        let Some((program, args)) = args.split_first() else {
+            anyhow::bail!("command expanded to nothing, there being no changed paths");
+        };
+        let mut command = std::process::Command::new(program);
+        command.args(args);
+        return Ok(command);
+    }
+
+    let (shell, flag) = if cfg!(windows) {
+        (OsString::from("cmd"), "/C")
+    } else {
+        (
+            std::env::var_os("SHELL").unwrap_or_else(|| "sh".into()),
+            "-c",
+        )
+    };
+    let mut command = std::process::Command::new(shell);
+    command.arg(flag).args(args);
+    Ok(command)
+}
+
+/// Newline-separated list of `paths`, as exposed to the command.
+fn join_lines(paths: &[PathBuf]) -> OsString {
+    let mut joined = OsString::new();
+    for path in paths {
+        joined.push(path);
+        joined.push("\n");
+    }
+    joined
+}
+
+/// A watched repository, with the rules for changes within it.
+struct Repo {
+    root: PathBuf,
+    rules: rules::Rules,
+}
+
+/// Index of the innermost of `roots` containing `path`, if any does.
+fn innermost<'a>(roots: impl Iterator<Item = &'a Path>, path: &Path) -> Option<usize> {
+    roots
+        .enumerate()
+        .filter(|(_, root)| path.starts_with(root))
+        .max_by_key(|(_, root)| root.as_os_str().len())
+        .map(|(idx, _)| idx)
+}
+
+/// Commands to run for `trigger`, for each repository with changes: each rule with
+/// the changes it matches, then the `--exec` commands and main command in sequence
+/// with the remaining changes. Every command runs in every repository when there are
+/// no changes at all, such as for `--initial`.
+fn plan<'a>(config: &'a Config, repos: &'a [Repo], trigger: Trigger) -> Vec<Job<'a>> {
+    let everything = trigger.paths.is_empty();
+    let mut within = vec![Vec::new(); repos.len()];
+    for path in trigger.paths.iter().cloned() {
+        let idx = innermost(repos.iter().map(|repo| repo.root.as_path()), &path);
+        within[idx.unwrap_or(0)].push(path);
+    }
+    let steps: Vec<Step> = config
+        .exec
+        .iter()
+        .map(|command| (std::slice::from_ref(command), true))
+        .chain(Some((config.command.as_slice(), config.shell)))
+        .filter(|(command, _)| !command.is_empty())
+        .collect();
+
+    let mut groups: Vec<Group> = Vec::new();
+    for (repo, paths) in repos.iter().zip(within) {
+        if !everything && paths.is_empty() {
+            continue;
+        }
+        let (matched, unmatched) = repo.rules.partition(&paths);
+        groups.extend(
+            repo.rules
+                .iter()
+                .zip(matched)
+                .filter(|(_, paths)| everything || !paths.is_empty())
+                .map(|(rule, paths)| {
+                    let steps = vec![(std::slice::from_ref(&rule.command), true)];
+                    (repo.root.as_path(), steps, paths)
+                }),
+        );
+        if !steps.is_empty() && (everything || !unmatched.is_empty()) {
+            groups.push((repo.root.as_path(), steps.clone(), unmatched));
+        }
+    }
+
+    let chunk = match config.per_file {
+        true => Some(1),
+        false => config.max_args,
+    };
+    if let Some(chunk) = chunk.filter(|_| !everything) {
+        groups = groups
+            .into_iter()
+            .flat_map(|(root, steps, paths)| {
+                paths
+                    .chunks(chunk)
+                    .map(|paths| (root, steps.clone(), paths.to_vec()))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+    }
+    groups
+        .iter()
+        .filter_map(|(root, steps, paths)| {
+            let trigger = Trigger {
+                paths: paths.clone(),
+                event: trigger.event,
+                kinds: trigger.kinds.clone(),
+                renames: trigger.renames.clone(),
+            };
+            Job::sequence(root, steps, &trigger)
+        })
+        .collect()
+}
+
+/// Spawn the command for `job`, describing the changes to it through the
+/// `GIT_WATCH_ROOT`, `GIT_WATCH_FILES` (newline-separated), `GIT_WATCH_EVENT`,
+/// `GIT_WATCH_CHANGE` (as `{event}`), `GIT_WATCH_CHANGES` (each file preceded by
+/// how it changed), and `GIT_WATCH_RENAMES` (each rename as its source and
+/// destination, tab-separated) environment variables.
+fn spawn_command(config: &Config, job: &Job) -> Result<Child> {
+    let trigger = &job.trigger;
+    let args = substitute(job.command, trigger, job.shell, config.append_paths);
+    if config.summary && !trigger.paths.is_empty() {
+        eprint!("{}", git_summary(job.root, &trigger.paths));
+    }
+
+    let mut user_command = build_command(job.shell, &args)?;
+    configure_env(config, &mut user_command);
+    priority::apply(&mut user_command, config.nice, config.ionice);
+    match config.workdir {
+        Some(ref workdir) => {
+            user_command.current_dir(workdir.resolve(job));
+        }
+        None if !config.repo.is_empty() => {
+            user_command.current_dir(job.root);
+        }
+        None => {}
+    }
+    user_command
+        .env("GIT_WATCH_ROOT", job.root)
+        .env("GIT_WATCH_FILES", join_lines(&trigger.paths))
+        .env("GIT_WATCH_EVENT", trigger.event.unwrap_or(""))
+        .env("GIT_WATCH_CHANGE", trigger.change())
+        .env("GIT_WATCH_CHANGES", trigger.describe())
+        .env("GIT_WATCH_RENAMES", trigger.describe_renames());
+    let terminal = match config.pty {
+        true => Some(pty::attach(&mut user_command)?),
+        false => None,
+    };
+    if (tui::enabled() || config.prefix) && terminal.is_none() {
+        user_command.stdout(Stdio::piped()).stderr(Stdio::piped());
+    }
+
+    match user_command.spawn() {
+        Ok(mut child) => {
+            use output::Stream;
+            shutdown::track(&child);
+            if let Some(terminal) = terminal {
+                output::relay(&child, terminal, Stream::Stdout, config.prefix);
+            }
+            if let Some(stdout) = child.stdout.take() {
+                output::relay(&child, stdout, Stream::Stdout, config.prefix);
+            }
+            if let Some(stderr) = child.stderr.take() {
+                output::relay(&child, stderr, Stream::Stderr, config.prefix);
+            }
+            Ok(child)
+        }
+        Err(_) => {
+            // Error if the command could not be found
+            anyhow::bail!("command not found: {}", &job.command[0])
+        }
+    }
+}
+
+fn log_status(command: &[String], status: ExitStatus) {
+    if status.success() {
+        log::debug!("Command success: {:?}", command);
+    } else {
+        log::debug!("Command failure: {:?}", command);
+    }
+}
+
+fn run_command(config: &Config, job: &Job, banner: banner::Banner) -> Result<ExitStatus> {
+    let start = Instant::now();
+    banner.start(job);
+    logfile::start(job);
+    tui::start(job);
+    let mut child = spawn_command(config, job)?;
+    let status = systemd::wait(&mut child)?;
+    output::drain(&child);
+    shutdown::forget(&child);
+    control::record(status);
+    banner.finish(job.command, Some(status), start.elapsed());
+    logfile::finish(job.command, Some(status), start.elapsed());
+    tui::finish(job.command, Some(status), start.elapsed());
+    log_status(job.command, status);
+    Ok(status)
+}
+
+/// Run `job`, retrying on failure as `on_error` allows, then the jobs it chains
+/// to for as long as each succeeds.
+fn run_job(
+    config: &Config,
+    job: &Job,
+    on_error: OnError,
+    banner: banner::Banner,
+) -> Result<ExitStatus> {
+    let mut status = run_command(config, job, banner)?;
+    if let OnError::Retry { attempts, backoff } = on_error {
+        for attempt in 1..=attempts {
+            if status.success() || shutdown::requested() {
+                break;
+            }
+            log::info!("Command failed; retrying ({}/{})", attempt, attempts);
+            std::thread::sleep(Duration::from_secs_f32(backoff));
+            status = run_command(config, job, banner)?;
+        }
+    }
+    match &job.then {
+        Some(then) if status.success() && !shutdown::requested() => {
+            run_job(config, then, on_error, banner)
+        }
+        _ => Ok(status),
+    }
+}
+
+/// Run `jobs`, up to `parallel` at a time, returning the status of the first
+/// (in order) to fail, else that of the last.
+fn run_jobs(
+    config: &Config,
+    jobs: &[Job],
+    on_error: OnError,
+    banner: banner::Banner,
+    parallel: usize,
+) -> Result<Option<ExitStatus>> {
+    let next = AtomicUsize::new(0);
+    let work = || {
+        let mut outcomes = Vec::new();
+        loop {
+            if shutdown::requested() {
+                return outcomes;
+            }
+            let idx = next.fetch_add(1, Ordering::Relaxed);
+            let Some(job) = jobs.get(idx) else {
+                return outcomes;
+            };
+            outcomes.push((idx, run_job(config, job, on_error, banner)));
+        }
+    };
+
+    let workers = parallel.clamp(1, jobs.len().max(1));
+    let mut outcomes = match workers {
+        1 => work(),
+        _ => std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..workers).map(|_| scope.spawn(work)).collect();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().expect("worker panicked"))
+                .collect()
+        }),
+    };
+    outcomes.sort_by_key(|(idx, _)| *idx);
+
+    let mut status: Option<ExitStatus> = None;
+    for (_, outcome) in outcomes {
+        let outcome = outcome?;
+        if status.is_none_or(|s| s.success()) {
+            status = Some(outcome);
+        }
+    }
+    Ok(status)
+}
+
+/// Stop `child` if still running, first asking politely (SIGTERM), then
+/// forcefully (SIGKILL) once `grace` has elapsed.
+///
+/// Returns the exit status only if the command had already exited on its own.
+fn terminate(command: &[String], child: &mut Child, grace: Duration) -> Result<Option<ExitStatus>> {
+    if let Some(status) = child.try_wait()? {
+        log_status(command, status);
+        return Ok(Some(status));
+    }
+
+    log::debug!("Terminating command: {:?}", command);
+
+    #[cfg(unix)]
+    {
+        // SAFETY: signals a process that has not yet been reaped, so its pid is not reused
+        unsafe { libc::kill(child.id() as libc::pid_t, libc::SIGTERM) };
+
+        let deadline = Instant::now() + grace;
+        while Instant::now() < deadline {
+            if child.try_wait()?.is_some() {
+                return Ok(None);
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        log::debug!("Command did not exit within grace period; killing");
+    }
+    #[cfg(not(unix))]
+    let _ = grace;
+
+    child.kill()?;
+    child.wait()?;
+    Ok(None)
+}
+
+/// Run the `--on-success` or `--on-failure` hook, whichever befits `status`, for
+/// the changes that led to the run.
+fn run_hook(config: &Config, root: &Path, trigger: &Trigger, status: ExitStatus) {
+    let hook = match status.success() {
+        true => &config.on_success,
+        false => &config.on_failure,
+    };
+    let Some(hook) = hook else {
+        return;
+    };
+    let args = substitute(std::slice::from_ref(hook), trigger, true, false);
+    let mut hooked = match build_command(true, &args) {
+        Ok(hooked) => hooked,
+        Err(e) => {
+            log::warn!("unable to run hook {:?}: {}", hook, e);
+            return;
+        }
+    };
+    configure_env(config, &mut hooked);
+    let hooked = hooked
+        .env("GIT_WATCH_ROOT", root)
+        .env("GIT_WATCH_FILES", join_lines(&trigger.paths))
+        .env("GIT_WATCH_EVENT", trigger.event.unwrap_or(""))
+        .env("GIT_WATCH_CHANGE", trigger.change())
+        .env("GIT_WATCH_CHANGES", trigger.describe())
+        .env("GIT_WATCH_RENAMES", trigger.describe_renames())
+        .env("GIT_WATCH_STATUS", exit_code(status).to_string())
+        .status();
+    match hooked {
+        Ok(hooked) if hooked.success() => log::debug!("Hook success: {:?}", hook),
+        Ok(hooked) => log::warn!("hook {:?} failed ({})", hook, hooked),
+        Err(e) => log::warn!("unable to run hook {:?}: {}", hook, e),
+    }
+}
+
+/// Process exit code mirroring `status`, following the shell convention of
+/// 128 plus the signal number for commands killed by a signal.
+fn exit_code(status: ExitStatus) -> i32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+    status.code().unwrap_or(1)
+}
+
+/// Seconds between polls when the platform cannot watch everything.
+const FALLBACK_POLL: f32 = 2.0;
+
+/// Watcher delivering events to `handler`, polling every `poll` seconds if given.
+fn new_watcher<F: notify::EventHandler>(poll: Option<f32>, handler: F) -> Result<Box<dyn Watcher>> {
+    Ok(match poll {
+        Some(interval) => {
+            let poll_config =
+                notify::Config::default().with_poll_interval(Duration::from_secs_f32(interval));
+            Box::new(notify::PollWatcher::new(handler, poll_config)?)
+        }
+        None => Box::new(notify::recommended_watcher(handler)?),
+    })
+}
+
+/// The kernel setting limiting inotify, if `e` is due to exceeding it.
+fn inotify_limit(e: &notify::Error) -> Option<&'static str> {
+    match e.kind {
+        notify::ErrorKind::MaxFilesWatch => Some("fs.inotify.max_user_watches"),
+        #[cfg(target_os = "linux")]
+        notify::ErrorKind::Io(ref io) if io.raw_os_error() == Some(libc::EMFILE) => {
+            Some("fs.inotify.max_user_instances")
+        }
+        _ => None,
+    }
+}
+
+fn main() -> Result<()> {
+    let (mut config, matches) = Config::parse_args(std::env::args_os());
+    init_logger(&config);
+    systemd::init();
+
+    log::debug!("{:#?}", config);
+
+    if let Some(ref path) = config.log_file {
+        logfile::init(path, config.log_size)
+            .map_err(|e| anyhow::anyhow!("unable to open log file {:?}: {}", path, e))?;
+    }
+
+    anyhow::ensure!(
+        !config.command.is_empty()
+            || !config.exec.is_empty()
+            || !config.rule.is_empty()
+            || config.emit.is_some()
+            || config.stop
+            || config.send.is_some()
+            || config.action.is_some(),
+        "no command argument provided"
+    );
+    anyhow::ensure!(
+        config.ionice.is_none() || priority::IONICE_SUPPORTED,
+        "--ionice is only supported on Linux"
+    );
+    let (sender, events) = mpsc::channel();
+
+    let git = !config.no_git && config.path.is_none();
+    anyhow::ensure!(
+        config.repo.len() < 2 || std::env::var_os("GIT_DIR").is_none(),
+        "cannot watch several repositories while GIT_DIR names one"
+    );
+    let mut roots = match (&config.path, &config.root) {
+        (Some(path), _) => vec![path.canonicalize()?],
+        _ if !git => vec![std::env::current_dir()?],
+        (None, Some(root)) => {
+            let root = root
+                .canonicalize()
+                .map_err(|e| anyhow::anyhow!("unable to watch {:?}: {}", root, e))?;
+            // let git, for ignore rules, find the working tree as well
+            if std::env::var_os("GIT_DIR").is_some() && std::env::var_os("GIT_WORK_TREE").is_none()
+            {
+                std::env::set_var("GIT_WORK_TREE", &root);
+            }
+            vec![root]
+        }
+        _ if config.repo.is_empty() => vec![git_root(Path::new("."))?],
+        _ => config
+            .repo
+            .iter()
+            .map(|dir| git_root(dir).map_err(|e| anyhow::anyhow!("{:?}: {}", dir, e)))
+            .collect::<Result<_>>()?,
+    };
+    // each repository once, however named, and in the order given
+    let mut seen = HashSet::new();
+    roots.retain(|root| seen.insert(root.canonicalize().unwrap_or_else(|_| root.clone())));
+    // the first repository names the pid file and socket, and holds tasks
+    let root = roots[0].as_path();
+
+    let pid_file = config
+        .pid_file
+        .clone()
+        .unwrap_or_else(|| runtime_path(root, "pid"));
+    if config.stop {
+        return daemon::stop(&pid_file);
+    }
+    let socket = config
+        .socket
+        .as_ref()
+        .map(|path| path.clone().unwrap_or_else(|| runtime_path(root, "sock")));
+    if let Some(ref command) = config.send {
+        let socket = socket.unwrap_or_else(|| runtime_path(root, "sock"));
+        println!("{}", control::send(&socket, command)?);
+        return Ok(());
+    }
+    if let Some(Action::InstallHooks { force }) = config.action {
+        let socket = config.socket.clone().flatten();
+        return hooks::install(root, socket.as_deref(), force);
+    }
+
+    log::info!("Running with root: {:?}", root);
+    for other in &roots[1..] {
+        log::info!("Also watching: {:?}", other);
+    }
+
+    if let [name] = config.command.as_slice() {
+        if let Some(task) = tasks::find(root, name)? {
+            log::info!("Running task {:?}", name);
+            tasks::apply(&mut config, &task, &matches)?;
+            log::debug!("{:#?}", config);
+        }
+    }
+    anyhow::ensure!(config.max_args != Some(0), "--max-args must be at least 1");
+    anyhow::ensure!(config.max_runs != Some(0), "--max-runs must be at least 1");
+
+    if config.daemon {
+        let log = config
+            .daemon_log
+            .clone()
+            .unwrap_or_else(|| pid_file.with_extension("log"));
+        daemon::start(&pid_file, &log)?;
+    }
+    let _lock = match config.emit.is_some() || config.dry_run {
+        true => None,
+        false => Some(lock::acquire(&lock_path(root, &config), config.takeover)?),
+    };
+
+    let cache = Caches(
+        roots
+            .iter()
+            .map(|root| Cache::new(config.clone(), root, git))
+            .collect::<Result<_>>()?,
+    );
+    pause::install_signals();
+    shutdown::install(sender.clone(), Duration::from_secs_f32(config.grace));
+    if config.exit_on_stdin_close {
+        shutdown::on_stdin_close(sender.clone());
+    }
+    if let Some(ref socket) = socket {
+        control::serve(socket, sender.clone())?;
+    }
+    let repos: Vec<Repo> = roots
+        .iter()
+        .map(|root| {
+            let rules = rules::Rules::new(root, &config.rule)?;
+            Ok(Repo {
+                root: root.clone(),
+                rules,
+            })
+        })
+        .collect::<Result<_>>()?;
+
+    // linked worktrees and submodules keep their git directory elsewhere
+    let git_dirs: Vec<(PathBuf, PathBuf)> = match git {
+        true => roots
+            .iter()
+            .map(|root| {
+                let git_dir = gitignore::git_dir(root);
+                let common_dir = gitignore::common_dir(&git_dir);
+                (git_dir, common_dir)
+            })
+            .collect(),
+        false => Vec::new(),
+    };
+    let (refs, index, dry_run) = (config.refs, config.on_index, config.dry_run);
+    let handler = |polling: bool| {
+        use notify::event::{EventKind, ModifyKind, RenameMode};
+
+        let sender = sender.clone();
+        let git_dirs = git_dirs.clone();
+        let mut events = config.events.clone();
+        if polling && events.contains(&EventFilter::CloseWrite) {
+            // polling only sees the resulting modification
+            events.push(EventFilter::Modify);
+        }
+        move |result: notify::Result<notify::Event>| {
+            let event = match result {
+                Ok(event) => event,
+                Err(e) => {
+                    match inotify_limit(&e) {
+                        Some(limit) => log::warn!("{} (raise {} to watch everything)", e, limit),
+                        None => log::warn!("watch error: {}", e),
+                    }
+                    return;
+                }
+            };
+            let monitored = events.iter().any(|filter| filter.matches(&event.kind));
+            let in_git_dir = |path: &Path| {
+                git_dirs.iter().find(|(git_dir, common_dir)| {
+                    path.starts_with(git_dir) || path.starts_with(common_dir)
+                })
+            };
+            // needed to recognize atomic saves even if renames are not monitored
+            if let (EventKind::Modify(ModifyKind::Name(RenameMode::Both)), [from, to]) =
+                (event.kind, event.paths.as_slice())
+            {
+                if in_git_dir(from).is_none() && in_git_dir(to).is_none() {
+                    let _ = sender.send(Event::Rename {
+                        from: from.clone(),
+                        to: to.clone(),
+                        observed: Instant::now(),
+                        monitored,
+                    });
+                }
+            }
+            for path in event.paths.iter() {
+                match in_git_dir(path) {
+                    Some((git_dir, common_dir)) => {
+                        // git replaces files by renames, so any non-access event counts
+                        let replaced = !matches!(event.kind, EventKind::Access(_));
+                        let git_event = git_relative(path, git_dir, common_dir)
+                            .and_then(|relative| git_event(relative, refs, index));
+                        if let Some(git_event) = git_event.filter(|_| replaced) {
+                            let _ = sender.send(Event::change(path, git_event));
+                        }
+                        // otherwise, a polling watcher cannot skip the git directory
+                    }
+                    None if monitored => {
+                        let _ = sender.send(Event::change(path, event_name(&event.kind)));
+                    }
+                    // opening and reading files is too common to report
+                    None if dry_run && !matches!(event.kind, EventKind::Access(_)) => report(
+                        path,
+                        event_name(&event.kind),
+                        Some("kind not among --events"),
+                    ),
+                    None => {}
+                }
+            }
+        }
+    };
+
+    let watch = |watcher: &mut dyn Watcher, polling: bool| -> Result<()> {
+        // Add a path to be watched. All files and directories at that path and
+        // below will be monitored for changes.
+        if config.paths.is_empty() {
+            for root in roots.iter() {
+                watcher.watch(root, RecursiveMode::Recursive)?;
+            }
+
+            // skip top-level git directories, unless kept elsewhere as for a linked worktree
+            for ((git_dir, _), root) in git_dirs.iter().zip(roots.iter()) {
+                if !polling && git_dir.starts_with(root) && watcher.unwatch(git_dir).is_err() {
+                    log::warn!("top level \".git\" directory not found and not ignored");
+                }
+            }
+        }
+        for (git_dir, common_dir) in git_dirs.iter() {
+            if (config.refs || config.on_index) && !polling {
+                // HEAD and the index live directly within the git directory
+                watcher.watch(git_dir, RecursiveMode::NonRecursive)?;
+                if config.refs {
+                    // packed-refs and branches are shared among worktrees
+                    if common_dir != git_dir {
+                        watcher.watch(common_dir, RecursiveMode::NonRecursive)?;
+                    }
+                    watcher.watch(
+                        &common_dir.join("refs").join("heads"),
+                        RecursiveMode::Recursive,
+                    )?;
+                }
+            }
+        }
+        for path in config.paths.iter() {
+            let subtree = Path::new(path)
+                .canonicalize()
+                .map_err(|e| anyhow::anyhow!("unable to watch {:?}: {}", path, e))?;
+            anyhow::ensure!(
+                roots.iter().any(|root| subtree.starts_with(root)),
+                "{:?} is outside of root {:?}",
+                path,
+                root
+            );
+            log::debug!("Watching {:?}", subtree);
+            watcher.watch(&subtree, RecursiveMode::Recursive)?;
+        }
+        Ok(())
+    };
+
+    // Automatically select the best implementation for your platform, unless
+    // polling, falling back to polling should the platform's limits be exceeded.
+    let mut poll = config.poll;
+    let _watcher = loop {
+        let polling = poll.is_some();
+        let watched = new_watcher(poll, handler(polling)).and_then(|mut watcher| {
+            watch(watcher.as_mut(), polling)?;
+            Ok(watcher)
+        });
+        match watched {
+            Ok(watcher) => break watcher,
+            Err(e) if !polling => {
+                let Some(limit) = e.downcast_ref().and_then(inotify_limit) else {
+                    return Err(e);
+                };
+                log::warn!(
+                    "inotify limit reached ({}); raise it with `sudo sysctl {}=N`, polling every {}s meanwhile",
+                    e,
+                    limit,
+                    FALLBACK_POLL
+                );
+                poll = Some(FALLBACK_POLL);
+            }
+            Err(e) => return Err(e),
+        }
+    };
+    systemd::ready();
+
+    let grace = Duration::from_secs_f32(config.grace);
+    let mut running: Vec<(Child, &[String], Instant)> = Vec::new();
+    let parallel = config.jobs.unwrap_or_else(|| match config.per_file {
+        true => std::thread::available_parallelism().map_or(1, usize::from),
+        false => 1,
+    });
+    let banner =
+        banner::Banner::new(!config.no_banner && !config.tui && (!config.quiet || config.verbose))
+            .alert(config.bell, config.set_title);
+    let tui = match config.tui {
+        true => Some(tui::Tui::start(root, config.toggle_file.clone())?),
+        false => None,
+    };
+    let mut last_run: Option<Instant> = None;
+    let mut last_finish: Option<Instant> = None;
+    let mut failures = 0;
+    let mut runs = 0;
+    let mut batches = match config.queue {
+        true => Batches::queued(&config, events, cache),
+        false => Batches::Direct {
+            events,
+            cache,
+            cursor: Cursor::default(),
+        },
+    };
+    let mut initial = config.initial;
+    let mut status: Option<ExitStatus> = None;
+    let on_error = match config.fail_fast {
+        true => OnError::Stop,
+        false => config.on_error,
+    };
+
+    loop {
+        // coalesce changes until the throttle interval since the last run, and
+        // the cooldown and backoff since it finished, elapse
+        let backoff = config
+            .backoff
+            .and_then(|max| backoff_interval(max, failures));
+        let not_before = [
+            (config.throttle.map(Duration::from_secs_f32), last_run),
+            (config.cooldown.map(Duration::from_secs_f32), last_finish),
+            (backoff, last_finish),
+        ]
+        .into_iter()
+        .filter_map(|(interval, since)| Some(since? + interval?))
+        .max();
+        let trigger = match std::mem::take(&mut initial) {
+            true => Trigger::default(),
+            false => match batches.next(&config, not_before) {
+                Some(trigger) => trigger,
+                None => break,
+            },
+        };
+
+        let changes = trigger.clone();
+        let mut jobs = plan(&config, &repos, trigger);
+        if config.diff_gate {
+            jobs.retain(|job| {
+                let trigger = &job.trigger;
+                // git and manual events are not about the content of files
+                let gated = !trigger.paths.is_empty()
+                    && trigger
+                        .paths
+                        .iter()
+                        .all(|path| !is_git_event(trigger.kind(path)))
+                    && trigger.event != Some(MANUAL_EVENT);
+                let differs = !gated || differs_from_head(job.root, &trigger.paths);
+                if !differs {
+                    log::debug!("No differences from HEAD; not running {:?}", job.command);
+                }
+                differs
+            });
+        }
+        if jobs.is_empty() || config.dry_run {
+            for job in jobs.iter() {
+                let commands: Vec<String> =
+                    std::iter::successors(Some(job), |job| job.then.as_deref())
+                        .map(|job| job.command.join(" "))
+                        .collect();
+                println!(
+                    "would run {} for {} file(s)",
+                    commands.join(", then "),
+                    job.trigger.paths.len()
+                );
+            }
+            // when only emitting changes, a oneshot ends after the first batch
+            if config.oneshot && (config.emit.is_some() || config.dry_run) {
+                break;
+            }
+            continue;
+        }
+
+        last_run = Some(Instant::now());
+        runs += 1;
+        if config.restart {
+            for (mut child, command, start) in running.drain(..) {
+                let exited = terminate(command, &mut child, grace)?;
+                output::drain(&child);
+                shutdown::forget(&child);
+                if let Some(exited) = exited {
+                    control::record(exited);
+                }
+                banner.finish(command, exited, start.elapsed());
+                logfile::finish(command, exited, start.elapsed());
+                tui::finish(command, exited, start.elapsed());
+                if exited.is_some_and(|s| !s.success()) || status.is_none() {
+                    status = exited;
+                }
+            }
+            if on_error == OnError::Stop && status.is_some_and(|s| !s.success()) {
+                break;
+            }
+            status = None;
+            if let Some(mode) = config.clear {
+                mode.clear()?;
+            }
+            for job in jobs.iter() {
+                banner.start(job);
+                logfile::start(job);
+                tui::start(job);
+                running.push((spawn_command(&config, job)?, job.command, Instant::now()));
+            }
+            if let Some(url) = &config.webhook {
+                webhook::post(url, root, &changes.path_kinds(), changes.event, None);
+            }
+        } else {
+            if let Some(mode) = config.clear {
+                mode.clear()?;
+            }
+            status = run_jobs(&config, &jobs, on_error, banner, parallel)?;
+            if let Some(status) = status {
+                run_hook(&config, root, &changes, status);
+            }
+            last_finish = Some(Instant::now());
+            match status {
+                Some(status) if !status.success() => failures += 1,
+                Some(_) => failures = 0,
+                None => {}
+            }
+            let backoff = config
+                .backoff
+                .and_then(|max| backoff_interval(max, failures));
+            if let Some(delay) = backoff {
+                log::info!(
+                    "Failed {} time(s) in a row; backing off {:?} between runs",
+                    failures,
+                    delay
+                );
+            }
+            tui::backoff(backoff);
+            if let (Some(window), Batches::Direct { cursor, .. }) =
+                (config.ignore_self, &mut batches)
+            {
+                cursor.discard_until = Some(Instant::now() + Duration::from_secs_f32(window));
+            }
+            if let Some(url) = &config.webhook {
+                webhook::post(url, root, &changes.path_kinds(), changes.event, status);
+            }
+            if on_error == OnError::Stop && status.is_some_and(|s| !s.success()) {
+                break;
+            }
+        }
+
+        if config.oneshot || config.max_runs.is_some_and(|max| runs >= max) {
+            break;
+        }
+    }
+
+    for (mut child, command, start) in running {
+        let exited = systemd::wait(&mut child)?;
+        output::drain(&child);
+        shutdown::forget(&child);
+        control::record(exited);
+        banner.finish(command, Some(exited), start.elapsed());
+        logfile::finish(command, Some(exited), start.elapsed());
+        tui::finish(command, Some(exited), start.elapsed());
+        log_status(command, exited);
+        if status.is_none_or(|s| s.success()) {
+            status = Some(exited);
+        }
+    }
+
+    systemd::notify("STOPPING=1");
+    if let Some(socket) = socket {
+        let _ = std::fs::remove_file(socket);
+    }
+    if config.daemon {
+        let _ = std::fs::remove_file(&pid_file);
+    }
+    drop(tui);
+    drop(batches); // saves ignore decisions
+    stats::report(config.stats_json.as_deref());
+
+    if let Some(signal) = shutdown::signal() {
+        log::info!("Stopped by {}", shutdown::name(signal));
+        std::process::exit(128 + signal);
+    }
+
+    match status {
+        Some(status) if !status.success() => std::process::exit(exit_code(status)),
+        _ => Ok(()),
+    }
+}
+
+/// Where settled batches of changes come from.
+enum Batches {
+    /// Settled on demand, coalescing all changes since the previous batch
+    Direct {
+        events: mpsc::Receiver<Event>,
+        cache: Caches,
+        cursor: Cursor,
+    },
+    /// Settled in the background, even while the command runs, and queued
+    Queued(mpsc::Receiver<Trigger>),
+}
+
+impl Batches {
+    fn queued(config: &Config, events: mpsc::Receiver<Event>, cache: Caches) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let config = config.clone();
+        std::thread::spawn(move || {
+            let mut cache = cache;
+            let mut cursor = Cursor::default();
+            // the watchdog hears from the main loop, as it waits for the queue
+            while let Some(trigger) =
+                next_trigger(&config, &events, &mut cache, &mut cursor, None, None)
+            {
+                log::debug!("Queued {} changes", trigger.paths.len());
+                if sender.send(trigger).is_err() {
+                    break;
+                }
+            }
+        });
+        Self::Queued(receiver)
+    }
+
+    /// Block until the next batch of changes, not returning before `not_before`,
+    /// or `None` once watching should stop.
+    fn next(&mut self, config: &Config, not_before: Option<Instant>) -> Option<Trigger> {
+        match self {
+            Self::Direct {
+                events,
+                cache,
+                cursor,
+            } => next_trigger(config, events, cache, cursor, not_before, systemd::tick()),
+            Self::Queued(receiver) => {
+                let trigger = loop {
+                    systemd::keep_alive();
+                    match systemd::tick() {
+                        None => break receiver.recv().ok()?,
+                        Some(tick) => match receiver.recv_timeout(tick) {
+                            Ok(trigger) => break trigger,
+                            Err(mpsc::RecvTimeoutError::Timeout) => continue,
+                            Err(mpsc::RecvTimeoutError::Disconnected) => return None,
+                        },
+                    }
+                };
+                if let Some(wait) =
+                    not_before.and_then(|t| t.checked_duration_since(Instant::now()))
+                {
+                    std::thread::sleep(wait);
+                }
+                Some(trigger)
+            }
+        }
+    }
+}
+
+/// Progress through the changes, carried from one batch to the next.
+#[derive(Default)]
+struct Cursor {
+    /// Changes observed before then are discarded, as those of the command itself
+    discard_until: Option<Instant>,
+    /// Until when changes belong to the burst that led to the previous run,
+    /// so are ignored, when running on the leading edge
+    quiet_until: Option<Instant>,
+    /// Content of files seen, with `--hash-check`
+    hashes: content::Hashes,
+    /// Files written recently, which may be renamed as part of atomic saves
+    writes: renames::Writes,
+}
+
+/// Minimum interval between runs after `failures` consecutive failures, with
+/// `--backoff` up to `max` seconds.
+fn backoff_interval(max: f32, failures: u32) -> Option<Duration> {
+    let secs = 2f32.powi(failures.checked_sub(1)?.min(31) as i32);
+    Some(Duration::from_secs_f32(secs.min(max)))
+}
+
+/// The path by which to report a change to `path`, or `None` if reached through a
+/// symlink that is not followed.
+fn resolve(config: &Config, cache: &Caches, path: PathBuf, event: &'static str) -> Option<PathBuf> {
+    let follow = !config.no_follow_symlinks;
+    let resolved = symlinks::resolve(path.clone(), cache.roots(), follow);
+    if resolved.is_none() {
+        stats::filtered();
+        if config.dry_run {
+            report(&path, event, Some("reached through a symlink"));
+        }
+    }
+    resolved
+}
+
+/// Block until actionable changes have arrived and the filesystem has settled
+/// (and not before `not_before`), returning those changes, or `None` once
+/// watching should stop.
+///
+/// Changes are examined in batches, as they accumulate while waiting, and the
+/// watchdog is pinged every `tick` meanwhile, if given.
+fn next_trigger(
+    config: &Config,
+    events: &mpsc::Receiver<Event>,
+    cache: &mut Caches,
+    cursor: &mut Cursor,
+    not_before: Option<Instant>,
+    tick: Option<Duration>,
+) -> Option<Trigger> {
+    let settle = Duration::from_secs_f32(config.settle);
+    let delay = Duration::from_secs_f32(config.delay);
+
+    let mut trigger = Trigger::default();
+    let mut deadline: Option<Instant> = None;
+    let mut delayed: Option<Instant> = None;
+    let mut first: Option<Instant> = None;
+    loop {
+        // waking at least once a tick, so that the watchdog hears from this loop
+        if tick.is_some() {
+            systemd::keep_alive();
+        }
+        let settling = deadline.map(|deadline| {
+            let ready = delayed.unwrap_or_else(|| not_before.map_or(deadline, |t| t.max(deadline)));
+            ready.saturating_duration_since(Instant::now())
+        });
+        let timeout = match (settling, tick) {
+            (Some(settling), Some(tick)) => Some(settling.min(tick)),
+            (settling, tick) => settling.or(tick),
+        };
+        let received = match timeout {
+            None => events.recv().ok()?,
+            Some(timeout) => match events.recv_timeout(timeout) {
+                Ok(event) => event,
+                Err(mpsc::RecvTimeoutError::Timeout)
+                    if settling.is_none_or(|settling| settling > timeout) =>
+                {
+                    continue;
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) if delayed.is_none() && !delay.is_zero() => {
+                    log::debug!("Filesystem settled; delaying");
+                    delayed = Some(Instant::now() + delay);
+                    continue;
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    log::debug!("Filesystem settled");
+                    let Trigger { paths, renames, .. } = &mut trigger;
+                    renames.retain(|(from, to)| paths.contains(from) || paths.contains(to));
+                    if let Some(first) = first {
+                        stats::settled(first.elapsed());
+                    }
+                    return Some(trigger);
+                }
+                Err(mpsc::RecvTimeoutError::Disconnected) => return None,
+            },
+        };
+
+        // examine everything that has arrived meanwhile as one batch, keeping
+        // only the latest event for each path
+        let mut batch: Vec<(PathBuf, &'static str)> = Vec::new();
+        let mut discarded = Vec::new();
+        for event in std::iter::once(received).chain(events.try_iter()) {
+            match event {
+                Event::Quit => return None,
+                Event::Force => {
+                    log::debug!("Run requested");
+                    trigger.event = Some(MANUAL_EVENT);
+                    deadline = Some(Instant::now());
+                }
+                Event::Change { observed, .. } | Event::Rename { observed, .. }
+                    if cursor.discard_until.is_some_and(|until| observed < until) =>
+                {
+                    discarded.push(event);
+                }
+                Event::Change {
+                    path,
+                    event,
+                    observed,
+                } => {
+                    let path = match is_git_event(event) {
+                        true => path,
+                        false => match resolve(config, cache, path, event) {
+                            Some(path) => path,
+                            None => continue,
+                        },
+                    };
+                    if renames::is_write(event) {
+                        cursor.writes.record(&path, event, observed);
+                    }
+                    match batch.iter_mut().find(|(p, _)| *p == path) {
+                        Some(entry) => entry.1 = event,
+                        None => batch.push((path, event)),
+                    }
+                }
+                Event::Rename {
+                    from,
+                    to,
+                    observed,
+                    monitored,
+                } => match cursor.writes.atomic_save(&from, observed) {
+                    Some(event) => {
+                        // the original was rewritten, and the temporary file is gone
+                        log::debug!("Atomic save of {:?} through {:?}", to, from);
+                        batch.retain(|(path, _)| *path != from);
+                        trigger.forget(&from);
+                        let Some(to) = resolve(config, cache, to, event) else {
+                            continue;
+                        };
+                        match batch.iter_mut().find(|(p, _)| *p == to) {
+                            Some(entry) => entry.1 = event,
+                            None => batch.push((to, event)),
+                        }
+                    }
+                    None if monitored => trigger.renames.push((from, to)),
+                    None => {}
+                },
+            }
+        }
+        if !discarded.is_empty() {
+            log::debug!("Discarding {} changes made by run", discarded.len());
+            for event in discarded {
+                stats::filtered();
+                if let (true, Event::Change { path, event, .. }) = (config.dry_run, event) {
+                    report(&path, event, Some("made by the command"));
+                }
+            }
+        }
+        if batch.is_empty() {
+            continue;
+        }
+
+        if pause::is_paused(config.toggle_file.as_deref()) {
+            log::debug!("Paused; discarding {} changes", batch.len());
+            batch.iter().for_each(|_| stats::filtered());
+            if config.dry_run {
+                batch
+                    .iter()
+                    .for_each(|(path, event)| report(path, event, Some("paused")));
+            }
+            continue;
+        }
+
+        for (path, _) in batch
+            .iter()
+            .filter(|(path, _)| path.file_name() == Some(OsStr::new(".gitignore")))
+        {
+            log::debug!("Ignore rules changed: {:?}", path);
+            cache.invalidate(path);
+        }
+
+        let paths: Vec<&PathBuf> = batch
+            .iter()
+            .filter(|(_, event)| !is_git_event(event))
+            .map(|(path, _)| path)
+            .collect();
+        let mut ignored = cache.are_ignored(&paths).into_iter();
+        let now = Instant::now();
+        let leading = config.edge == Edge::Leading;
+        let quiet = leading && cursor.quiet_until.is_some_and(|until| now < until);
+        let mut actionable = false;
+        // longest of the settle times of actionable changes
+        let mut window = Duration::ZERO;
+        for (path, event) in batch.into_iter() {
+            let reason = if !is_git_event(event) && ignored.next().unwrap_or(false) {
+                Some(cache.why_ignored(&path))
+            } else if config.hash_check && !is_git_event(event) && !cursor.hashes.changed(&path) {
+                log::debug!("Content unchanged: {:?}", path);
+                Some("content unchanged")
+            } else {
+                None
+            };
+            let settle = cache.settle_for(&path).unwrap_or(settle);
+            if reason.is_none() {
+                actionable = true;
+                window = window.max(settle);
+            }
+            let reason = reason.or(quiet.then_some("burst already triggered a run"));
+            if config.dry_run {
+                report(&path, event, reason);
+            }
+            if reason.is_some() {
+                stats::filtered();
+            } else {
+                first.get_or_insert(now);
+                logfile::change(&path, event);
+                tui::change(&path, event);
+                if let Some(format) = config.emit {
+                    format.print(&path, event);
+                }
+                trigger.push(path, event);
+                deadline = Some(match (leading, deadline) {
+                    (true, _) => now,
+                    (false, Some(deadline)) => deadline.max(now + settle),
+                    (false, None) => now + settle,
+                });
+            }
+        }
+        if actionable && leading {
+            if quiet {
+                log::debug!("Ignoring changes until settled");
+            }
+            cursor.quiet_until = Some(now + window);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Verify that event filters select their respective kinds of events.
+    fn test_event_filter() {
+        use notify::event::{
+            AccessKind, AccessMode, CreateKind, DataChange, ModifyKind, RemoveKind, RenameMode,
+        };
+        use notify::EventKind;
+
+        let close_write = EventKind::Access(AccessKind::Close(AccessMode::Write));
+        let create = EventKind::Create(CreateKind::File);
+        let modify = EventKind::Modify(ModifyKind::Data(DataChange::Content));
+        let rename = EventKind::Modify(ModifyKind::Name(RenameMode::To));
+        let remove = EventKind::Remove(RemoveKind::File);
+
+        assert!(EventFilter::CloseWrite.matches(&close_write));
+        assert!(EventFilter::Create.matches(&create));
+        assert!(EventFilter::Modify.matches(&modify));
+        assert!(!EventFilter::Modify.matches(&rename));
+        assert!(EventFilter::Rename.matches(&rename));
+        assert!(EventFilter::Remove.matches(&remove));
+        assert!(!EventFilter::CloseWrite.matches(&modify));
+    }
+
+    #[test]
+    /// Verify that positional arguments are paths only when followed by `--` and a command.
+    fn test_parse_paths() {
+        let (config, _) =
+            Config::parse_args(["git-watch", "src/", "tests/", "--", "cargo", "test"]);
+        assert_eq!(vec!["src/", "tests/"], config.paths);
+        assert_eq!(vec!["cargo", "test"], config.command);
+
+        let (config, _) = Config::parse_args(["git-watch", "cargo", "test"]);
+        assert!(config.paths.is_empty());
+        assert_eq!(vec!["cargo", "test"], config.command);
+
+        let (config, _) = Config::parse_args(["git-watch", "--", "cargo", "test"]);
+        assert!(config.paths.is_empty());
+        assert_eq!(vec!["cargo", "test"], config.command);
+
+        let (config, _) = Config::parse_args(["git-watch", "--emit", "src/"]);
+        assert_eq!(vec!["src/"], config.paths);
+        assert!(config.command.is_empty());
+
+        let (config, _) = Config::parse_args(["git-watch", "--rule", "*.rs=cargo test", "src/"]);
+        assert_eq!(vec!["src/"], config.paths);
+        assert!(config.command.is_empty());
+
+        let (config, _) = Config::parse_args(["git-watch", "--exec", "make", "src/", "docs/"]);
+        assert_eq!(vec!["src/", "docs/"], config.paths);
+        assert!(config.command.is_empty());
+    }
+
+    #[test]
+    /// Verify that `--exec` commands chain ahead of the main command, per file if asked.
+    fn test_plan_exec() {
+        let (config, _) = Config::parse_args([
+            "git-watch",
+            "--exec",
+            "cargo fmt",
+            "--exec",
+            "cargo clippy",
+            "--per-file",
+            "--",
+            "cargo",
+            "test",
+        ]);
+        let repos = [Repo {
+            root: PathBuf::from("/"),
+            rules: rules::Rules::new(Path::new("/"), &[]).unwrap(),
+        }];
+        let trigger = Trigger {
+            paths: vec![PathBuf::from("/a.rs"), PathBuf::from("/b.rs")],
+            event: Some("modify"),
+            ..Default::default()
+        };
+        let jobs = plan(&config, &repos, trigger);
+        assert_eq!(2, jobs.len());
+        let commands: Vec<_> = std::iter::successors(Some(&jobs[1]), |job| job.then.as_deref())
+            .map(|job| (job.command.join(" "), job.shell, job.trigger.paths.clone()))
+            .collect();
+        assert_eq!(
+            vec![
+                ("cargo fmt".to_string(), true, vec![PathBuf::from("/b.rs")]),
+                (
+                    "cargo clippy".to_string(),
+                    true,
+                    vec![PathBuf::from("/b.rs")]
+                ),
+                (
+                    "cargo test".to_string(),
+                    false,
+                    vec![PathBuf::from("/b.rs")]
+                ),
+            ],
+            commands
+        );
+    }
+
+    #[test]
+    /// Verify that a command of only a placeholder for paths fails to spawn, rather
+    /// than panicking, when run without changes as for `--initial`.
+    fn test_initial_without_paths() {
+        let (config, _) = Config::parse_args(["git-watch", "--initial", "--", "{}"]);
+        let repos = [Repo {
+            root: PathBuf::from("/"),
+            rules: rules::Rules::new(Path::new("/"), &[]).unwrap(),
+        }];
+        let jobs = plan(&config, &repos, Trigger::default());
+        assert_eq!(1, jobs.len());
+        let e = spawn_command(&config, &jobs[0]).unwrap_err();
+        assert!(e.to_string().contains("expanded to nothing"));
+    }
+
+    #[test]
+    /// Verify that paths belong to the innermost repository containing them.
+    fn test_innermost() {
+        let roots = [Path::new("/ws/app"), Path::new("/ws/app/vendor/lib")];
+        let find = |path| innermost(roots.iter().copied(), Path::new(path));
+        assert_eq!(Some(0), find("/ws/app/src/main.rs"));
+        assert_eq!(Some(1), find("/ws/app/vendor/lib/lib.rs"));
+        assert_eq!(None, find("/ws/other/main.rs"));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    /// Verify that exit codes pass through, and signals map to 128 plus the signal number.
+    fn test_exit_code() {
+        use std::os::unix::process::ExitStatusExt;
+
+        assert_eq!(3, exit_code(ExitStatus::from_raw(3 << 8)));
+        assert_eq!(
+            128 + libc::SIGTERM,
+            exit_code(ExitStatus::from_raw(libc::SIGTERM))
+        );
+    }
+
+    #[test]
+    /// Verify parsing of failure policies, including retry defaults.
+    fn test_on_error() {
+        assert_eq!(Ok(OnError::Continue), "continue".parse());
+        assert_eq!(Ok(OnError::Stop), "stop".parse());
+        assert_eq!(
+            Ok(OnError::Retry {
+                attempts: 3,
+                backoff: 1.0
+            }),
+            "retry".parse()
+        );
+        assert_eq!(
+            Ok(OnError::Retry {
+                attempts: 5,
+                backoff: 0.5
+            }),
+            "retry:5:0.5".parse()
+        );
+        assert!("stop:1".parse::<OnError>().is_err());
+        assert!("retry:x".parse::<OnError>().is_err());
+        assert!("retry:1:-1".parse::<OnError>().is_err());
+        assert!("retry:1:2:3".parse::<OnError>().is_err());
+    }
+
+    #[test]
+    /// Verify that variables split at the first `=`, and require a name.
+    fn test_env_var() {
+        let var: EnvVar = "RUSTFLAGS=-C opt-level=2".parse().unwrap();
+        assert_eq!(("RUSTFLAGS", "-C opt-level=2"), (&*var.key, &*var.value));
+        assert_eq!(
+            Ok(String::new()),
+            "EMPTY=".parse().map(|var: EnvVar| var.value)
+        );
+        assert!("=1".parse::<EnvVar>().is_err());
+        assert!("KEY".parse::<EnvVar>().is_err());
+    }
+
+    #[test]
+    /// Verify that the working directory resolves against the job's changes and root.
+    fn test_workdir() {
+        let trigger = Trigger {
+            paths: vec![PathBuf::from("/repo/src/a.rs")],
+            ..Default::default()
+        };
+        let job = Job::sequence(Path::new("/repo"), &[(&[], false)], &trigger).unwrap();
+        let resolve = |s: &str| s.parse::<Workdir>().unwrap().resolve(&job);
+        assert_eq!(PathBuf::from("/repo"), resolve("root"));
+        assert_eq!(PathBuf::from("/repo/src"), resolve("event-dir"));
+        assert_eq!(PathBuf::from("/repo/web"), resolve("web"));
+        assert_eq!(PathBuf::from("/tmp"), resolve("/tmp"));
+        assert!("".parse::<Workdir>().is_err());
+    }
+
+    #[test]
+    /// Verify that task settings apply, except those given on the command line.
+    fn test_apply_task() {
+        let task: toml::Table = r#"
+            command = "cargo test | tail"
+            include = ["*.rs"]
+            settle = 1
+            restart = true
+            on-error = "retry:2"
+        "#
+        .parse()
+        .unwrap();
+
+        let (mut config, matches) = Config::parse_args(["git-watch", "-s", "0.5", "test"]);
+        tasks::apply(&mut config, &task, &matches).unwrap();
+        assert_eq!(vec!["cargo test | tail"], config.command);
+        assert!(config.shell);
+        assert_eq!(vec!["*.rs"], config.include);
+        assert_eq!(0.5, config.settle);
+        assert!(config.restart);
+        assert_eq!(
+            OnError::Retry {
+                attempts: 2,
+                backoff: 1.0
+            },
+            config.on_error
+        );
+
+        let task: toml::Table = "command = [\"make\"]\nsettel = 1".parse().unwrap();
+        assert!(tasks::apply(&mut config, &task, &matches).is_err());
+        for max_args in ["0", "0.5", "-1"] {
+            let task: toml::Table = format!("command = \"make\"\nmax_args = {}", max_args)
+                .parse()
+                .unwrap();
+            assert!(tasks::apply(&mut config, &task, &matches).is_err());
+        }
+    }
+
+    #[test]
+    /// Verify that settle times split at the last `=`, and apply by the last matching glob.
+    fn test_settle_for() {
+        let lock: filter::SettleFor = "*.lock=2".parse().unwrap();
+        assert_eq!(("*.lock", 2.0), (lock.glob.as_str(), lock.secs));
+        assert!("*.lock".parse::<filter::SettleFor>().is_err());
+        assert!("*.lock=-1".parse::<filter::SettleFor>().is_err());
+
+        let proto: filter::SettleFor = "gen/*=1.5".parse().unwrap();
+        let times = filter::SettleTimes::new(Path::new("/repo"), &[lock, proto]).unwrap();
+        assert_eq!(Some(2.0), times.get(Path::new("/repo/Cargo.lock")));
+        assert_eq!(Some(1.5), times.get(Path::new("/repo/gen/x.lock")));
+        assert_eq!(None, times.get(Path::new("/repo/src/main.rs")));
+    }
+
+    #[test]
+    /// Verify that HEAD and branches are refs, the index is only of interest when
+    /// requested, and lock files never are.
+    fn test_is_ref() {
+        assert!(is_ref(Path::new("HEAD")));
+        assert!(is_ref(Path::new("packed-refs")));
+        assert!(is_ref(Path::new("refs/heads/feature/x")));
+        assert!(!is_ref(Path::new("HEAD.lock")));
+        assert!(!is_ref(Path::new("refs/heads/main.lock")));
+        assert!(!is_ref(Path::new("refs/tags/v1")));
+        assert!(!is_ref(Path::new("index")));
+
+        assert_eq!(Some(INDEX_EVENT), git_event(Path::new("index"), true, true));
+        assert_eq!(None, git_event(Path::new("index"), true, false));
+        assert_eq!(None, git_event(Path::new("index.lock"), true, true));
+        assert_eq!(None, git_event(Path::new("HEAD"), false, true));
+    }
+
+    #[test]
+    /// Verify that a linked worktree's own files are relative to its git directory,
+    /// while only shared refs are relative to the common directory.
+    fn test_git_relative() {
+        let git_dir = Path::new("/repo/.git/worktrees/wt");
+        let common_dir = Path::new("/repo/.git");
+        let relative = |path| git_relative(Path::new(path), git_dir, common_dir);
+
+        assert_eq!(
+            Some(Path::new("HEAD")),
+            relative("/repo/.git/worktrees/wt/HEAD")
+        );
+        assert_eq!(
+            Some(Path::new("index")),
+            relative("/repo/.git/worktrees/wt/index")
+        );
+        assert_eq!(
+            Some(Path::new("refs/heads/main")),
+            relative("/repo/.git/refs/heads/main")
+        );
+        assert_eq!(
+            Some(Path::new("packed-refs")),
+            relative("/repo/.git/packed-refs")
+        );
+        assert_eq!(None, relative("/repo/.git/HEAD"));
+        assert_eq!(None, relative("/repo/.git/index"));
+    }
+
+    #[test]
+    /// Verify that each changed path is terminated by a newline.
+    fn test_join_lines() {
+        let paths = [PathBuf::from("/repo/a.rs"), PathBuf::from("/repo/b c.rs")];
+        assert_eq!(
+            OsString::from("/repo/a.rs\n/repo/b c.rs\n"),
+            join_lines(&paths)
+        );
+        assert_eq!(OsString::new(), join_lines(&[]));
+    }
+
+    #[test]
+    /// Verify that standalone placeholders expand to separate arguments, while
+    /// embedded placeholders are replaced by a space-separated list.
+    fn test_substitute() {
+        let args: Vec<String> = ["rustfmt", "{}", "--dirs={dir}", "{dir}", "x{files}"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let trigger = Trigger {
+            paths: vec![
+                PathBuf::from("/repo/src/a.rs"),
+                PathBuf::from("/repo/src/b.rs"),
+                PathBuf::from("/repo/c.rs"),
+            ],
+            ..Default::default()
+        };
+
+        let exp: Vec<OsString> = [
+            "rustfmt",
+            "/repo/src/a.rs",
+            "/repo/src/b.rs",
+            "/repo/c.rs",
+            "--dirs=/repo/src /repo",
+            "/repo/src",
+            "/repo",
+            "x/repo/src/a.rs /repo/src/b.rs /repo/c.rs",
+        ]
+        .iter()
+        .map(OsString::from)
+        .collect();
+
+        assert_eq!(exp, substitute(&args, &trigger, false, false));
+    }
+
+    #[test]
+    /// Verify that shell mode joins arguments into a single script with quoted paths.
+    fn test_substitute_shell() {
+        let args = vec!["cat {} | wc -l && ls {dir}".to_string()];
+        let trigger = Trigger {
+            paths: vec![PathBuf::from("/repo/it's.rs"), PathBuf::from("/repo/b.rs")],
+            ..Default::default()
+        };
+
+        let exp = vec![OsString::from(
+            "cat '/repo/it'\\''s.rs' '/repo/b.rs' | wc -l && ls '/repo'",
+        )];
+        assert_eq!(exp, substitute(&args, &trigger, true, false));
+    }
+
+    #[test]
+    /// Verify that the backoff doubles with each failure, up to its maximum.
+    fn test_backoff() {
+        assert_eq!(None, backoff_interval(60.0, 0));
+        assert_eq!(Some(Duration::from_secs(1)), backoff_interval(60.0, 1));
+        assert_eq!(Some(Duration::from_secs(8)), backoff_interval(60.0, 4));
+        assert_eq!(Some(Duration::from_secs(60)), backoff_interval(60.0, 7));
+        assert_eq!(Some(Duration::from_secs(60)), backoff_interval(60.0, 1000));
+    }
+
+    #[test]
+    /// Verify that --append-paths adds the changed files after the arguments.
+    fn test_substitute_append() {
+        let args = vec!["eslint".to_string(), "--fix".to_string()];
+        let trigger = Trigger {
+            paths: vec![PathBuf::from("/repo/a b.js"), PathBuf::from("/repo/c.js")],
+            ..Default::default()
+        };
+
+        let exp: Vec<OsString> = ["eslint", "--fix", "/repo/a b.js", "/repo/c.js"]
+            .iter()
+            .map(OsString::from)
+            .collect();
+        assert_eq!(exp, substitute(&args, &trigger, false, true));
+        let exp = vec![OsString::from("eslint --fix '/repo/a b.js' '/repo/c.js'")];
+        assert_eq!(exp, substitute(&args, &trigger, true, true));
+    }
+
+    #[test]
+    /// Verify that `{event}` tells how the files changed over a batch.
+    fn test_substitute_event() {
+        let args = vec!["make".to_string(), "{event}".to_string()];
+        let mut trigger = Trigger::default();
+        trigger.push(PathBuf::from("/repo/a.c"), "create");
+        trigger.push(PathBuf::from("/repo/a.c"), "close_write");
+        assert_eq!(
+            vec!["make", "created"],
+            substitute(&args, &trigger, false, false)
+        );
+
+        trigger.push(PathBuf::from("/repo/a.c"), "remove");
+        assert_eq!(
+            vec!["make", "removed"],
+            substitute(&args, &trigger, false, false)
+        );
+
+        trigger.push(PathBuf::from("/repo/b.c"), "close_write");
+        assert_eq!(
+            vec!["make changed"],
+            substitute(&args, &trigger, true, false)
+        );
+        assert_eq!(
+            "removed /repo/a.c\nmodified /repo/b.c\n",
+            trigger.describe()
+        );
+    }
 }
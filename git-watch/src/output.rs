@@ -0,0 +1,107 @@
+//! Relaying the output of commands that is captured rather than inherited,
+//! whether to show it in the dashboard, to prefix each line, or because it
+//! comes from a pseudo-terminal.
+
+use crate::tui;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::Child;
+use std::sync::mpsc;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+/// How long to wait for output still being relayed after a command exits,
+/// such as when a background process it started keeps its output open.
+const DRAIN: Duration = Duration::from_millis(500);
+
+/// Relays in progress, by process id, each signaling once drained.
+static RELAYS: Mutex<Vec<(u32, mpsc::Receiver<()>)>> = Mutex::new(Vec::new());
+
+/// Which of our streams output is relayed to.
+#[derive(Debug, Clone, Copy)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+impl Stream {
+    fn tag(&self) -> &'static str {
+        match self {
+            Self::Stdout => "out",
+            Self::Stderr => "err",
+        }
+    }
+
+    fn write(&self, bytes: &[u8]) {
+        let _ = match self {
+            Self::Stdout => {
+                let mut stdout = std::io::stdout().lock();
+                stdout.write_all(bytes).and_then(|_| stdout.flush())
+            }
+            Self::Stderr => std::io::stderr().lock().write_all(bytes),
+        };
+    }
+}
+
+/// Relay `source`, captured from `child`, to the dashboard if shown, else to
+/// `stream`, with each line prefixed by the time and stream if `prefix`.
+pub fn relay(child: &Child, source: impl Read + Send + 'static, stream: Stream, prefix: bool) {
+    if tui::enabled() {
+        tui::forward(source);
+        return;
+    }
+
+    let (drained, done) = mpsc::channel();
+    RELAYS.lock().unwrap().push((child.id(), done));
+    std::thread::spawn(move || {
+        match prefix {
+            true => {
+                let mut reader = BufReader::new(source);
+                let mut line = Vec::new();
+                loop {
+                    // even a failed read may have yielded the end of a line
+                    let read = reader.read_until(b'\n', &mut line);
+                    if !line.is_empty() {
+                        if !line.ends_with(b"\n") {
+                            line.push(b'\n');
+                        }
+                        let mut prefixed = format!("[{} {}] ", clock(), stream.tag()).into_bytes();
+                        prefixed.append(&mut line);
+                        stream.write(&prefixed);
+                    }
+                    if !matches!(read, Ok(n) if n > 0) {
+                        break;
+                    }
+                }
+            }
+            false => {
+                let mut source = source;
+                let mut buf = [0; 4096];
+                // a pseudo-terminal fails with EIO once no process has it open
+                while let Ok(n @ 1..) = source.read(&mut buf) {
+                    stream.write(&buf[..n]);
+                }
+            }
+        }
+        let _ = drained.send(());
+    });
+}
+
+/// Wait briefly for the output of `child`, which has exited, to be relayed.
+pub fn drain(child: &Child) {
+    let done: Vec<_> = {
+        let mut relays = RELAYS.lock().unwrap();
+        let (done, pending) = relays.drain(..).partition(|(id, _)| *id == child.id());
+        *relays = pending;
+        done
+    };
+    let deadline = Instant::now() + DRAIN;
+    for (_, done) in done {
+        let _ = done.recv_timeout(deadline.saturating_duration_since(Instant::now()));
+    }
+}
+
+/// `HH:MM:SS.mmm` in UTC.
+fn clock() -> String {
+    let stamp = humantime::format_rfc3339_millis(SystemTime::now()).to_string();
+    stamp[11..23].to_string()
+}
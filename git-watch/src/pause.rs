@@ -0,0 +1,34 @@
+//! Pausing and resuming triggering without stopping the watcher, such as for
+//! the duration of a rebase. Changes observed while paused are discarded.
+
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static PAUSED: AtomicBool = AtomicBool::new(false);
+
+/// Pause on SIGUSR1, and resume on SIGUSR2.
+#[cfg(unix)]
+pub fn install_signals() {
+    extern "C" fn handle(signal: libc::c_int) {
+        PAUSED.store(signal == libc::SIGUSR1, Ordering::SeqCst);
+    }
+
+    // SAFETY: the handler only stores to an atomic, which is async-signal-safe
+    unsafe {
+        libc::signal(libc::SIGUSR1, handle as *const () as libc::sighandler_t);
+        libc::signal(libc::SIGUSR2, handle as *const () as libc::sighandler_t);
+    }
+}
+
+#[cfg(not(unix))]
+pub fn install_signals() {}
+
+/// Pause or resume, as by signal.
+pub fn set(paused: bool) {
+    PAUSED.store(paused, Ordering::SeqCst);
+}
+
+/// Whether paused, by signal or by the existence of `toggle_file`.
+pub fn is_paused(toggle_file: Option<&Path>) -> bool {
+    PAUSED.load(Ordering::SeqCst) || toggle_file.is_some_and(Path::exists)
+}
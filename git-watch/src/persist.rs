@@ -0,0 +1,191 @@
+//! Ignore decisions kept on disk between sessions, so that a restarted watcher
+//! need not consult the ignore rules (or `git check-ignore`) about every path
+//! afresh.
+//!
+//! Each decision is stored with a fingerprint of the `.gitignore` files that
+//! could have affected it, those of its parent directories, and is disregarded
+//! once any of them has changed. All decisions are disregarded once
+//! `info/exclude` or the global excludes file has changed.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// How often decisions made since are written out, should the watcher not
+/// exit cleanly.
+const SAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Version of the file format, the first word of its header.
+const VERSION: &str = "v1";
+
+pub struct Decisions {
+    root: PathBuf,
+    file: PathBuf,
+    /// Fingerprint of the repository-wide ignore files
+    global: u64,
+    /// Fingerprint of the applicable `.gitignore` files, and whether ignored,
+    /// by path relative to the root
+    entries: HashMap<PathBuf, (u64, bool)>,
+    /// Modification time of the `.gitignore` file of each directory, if any
+    mtimes: HashMap<PathBuf, Option<SystemTime>>,
+    /// Decisions made since last saved, if any
+    unsaved_since: Option<Instant>,
+}
+
+impl Decisions {
+    /// Decisions for the repository at `root`, as saved by a previous session,
+    /// or none if they no longer apply.
+    pub fn load(root: &Path, submodules: bool) -> Self {
+        let git_dir = crate::gitignore::git_dir(root);
+        let file = git_dir.join("git-watch-ignores");
+        let mut hasher = DefaultHasher::new();
+        submodules.hash(&mut hasher);
+        let exclude = crate::gitignore::common_dir(&git_dir)
+            .join("info")
+            .join("exclude");
+        for path in [Some(exclude), global_excludes(root)].iter().flatten() {
+            modified(path).hash(&mut hasher);
+        }
+        let mut decisions = Self {
+            root: root.to_path_buf(),
+            file,
+            global: hasher.finish(),
+            entries: HashMap::new(),
+            mtimes: HashMap::new(),
+            unsaved_since: None,
+        };
+        if let Err(e) = decisions.read() {
+            log::debug!("Not using saved ignore decisions: {}", e);
+            decisions.entries.clear();
+        }
+        decisions
+    }
+
+    fn read(&mut self) -> anyhow::Result<()> {
+        let file = std::fs::File::open(&self.file)?;
+        let mut lines = BufReader::new(file).lines();
+        let header = lines.next().transpose()?.unwrap_or_default();
+        anyhow::ensure!(
+            header == format!("{} {:016x}", VERSION, self.global),
+            "ignore rules changed"
+        );
+        for line in lines {
+            let line = line?;
+            let mut fields = line.splitn(3, ' ');
+            let (Some(fingerprint), Some(ignored), Some(path)) =
+                (fields.next(), fields.next(), fields.next())
+            else {
+                anyhow::bail!("malformed line {:?}", line);
+            };
+            let fingerprint = u64::from_str_radix(fingerprint, 16)?;
+            self.entries
+                .insert(PathBuf::from(path), (fingerprint, ignored == "1"));
+        }
+        log::debug!(
+            "Loaded {} ignore decisions from {:?}",
+            self.entries.len(),
+            self.file
+        );
+        Ok(())
+    }
+
+    /// Whether `path` is ignored, if decided before under the same rules.
+    pub fn get(&mut self, path: &Path) -> Option<bool> {
+        let relative = path.strip_prefix(&self.root).ok()?;
+        let &(fingerprint, ignored) = self.entries.get(relative)?;
+        (fingerprint == self.fingerprint(path)).then_some(ignored)
+    }
+
+    pub fn insert(&mut self, path: &Path, ignored: bool) {
+        let Ok(relative) = path.strip_prefix(&self.root) else {
+            return;
+        };
+        let entry = (self.fingerprint(path), ignored);
+        self.entries.insert(relative.to_path_buf(), entry);
+        let since = *self.unsaved_since.get_or_insert_with(Instant::now);
+        if since.elapsed() >= SAVE_INTERVAL {
+            self.save();
+        }
+    }
+
+    /// Forget the modification times of `.gitignore` files, as after one changed.
+    pub fn invalidate(&mut self) {
+        self.mtimes.clear();
+    }
+
+    /// Fingerprint of the `.gitignore` files that may apply to `path`.
+    fn fingerprint(&mut self, path: &Path) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        for dir in path.ancestors().skip(1) {
+            if !dir.starts_with(&self.root) {
+                break;
+            }
+            let mtime = self
+                .mtimes
+                .entry(dir.to_path_buf())
+                .or_insert_with(|| modified(&dir.join(".gitignore")));
+            mtime.hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+
+    /// Write out the decisions about paths that still exist, replacing the file
+    /// atomically.
+    fn save(&mut self) {
+        self.unsaved_since = None;
+        let temporary = self.file.with_extension("tmp");
+        let written = (|| {
+            let mut file = std::io::BufWriter::new(std::fs::File::create(&temporary)?);
+            writeln!(file, "{} {:016x}", VERSION, self.global)?;
+            for (path, (fingerprint, ignored)) in self.entries.iter() {
+                let Some(path) = path.to_str().filter(|path| !path.contains('\n')) else {
+                    continue;
+                };
+                if self.root.join(path).symlink_metadata().is_err() {
+                    continue;
+                }
+                writeln!(file, "{:016x} {} {}", fingerprint, *ignored as u8, path)?;
+            }
+            file.flush()?;
+            std::fs::rename(&temporary, &self.file)
+        })();
+        match written {
+            Ok(()) => log::debug!("Saved ignore decisions to {:?}", self.file),
+            Err(e) => log::warn!("unable to save ignore decisions to {:?}: {}", self.file, e),
+        }
+    }
+}
+
+impl Drop for Decisions {
+    fn drop(&mut self) {
+        if self.unsaved_since.is_some() {
+            self.save();
+        }
+    }
+}
+
+/// When `path` was last modified, or `None` if it does not exist.
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// The user's global excludes file, as configured by `core.excludesFile` or
+/// else at its default location.
+fn global_excludes(root: &Path) -> Option<PathBuf> {
+    let output = std::process::Command::new("git")
+        .current_dir(root)
+        .args(["config", "--path", "--get", "core.excludesFile"])
+        .output()
+        .ok()?;
+    let configured = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if !configured.is_empty() {
+        return Some(PathBuf::from(configured));
+    }
+    let config = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some(config.join("git").join("ignore"))
+}
@@ -0,0 +1,69 @@
+//! CPU and I/O priority of commands, so that rebuilds in the background leave
+//! the editor and other foreground work responsive.
+
+use std::process::Command;
+
+/// I/O scheduling class, as for `ionice`.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum IoClass {
+    /// Only use the disk when no other process does
+    Idle,
+    /// Share the disk with other processes
+    BestEffort,
+    /// Use the disk before any other process
+    Realtime,
+}
+
+impl IoClass {
+    /// Value for `ioprio_set`: the class, and the middle priority within it.
+    #[cfg(target_os = "linux")]
+    fn ioprio(self) -> libc::c_int {
+        const CLASS_SHIFT: libc::c_int = 13;
+        match self {
+            Self::Realtime => (1 << CLASS_SHIFT) | 4,
+            Self::BestEffort => (2 << CLASS_SHIFT) | 4,
+            Self::Idle => 3 << CLASS_SHIFT,
+        }
+    }
+}
+
+/// Whether `--ionice` can take effect on this platform.
+pub const IONICE_SUPPORTED: bool = cfg!(target_os = "linux");
+
+/// Make `command` run at niceness `nice` and in the I/O scheduling class `io`,
+/// as given.
+#[cfg(unix)]
+pub fn apply(command: &mut Command, nice: Option<i32>, io: Option<IoClass>) {
+    use std::os::unix::process::CommandExt;
+
+    if nice.is_none() && io.is_none() {
+        return;
+    }
+    // SAFETY: setpriority and ioprio_set are async-signal-safe
+    unsafe {
+        command.pre_exec(move || {
+            if let Some(nice) = nice {
+                if libc::setpriority(libc::PRIO_PROCESS, 0, nice) == -1 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            #[cfg(target_os = "linux")]
+            if let Some(io) = io {
+                const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+                if libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, io.ioprio()) == -1 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            #[cfg(not(target_os = "linux"))]
+            let _ = io;
+            Ok(())
+        });
+    }
+}
+
+#[cfg(not(unix))]
+pub fn apply(_command: &mut Command, nice: Option<i32>, _io: Option<IoClass>) {
+    if nice.is_some() {
+        log::warn!("--nice is only supported on Unix");
+    }
+}
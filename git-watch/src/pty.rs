@@ -0,0 +1,69 @@
+//! Running commands in a pseudo-terminal, so that they keep the colored and
+//! progress output they show when run interactively, rather than downgrading
+//! to plain output upon detecting a pipe.
+
+use std::fs::File;
+use std::process::Command;
+
+#[cfg(unix)]
+fn cloexec(fd: std::os::unix::io::RawFd) -> std::io::Result<()> {
+    // SAFETY: only manipulates the descriptor flags of an owned descriptor
+    match unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) } {
+        -1 => Err(std::io::Error::last_os_error()),
+        _ => Ok(()),
+    }
+}
+
+/// Make `command` write to a new pseudo-terminal, sized like ours, as its
+/// controlling terminal, returning the controlling side.
+#[cfg(unix)]
+pub fn attach(command: &mut Command) -> std::io::Result<File> {
+    use std::os::unix::io::{AsRawFd, FromRawFd};
+    use std::os::unix::process::CommandExt;
+
+    let (mut master, mut slave) = (0, 0);
+    // SAFETY: TIOCGWINSZ only writes to the provided winsize
+    let mut ws: libc::winsize = unsafe { std::mem::zeroed() };
+    if unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut ws) } != 0 {
+        ws.ws_col = 80;
+        ws.ws_row = 24;
+    }
+    // SAFETY: openpty only writes the descriptors it opens
+    let opened = unsafe {
+        libc::openpty(
+            &mut master,
+            &mut slave,
+            std::ptr::null_mut(),
+            std::ptr::null(),
+            &ws,
+        )
+    };
+    if opened != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    // SAFETY: both descriptors were just opened and are owned by nothing else
+    let (master, slave) = unsafe { (File::from_raw_fd(master), File::from_raw_fd(slave)) };
+    cloexec(master.as_raw_fd())?;
+    cloexec(slave.as_raw_fd())?;
+
+    command.stdout(slave.try_clone()?).stderr(slave);
+    // SAFETY: setsid and ioctl are async-signal-safe
+    unsafe {
+        command.pre_exec(|| {
+            if libc::setsid() == -1 {
+                return Err(std::io::Error::last_os_error());
+            }
+            libc::ioctl(libc::STDOUT_FILENO, libc::TIOCSCTTY as _, 0);
+            Ok(())
+        });
+    }
+    Ok(master)
+}
+
+#[cfg(not(unix))]
+pub fn attach(_command: &mut Command) -> std::io::Result<File> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "pseudo-terminals are only supported on Unix",
+    ))
+}
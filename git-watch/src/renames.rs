@@ -0,0 +1,59 @@
+//! Recognition of atomic saves, where an editor writes a temporary file and then
+//! renames it over the original, so that these count as a modification of the
+//! original rather than as changes to two files.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// How soon after a file was written renaming it counts as an atomic save.
+const WINDOW: Duration = Duration::from_secs(2);
+
+/// Files written recently, with the event that wrote each and when.
+#[derive(Default)]
+pub struct Writes(HashMap<PathBuf, (&'static str, Instant)>);
+
+impl Writes {
+    /// Note that `event`, observed at `observed`, wrote `path`.
+    pub fn record(&mut self, path: &Path, event: &'static str, observed: Instant) {
+        self.0
+            .retain(|_, (_, written)| observed.saturating_duration_since(*written) < WINDOW);
+        self.0.insert(path.to_path_buf(), (event, observed));
+    }
+
+    /// The event that wrote `from`, if renaming it at `observed` completes an
+    /// atomic save.
+    pub fn atomic_save(&mut self, from: &Path, observed: Instant) -> Option<&'static str> {
+        let (event, written) = self.0.remove(from)?;
+        (observed.saturating_duration_since(written) < WINDOW).then_some(event)
+    }
+}
+
+/// Whether `event` writes the contents of a file.
+pub fn is_write(event: &str) -> bool {
+    matches!(event, "close_write" | "modify" | "create")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Verify that only renaming a file soon after writing it counts as an atomic save.
+    fn test_atomic_save() {
+        let mut writes = Writes::default();
+        let now = Instant::now();
+        writes.record(Path::new("/repo/.a.swp"), "close_write", now);
+        writes.record(Path::new("/repo/b.tmp"), "close_write", now);
+        assert_eq!(None, writes.atomic_save(Path::new("/repo/c"), now));
+        assert_eq!(
+            Some("close_write"),
+            writes.atomic_save(Path::new("/repo/.a.swp"), now + Duration::from_millis(10))
+        );
+        assert_eq!(None, writes.atomic_save(Path::new("/repo/.a.swp"), now));
+        assert_eq!(
+            None,
+            writes.atomic_save(Path::new("/repo/b.tmp"), now + Duration::from_secs(5))
+        );
+    }
+}
@@ -0,0 +1,88 @@
+//! Rules mapping path patterns to their own commands, so that one watcher can
+//! drive several build steps.
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::path::{Path, PathBuf};
+
+/// `GLOB=COMMAND`, where the command is run through the shell.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    pub glob: String,
+    pub command: String,
+}
+
+impl std::str::FromStr for Rule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some((glob, command)) if !glob.is_empty() && !command.trim().is_empty() => Ok(Self {
+                glob: glob.to_string(),
+                command: command.to_string(),
+            }),
+            _ => Err(format!("invalid rule: {:?} (expected GLOB=COMMAND)", s)),
+        }
+    }
+}
+
+/// Rules with their globs compiled, relative to the watched root.
+pub struct Rules {
+    rules: Vec<(Rule, Gitignore)>,
+}
+
+impl Rules {
+    pub fn new(root: &Path, rules: &[Rule]) -> Result<Self, ignore::Error> {
+        let rules = rules
+            .iter()
+            .map(|rule| {
+                let mut builder = GitignoreBuilder::new(root);
+                builder.add_line(None, &rule.glob)?;
+                Ok((rule.clone(), builder.build()?))
+            })
+            .collect::<Result<_, ignore::Error>>()?;
+        Ok(Self { rules })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Rule> {
+        self.rules.iter().map(|(rule, _)| rule)
+    }
+
+    /// Paths matched by each rule, in order, followed by the paths matched by none.
+    pub fn partition(&self, paths: &[PathBuf]) -> (Vec<Vec<PathBuf>>, Vec<PathBuf>) {
+        let mut matched = vec![Vec::new(); self.rules.len()];
+        let mut unmatched = Vec::new();
+        for path in paths {
+            let mut any = false;
+            for ((_, glob), matched) in self.rules.iter().zip(matched.iter_mut()) {
+                if glob
+                    .matched_path_or_any_parents(path, path.is_dir())
+                    .is_ignore()
+                {
+                    matched.push(path.clone());
+                    any = true;
+                }
+            }
+            if !any {
+                unmatched.push(path.clone());
+            }
+        }
+        (matched, unmatched)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Verify that rules split at the first `=`, and require both a glob and a command.
+    fn test_rule() {
+        let rule: Rule = "*.md=mdbook build --dest-dir=out".parse().unwrap();
+        assert_eq!("*.md", rule.glob);
+        assert_eq!("mdbook build --dest-dir=out", rule.command);
+
+        assert!("*.md".parse::<Rule>().is_err());
+        assert!("=make".parse::<Rule>().is_err());
+        assert!("*.md= ".parse::<Rule>().is_err());
+    }
+}
@@ -0,0 +1,132 @@
+//! Graceful shutdown on SIGINT or SIGTERM, or once standard input closes: the
+//! signal (or SIGTERM) is forwarded to running commands, which are killed should
+//! they outlast the grace period, and watching stops so that git-watch exits
+//! once they have.
+
+use crate::Event;
+use std::process::Child;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicUsize, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The last signal received, and how many have been.
+static SIGNAL: AtomicI32 = AtomicI32::new(0);
+static RECEIVED: AtomicUsize = AtomicUsize::new(0);
+
+/// Whether standard input has closed, if watched.
+static STDIN_CLOSED: AtomicBool = AtomicBool::new(false);
+
+/// Process ids of running commands.
+static CHILDREN: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+
+/// Forward signals to `child` until it is forgotten.
+pub fn track(child: &Child) {
+    CHILDREN.lock().unwrap().push(child.id());
+}
+
+/// Stop forwarding signals to `child`, which has exited.
+pub fn forget(child: &Child) {
+    CHILDREN.lock().unwrap().retain(|&id| id != child.id());
+}
+
+/// The signal by which shutdown was requested, if it was.
+pub fn signal() -> Option<i32> {
+    match RECEIVED.load(Ordering::SeqCst) {
+        0 => None,
+        _ => Some(SIGNAL.load(Ordering::SeqCst)),
+    }
+}
+
+pub fn requested() -> bool {
+    signal().is_some()
+}
+
+/// Name of `signal`, for messages.
+pub fn name(signal: i32) -> String {
+    #[cfg(unix)]
+    match signal {
+        libc::SIGINT => return "SIGINT".to_string(),
+        libc::SIGTERM => return "SIGTERM".to_string(),
+        _ => {}
+    }
+    format!("signal {}", signal)
+}
+
+#[cfg(unix)]
+fn forward(signal: libc::c_int) {
+    for &id in CHILDREN.lock().unwrap().iter() {
+        // SAFETY: only signals a command that has not yet been reaped
+        unsafe { libc::kill(id as libc::pid_t, signal) };
+    }
+}
+
+/// Handle SIGINT and SIGTERM (and the closing of standard input, if watched) by
+/// forwarding them to commands, killing those still running after `grace` (or
+/// upon a second signal), and stopping watching through `events`.
+#[cfg(unix)]
+pub fn install(events: Sender<Event>, grace: Duration) {
+    extern "C" fn handle(signal: libc::c_int) {
+        SIGNAL.store(signal, Ordering::SeqCst);
+        RECEIVED.fetch_add(1, Ordering::SeqCst);
+    }
+
+    // SAFETY: the handler only stores to atomics, which is async-signal-safe
+    unsafe {
+        libc::signal(libc::SIGINT, handle as *const () as libc::sighandler_t);
+        libc::signal(libc::SIGTERM, handle as *const () as libc::sighandler_t);
+    }
+
+    // signal handlers cannot wake the main thread, so watch for them here
+    std::thread::spawn(move || {
+        let mut handled = 0;
+        let mut stopping = false;
+        let mut deadline: Option<Instant> = None;
+        loop {
+            std::thread::sleep(Duration::from_millis(50));
+            let received = RECEIVED.load(Ordering::SeqCst);
+            let mut stop = None;
+            if received > handled {
+                let signal = SIGNAL.load(Ordering::SeqCst);
+                if !stopping {
+                    stop = Some((format!("Received {}", name(signal)), signal));
+                } else {
+                    log::info!("Received {} again; killing commands", name(signal));
+                    forward(libc::SIGKILL);
+                }
+                handled = received;
+            } else if !stopping && STDIN_CLOSED.load(Ordering::SeqCst) {
+                stop = Some(("Standard input closed".to_string(), libc::SIGTERM));
+            }
+            if let Some((reason, signal)) = stop {
+                log::info!("{}; stopping", reason);
+                forward(signal);
+                stopping = true;
+                deadline = Some(Instant::now() + grace);
+                let _ = events.send(Event::Quit);
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                log::debug!("Commands did not exit within grace period; killing");
+                forward(libc::SIGKILL);
+                deadline = None;
+            }
+        }
+    });
+}
+
+#[cfg(not(unix))]
+pub fn install(_events: Sender<Event>, _grace: Duration) {}
+
+/// Stop once standard input closes, as when the program that spawned git-watch
+/// exits, discarding anything read from it meanwhile.
+pub fn on_stdin_close(events: Sender<Event>) {
+    std::thread::spawn(move || {
+        let _ = std::io::copy(&mut std::io::stdin().lock(), &mut std::io::sink());
+        STDIN_CLOSED.store(true, Ordering::SeqCst);
+        // without signal handling, nothing else will notice
+        if cfg!(not(unix)) {
+            log::info!("Standard input closed; stopping");
+            let _ = events.send(Event::Quit);
+        }
+    });
+}
@@ -0,0 +1,125 @@
+//! Statistics of the session, summarized on exit to help tune `--settle`,
+//! `--age`, and `--size`.
+
+use crate::control;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+
+static EVENTS: AtomicUsize = AtomicUsize::new(0);
+static FILTERED: AtomicUsize = AtomicUsize::new(0);
+static CACHE_HITS: AtomicUsize = AtomicUsize::new(0);
+static CACHE_MISSES: AtomicUsize = AtomicUsize::new(0);
+static SETTLES: AtomicUsize = AtomicUsize::new(0);
+static SETTLE_MICROS: AtomicU64 = AtomicU64::new(0);
+
+/// Account for an event observed by the watcher.
+pub fn event() {
+    EVENTS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Account for a change that did not trigger the command.
+pub fn filtered() {
+    FILTERED.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Account for a lookup of the ignore cache.
+pub fn cache(hit: bool) {
+    match hit {
+        true => CACHE_HITS.fetch_add(1, Ordering::Relaxed),
+        false => CACHE_MISSES.fetch_add(1, Ordering::Relaxed),
+    };
+}
+
+/// Number of lookups of the ignore cache so far that hit, and that missed.
+pub fn cache_counts() -> (usize, usize) {
+    (
+        CACHE_HITS.load(Ordering::Relaxed),
+        CACHE_MISSES.load(Ordering::Relaxed),
+    )
+}
+
+/// Account for a batch of changes that took `elapsed` to settle.
+pub fn settled(elapsed: Duration) {
+    SETTLES.fetch_add(1, Ordering::Relaxed);
+    SETTLE_MICROS.fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+}
+
+/// Statistics of the session so far.
+#[derive(Debug)]
+struct Summary {
+    events: usize,
+    filtered: usize,
+    runs: usize,
+    failures: usize,
+    /// Mean time from the first change of a batch until it settled
+    settle: Option<Duration>,
+    cache_hits: usize,
+    cache_misses: usize,
+}
+
+impl Summary {
+    fn collect() -> Self {
+        let (runs, failures) = control::counts();
+        let settles = SETTLES.load(Ordering::Relaxed) as u64;
+        Self {
+            events: EVENTS.load(Ordering::Relaxed),
+            filtered: FILTERED.load(Ordering::Relaxed),
+            runs,
+            failures,
+            settle: (settles > 0)
+                .then(|| Duration::from_micros(SETTLE_MICROS.load(Ordering::Relaxed) / settles)),
+            cache_hits: CACHE_HITS.load(Ordering::Relaxed),
+            cache_misses: CACHE_MISSES.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Fraction of cache lookups that were hits, if there were any.
+    fn hit_rate(&self) -> Option<f64> {
+        let lookups = self.cache_hits + self.cache_misses;
+        (lookups > 0).then(|| self.cache_hits as f64 / lookups as f64)
+    }
+
+    fn json(&self) -> String {
+        let null = || "null".to_string();
+        format!(
+            "{{\"events\":{},\"filtered\":{},\"runs\":{},\"failures\":{},\"settle_secs\":{},\"cache_hits\":{},\"cache_misses\":{},\"cache_hit_rate\":{}}}\n",
+            self.events,
+            self.filtered,
+            self.runs,
+            self.failures,
+            self.settle.map_or_else(null, |d| format!("{:.3}", d.as_secs_f64())),
+            self.cache_hits,
+            self.cache_misses,
+            self.hit_rate().map_or_else(null, |r| format!("{:.3}", r)),
+        )
+    }
+}
+
+impl std::fmt::Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} events ({} filtered), {} runs ({} failed)",
+            self.events, self.filtered, self.runs, self.failures
+        )?;
+        if let Some(settle) = self.settle {
+            write!(f, ", settled in {:.2}s on average", settle.as_secs_f64())?;
+        }
+        if let Some(rate) = self.hit_rate() {
+            write!(f, ", cache hit rate {:.0}%", rate * 100.0)?;
+        }
+        Ok(())
+    }
+}
+
+/// Print the summary of the session, and write it as JSON to `json` if given.
+pub fn report(json: Option<&Path>) {
+    let summary = Summary::collect();
+    log::info!("Session: {}", summary);
+    if let Some(path) = json {
+        if let Err(e) = std::fs::write(path, summary.json()) {
+            log::warn!("unable to write statistics to {:?}: {}", path, e);
+        }
+    }
+}
@@ -0,0 +1,35 @@
+//! Changes reached through symbolic links to directories, which the watcher
+//! descends into: reported once, by their real path, or disregarded when they
+//! lie outside the repository.
+
+use std::path::{Path, PathBuf};
+
+/// The path by which to report a change to `path`, or `None` to disregard it.
+///
+/// A path reached through a link is reported by its real path if that is within
+/// one of `roots` as well, so that changes seen through several links trigger
+/// only once. Otherwise, it is kept as is when following links, and disregarded
+/// when not.
+pub fn resolve<'a>(
+    path: PathBuf,
+    mut roots: impl Iterator<Item = &'a Path> + Clone,
+    follow: bool,
+) -> Option<PathBuf> {
+    let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+        return Some(path);
+    };
+    if !roots.clone().any(|root| path.starts_with(root)) {
+        return Some(path);
+    }
+    match parent.canonicalize() {
+        Ok(real) if real != parent => {
+            let real = real.join(name);
+            if roots.any(|root| real.starts_with(root)) {
+                Some(real)
+            } else {
+                follow.then_some(path)
+            }
+        }
+        _ => Some(path),
+    }
+}
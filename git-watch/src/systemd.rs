@@ -0,0 +1,108 @@
+//! Service manager notifications, following systemd's `sd_notify` protocol:
+//! readiness once watching, and watchdog keep-alives while running.
+//!
+//! Keep-alives are sent from the event loop as it waits, rather than from a
+//! thread of their own, so that should the loop hang the watchdog fires.
+
+use std::process::{Child, ExitStatus};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Notification socket and watchdog interval, taken from the environment so
+/// that commands do not inherit them.
+static SERVICE: OnceLock<Service> = OnceLock::new();
+
+/// When the watchdog was last pinged.
+static PINGED: Mutex<Option<Instant>> = Mutex::new(None);
+
+#[derive(Debug, Default)]
+struct Service {
+    socket: Option<std::ffi::OsString>,
+    watchdog: Option<Duration>,
+}
+
+/// Take over `NOTIFY_SOCKET` and `WATCHDOG_USEC` from the environment.
+///
+/// Must be called before any threads are started.
+pub fn init() {
+    let socket = std::env::var_os("NOTIFY_SOCKET");
+    let watchdog_pid = std::env::var("WATCHDOG_PID").ok();
+    let watchdog = std::env::var("WATCHDOG_USEC")
+        .ok()
+        .and_then(|usec| usec.parse().ok())
+        .filter(|_| watchdog_pid.is_none_or(|pid| pid == std::process::id().to_string()))
+        .map(Duration::from_micros);
+    for var in ["NOTIFY_SOCKET", "WATCHDOG_USEC", "WATCHDOG_PID"] {
+        std::env::remove_var(var);
+    }
+    let _ = SERVICE.set(Service { socket, watchdog });
+}
+
+/// Send `state`, such as `READY=1`, if run by a service manager.
+#[cfg(unix)]
+pub fn notify(state: &str) {
+    use std::os::unix::net::UnixDatagram;
+
+    let Some(path) = SERVICE.get().and_then(|s| s.socket.as_ref()) else {
+        return;
+    };
+    let sent = UnixDatagram::unbound().and_then(|socket| {
+        #[cfg(target_os = "linux")]
+        if let Some(name) = path.to_str().and_then(|p| p.strip_prefix('@')) {
+            use std::os::linux::net::SocketAddrExt;
+            let addr = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+            return socket.send_to_addr(state.as_bytes(), &addr);
+        }
+        socket.send_to(state.as_bytes(), path)
+    });
+    if let Err(e) = sent {
+        log::warn!("unable to notify service manager: {}", e);
+    }
+}
+
+#[cfg(not(unix))]
+pub fn notify(_state: &str) {}
+
+/// Report readiness.
+pub fn ready() {
+    notify("READY=1");
+    if let Some(tick) = tick() {
+        log::debug!("Pinging watchdog every {:?}", tick);
+        keep_alive();
+    }
+}
+
+/// How often to ping the watchdog, if any: twice within its timeout.
+pub fn tick() -> Option<Duration> {
+    SERVICE
+        .get()
+        .and_then(|s| s.watchdog)
+        .map(|interval| interval / 2)
+}
+
+/// Ping the watchdog, if any, unless it was pinged less than a tick ago.
+pub fn keep_alive() {
+    let Some(tick) = tick() else {
+        return;
+    };
+    let mut pinged = PINGED.lock().unwrap();
+    if pinged.is_none_or(|pinged| pinged.elapsed() >= tick) {
+        notify("WATCHDOG=1");
+        *pinged = Some(Instant::now());
+    }
+}
+
+/// Wait for `child` to exit, pinging the watchdog, if any, meanwhile, as a
+/// command that runs long is not git-watch hanging.
+pub fn wait(child: &mut Child) -> std::io::Result<ExitStatus> {
+    let Some(tick) = tick() else {
+        return child.wait();
+    };
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+        keep_alive();
+        std::thread::sleep(tick.min(Duration::from_millis(100)));
+    }
+}
@@ -0,0 +1,184 @@
+//! Named tasks defined in a `.git-watch.toml` at the root, so that a project's
+//! watch setup can be committed alongside its code:
+//!
+//! ```toml
+//! [tasks.test]
+//! command = ["cargo", "test"]
+//! include = ["*.rs", "Cargo.toml"]
+//! settle = 0.5
+//! restart = true
+//! ```
+//!
+//! A string `command` is run through the shell, as are the commands of `rules`
+//! (each `"GLOB=COMMAND"`). Settings given on the command
+//! line take precedence over those of the task.
+
+use crate::{Config, Edge, EventFilter, OnError};
+use anyhow::Result;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, ValueEnum};
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+pub const FILENAME: &str = ".git-watch.toml";
+
+/// Look up task `name` in the tasks file under `root`, if there is one.
+pub fn find(root: &Path, name: &str) -> Result<Option<toml::Table>> {
+    let path = root.join(FILENAME);
+    if !path.is_file() {
+        return Ok(None);
+    }
+
+    let content = std::fs::read_to_string(&path)?;
+    let table: toml::Table = content
+        .parse()
+        .map_err(|e| anyhow::anyhow!("unable to parse {:?}: {}", path, e))?;
+
+    let task = table
+        .get("tasks")
+        .and_then(|tasks| tasks.as_table())
+        .and_then(|tasks| tasks.get(name));
+    match task {
+        None => Ok(None),
+        Some(toml::Value::Table(task)) => Ok(Some(task.clone())),
+        Some(_) => anyhow::bail!("task {:?} in {:?} is not a table", name, path),
+    }
+}
+
+/// Apply the settings of `task` to `config`, except those given on the command line.
+pub fn apply(config: &mut Config, task: &toml::Table, matches: &ArgMatches) -> Result<()> {
+    let explicit = |id: &str| {
+        matches.ids().any(|i| i == id) && matches.value_source(id) == Some(ValueSource::CommandLine)
+    };
+
+    for (key, value) in task.iter() {
+        let id = match key.replace('-', "_") {
+            id if id == "rules" => "rule".to_string(),
+            id => id,
+        };
+        // the command named the task, so the task's command always replaces it
+        if id != "command" && explicit(&id) {
+            continue;
+        }
+
+        match id.as_str() {
+            "command" => match value {
+                toml::Value::String(script) => {
+                    config.command = vec![script.clone()];
+                    config.shell = true;
+                }
+                _ => config.command = strings(key, value)?,
+            },
+            "rule" => {
+                config.rule = strings(key, value)?
+                    .iter()
+                    .map(|rule| rule.parse().map_err(anyhow::Error::msg))
+                    .collect::<Result<_>>()?
+            }
+            "exec" => config.exec = strings(key, value)?,
+            "workdir" => {
+                config.workdir = Some(string(key, value)?.parse().map_err(anyhow::Error::msg)?)
+            }
+            "shell" => config.shell = boolean(key, value)?,
+            "env" => {
+                config.env = strings(key, value)?
+                    .iter()
+                    .map(|var| var.parse().map_err(anyhow::Error::msg))
+                    .collect::<Result<_>>()?
+            }
+            "clean_env" => config.clean_env = boolean(key, value)?,
+            "nice" => config.nice = Some(integer(key, value, -20..=19)? as i32),
+            "include" => config.include = strings(key, value)?,
+            "ext" => config.ext = strings(key, value)?,
+            "exclude" => config.exclude = strings(key, value)?,
+            "events" => {
+                config.events = strings(key, value)?
+                    .iter()
+                    .map(|event| EventFilter::from_str(event, false).map_err(anyhow::Error::msg))
+                    .collect::<Result<_>>()?
+            }
+            "settle" => config.settle = float(key, value)?,
+            "settle_for" => {
+                config.settle_for = strings(key, value)?
+                    .iter()
+                    .map(|settle| settle.parse().map_err(anyhow::Error::msg))
+                    .collect::<Result<_>>()?
+            }
+            "delay" => config.delay = float(key, value)?,
+            "edge" => {
+                config.edge =
+                    Edge::from_str(string(key, value)?, false).map_err(anyhow::Error::msg)?
+            }
+            "hash_check" => config.hash_check = boolean(key, value)?,
+            "diff_gate" => config.diff_gate = boolean(key, value)?,
+            "ignore_self" => config.ignore_self = Some(float(key, value)?),
+            "restart" => config.restart = boolean(key, value)?,
+            "grace" => config.grace = float(key, value)?,
+            "throttle" => config.throttle = Some(float(key, value)?),
+            "cooldown" => config.cooldown = Some(float(key, value)?),
+            "backoff" => config.backoff = Some(float(key, value)?),
+            "initial" => config.initial = boolean(key, value)?,
+            "on_error" => {
+                config.on_error = string(key, value)?
+                    .parse::<OnError>()
+                    .map_err(anyhow::Error::msg)?
+            }
+            "append_paths" => config.append_paths = boolean(key, value)?,
+            "max_args" => config.max_args = Some(integer(key, value, 1..=i64::MAX)? as usize),
+            "on_success" => config.on_success = Some(string(key, value)?.to_string()),
+            "on_failure" => config.on_failure = Some(string(key, value)?.to_string()),
+            _ => anyhow::bail!("unknown task setting {:?}", key),
+        }
+    }
+
+    anyhow::ensure!(!config.command.is_empty(), "task has an empty command");
+    Ok(())
+}
+
+fn string<'a>(key: &str, value: &'a toml::Value) -> Result<&'a str> {
+    value
+        .as_str()
+        .ok_or_else(|| anyhow::anyhow!("task setting {:?} must be a string", key))
+}
+
+/// A list of strings, or a single string as a list of one.
+fn strings(key: &str, value: &toml::Value) -> Result<Vec<String>> {
+    if let Some(s) = value.as_str() {
+        return Ok(vec![s.to_string()]);
+    }
+    value
+        .as_array()
+        .and_then(|items| {
+            items
+                .iter()
+                .map(|item| item.as_str().map(str::to_string))
+                .collect()
+        })
+        .ok_or_else(|| anyhow::anyhow!("task setting {:?} must be a list of strings", key))
+}
+
+fn boolean(key: &str, value: &toml::Value) -> Result<bool> {
+    value
+        .as_bool()
+        .ok_or_else(|| anyhow::anyhow!("task setting {:?} must be a boolean", key))
+}
+
+fn integer(key: &str, value: &toml::Value, range: RangeInclusive<i64>) -> Result<i64> {
+    match value.as_integer() {
+        Some(i) if range.contains(&i) => Ok(i),
+        _ => anyhow::bail!(
+            "task setting {:?} must be an integer from {} to {}",
+            key,
+            range.start(),
+            range.end()
+        ),
+    }
+}
+
+fn float(key: &str, value: &toml::Value) -> Result<f32> {
+    match value {
+        toml::Value::Integer(i) if *i >= 0 => Ok(*i as f32),
+        toml::Value::Float(f) if *f >= 0.0 => Ok(*f as f32),
+        _ => anyhow::bail!("task setting {:?} must be a non-negative number", key),
+    }
+}
@@ -0,0 +1,346 @@
+//! Full-screen dashboard of recent changes, the history of runs, and the
+//! output of the latest run, redrawn as they happen.
+
+use crate::{pause, Job};
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime};
+
+/// Number of changes, runs, and output lines kept.
+const CHANGES: usize = 200;
+const RUNS: usize = 200;
+const OUTPUT: usize = 2000;
+
+/// Enter the alternate screen and hide the cursor, and the reverse.
+const ENTER: &str = "\x1b[?1049h\x1b[?25l";
+const LEAVE: &str = "\x1b[?25h\x1b[?1049l";
+
+const REVERSE: &str = "\x1b[7m";
+const GREEN: &str = "\x1b[32m";
+const RED: &str = "\x1b[31m";
+const RESET: &str = "\x1b[0m";
+
+static STATE: Mutex<Option<State>> = Mutex::new(None);
+
+#[derive(Default)]
+struct State {
+    changes: VecDeque<String>,
+    runs: VecDeque<Run>,
+    output: VecDeque<String>,
+    /// Minimum interval between runs while failing, with `--backoff`
+    backoff: Option<Duration>,
+    dirty: bool,
+}
+
+struct Run {
+    started: SystemTime,
+    command: String,
+    /// `None` while running
+    outcome: Option<(Option<ExitStatus>, Duration)>,
+}
+
+fn push<T>(queue: &mut VecDeque<T>, max: usize, item: T) {
+    if queue.len() == max {
+        queue.pop_front();
+    }
+    queue.push_back(item);
+}
+
+/// Update the dashboard, if shown.
+fn update(f: impl FnOnce(&mut State)) {
+    if let Some(state) = STATE.lock().unwrap().as_mut() {
+        f(state);
+        state.dirty = true;
+    }
+}
+
+/// The dashboard, shown until dropped.
+pub struct Tui {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Tui {
+    pub fn start(root: &Path, toggle_file: Option<PathBuf>) -> std::io::Result<Self> {
+        *STATE.lock().unwrap() = Some(State {
+            dirty: true,
+            ..Default::default()
+        });
+        let mut stdout = std::io::stdout();
+        stdout.write_all(ENTER.as_bytes())?;
+        stdout.flush()?;
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let stop = Arc::clone(&stop);
+            let root = root.to_path_buf();
+            let mut paused = false;
+            std::thread::spawn(move || {
+                while !stop.load(Ordering::SeqCst) {
+                    let now_paused = pause::is_paused(toggle_file.as_deref());
+                    let mut guard = STATE.lock().unwrap();
+                    if let Some(state) = guard.as_mut().filter(|s| s.dirty || paused != now_paused)
+                    {
+                        state.dirty = false;
+                        paused = now_paused;
+                        let (width, height) = size();
+                        let screen = render(state, &root, paused, width, height);
+                        drop(guard);
+                        let mut stdout = std::io::stdout().lock();
+                        let _ = stdout.write_all(screen.as_bytes());
+                        let _ = stdout.flush();
+                    } else {
+                        drop(guard);
+                    }
+                    std::thread::sleep(Duration::from_millis(100));
+                }
+            })
+        };
+        Ok(Self {
+            stop,
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for Tui {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        *STATE.lock().unwrap() = None;
+        print!("{}", LEAVE);
+        let _ = std::io::stdout().flush();
+    }
+}
+
+/// Terminal width and height, falling back to 80x24.
+fn size() -> (usize, usize) {
+    #[cfg(unix)]
+    {
+        // SAFETY: TIOCGWINSZ only writes to the provided winsize
+        let mut ws: libc::winsize = unsafe { std::mem::zeroed() };
+        if unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut ws) } == 0
+            && ws.ws_col > 0
+            && ws.ws_row > 0
+        {
+            return (ws.ws_col as usize, ws.ws_row as usize);
+        }
+    }
+    (80, 24)
+}
+
+/// Whether the dashboard is shown, and so captures the output of commands.
+pub fn enabled() -> bool {
+    STATE.lock().unwrap().is_some()
+}
+
+/// Record an actionable change.
+pub fn change(path: &Path, event: &str) {
+    let line = format!(
+        "{} {:<11} {}",
+        clock(SystemTime::now()),
+        event,
+        path.display()
+    );
+    update(|state| push(&mut state.changes, CHANGES, line));
+}
+
+/// Record the start of `job`, whose output replaces that of earlier runs.
+pub fn start(job: &Job) {
+    let run = Run {
+        started: SystemTime::now(),
+        command: job.command.join(" "),
+        outcome: None,
+    };
+    update(|state| {
+        if state.runs.iter().all(|run| run.outcome.is_some()) {
+            state.output.clear();
+        }
+        push(&mut state.runs, RUNS, run);
+    });
+}
+
+/// Record how `command` ended; `status` is `None` if it was terminated.
+pub fn finish(command: &[String], status: Option<ExitStatus>, elapsed: Duration) {
+    let command = command.join(" ");
+    update(|state| {
+        let run = state
+            .runs
+            .iter_mut()
+            .find(|run| run.outcome.is_none() && run.command == command);
+        if let Some(run) = run {
+            run.outcome = Some((status, elapsed));
+        }
+    });
+}
+
+/// Record the interval runs are held back by after repeated failures, if any.
+pub fn backoff(delay: Option<Duration>) {
+    update(|state| state.backoff = delay);
+}
+
+/// Stream the lines of `stream` into the dashboard.
+pub fn forward(stream: impl Read + Send + 'static) {
+    std::thread::spawn(move || {
+        let mut reader = BufReader::new(stream);
+        let mut line = Vec::new();
+        loop {
+            // even a failed read may have yielded the end of a line
+            let read = reader.read_until(b'\n', &mut line);
+            if !line.is_empty() {
+                let text = printable(&String::from_utf8_lossy(&line));
+                update(|state| push(&mut state.output, OUTPUT, text));
+                line.clear();
+            }
+            if !matches!(read, Ok(n) if n > 0) {
+                break;
+            }
+        }
+    });
+}
+
+/// Log messages, shown with the output while the dashboard is, else on stderr.
+pub struct LogWriter;
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if enabled() {
+            for line in String::from_utf8_lossy(buf).lines() {
+                let line = printable(line);
+                update(|state| push(&mut state.output, OUTPUT, line));
+            }
+            Ok(buf.len())
+        } else {
+            std::io::stderr().write(buf)
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        std::io::stderr().flush()
+    }
+}
+
+/// `line` without escape sequences or other control characters, and with only
+/// the text after the last carriage return, as a terminal would leave it.
+fn printable(line: &str) -> String {
+    let line = line.trim_end_matches(['\n', '\r']);
+    let line = line.rsplit('\r').next().unwrap_or(line);
+    let mut text = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            // CSI sequences end with a byte in '@'..='~'
+            '\x1b' => {
+                if chars.next() == Some('[') {
+                    for c in chars.by_ref() {
+                        if ('@'..='~').contains(&c) {
+                            break;
+                        }
+                    }
+                }
+            }
+            '\t' => text.push_str("    "),
+            c if c.is_control() => {}
+            c => text.push(c),
+        }
+    }
+    text
+}
+
+/// `HH:MM:SS` in UTC.
+fn clock(time: SystemTime) -> String {
+    let stamp = humantime::format_rfc3339_seconds(time).to_string();
+    stamp[11..19].to_string()
+}
+
+fn truncate(line: &str, width: usize) -> String {
+    line.chars().take(width).collect()
+}
+
+/// Draw the whole screen: a status line, then the most recent changes, runs,
+/// and output, each under a heading.
+fn render(state: &State, root: &Path, paused: bool, width: usize, height: usize) -> String {
+    let last = state.runs.iter().rev().find_map(|run| run.outcome);
+    let status = match last {
+        None => "no runs yet".to_string(),
+        Some((Some(status), _)) if status.success() => "last run passed".to_string(),
+        Some((Some(status), _)) => format!("last run failed ({})", status),
+        Some((None, _)) => "last run terminated".to_string(),
+    };
+    let status = match state.backoff {
+        Some(delay) => format!("{}, backing off {}s", status, delay.as_secs()),
+        None => status,
+    };
+    let header = format!(
+        " git-watch: {} | {} | {} ",
+        root.display(),
+        if paused { "paused" } else { "watching" },
+        status
+    );
+
+    let mut lines = vec![format!(
+        "{}{:<width$}{}",
+        REVERSE,
+        truncate(&header, width),
+        RESET,
+        width = width
+    )];
+
+    // one line for the status, one heading per pane
+    let body = height.saturating_sub(4);
+    let changes = (body / 4).max(1);
+    let runs = (body / 4).max(1);
+    let output = body.saturating_sub(changes + runs);
+
+    lines.push(truncate("-- Changes", width));
+    let skip = state.changes.len().saturating_sub(changes);
+    for change in state.changes.iter().skip(skip) {
+        lines.push(truncate(change, width));
+    }
+    lines.resize(2 + changes, String::new());
+
+    lines.push(truncate("-- Runs", width));
+    let skip = state.runs.len().saturating_sub(runs);
+    for run in state.runs.iter().skip(skip) {
+        let (color, outcome) = match run.outcome {
+            None => ("", "running".to_string()),
+            Some((Some(status), elapsed)) if status.success() => {
+                (GREEN, format!("passed {:.2}s", elapsed.as_secs_f32()))
+            }
+            Some((Some(status), elapsed)) => (
+                RED,
+                format!(
+                    "failed {:.2}s ({})",
+                    elapsed.as_secs_f32(),
+                    status
+                        .code()
+                        .map_or("signal".to_string(), |c| c.to_string())
+                ),
+            ),
+            Some((None, elapsed)) => ("", format!("terminated {:.2}s", elapsed.as_secs_f32())),
+        };
+        let line = format!("{} {:<20} {}", clock(run.started), outcome, run.command);
+        lines.push(format!("{}{}{}", color, truncate(&line, width), RESET));
+    }
+    lines.resize(3 + changes + runs, String::new());
+
+    lines.push(truncate("-- Output", width));
+    let skip = state.output.len().saturating_sub(output);
+    for line in state.output.iter().skip(skip) {
+        lines.push(truncate(line, width));
+    }
+    lines.truncate(height);
+
+    // redraw in place, clearing what remains of each line and below
+    let mut screen = String::from("\x1b[H");
+    screen.push_str(&lines.join("\x1b[K\r\n"));
+    screen.push_str("\x1b[K\x1b[J");
+    screen
+}
@@ -0,0 +1,111 @@
+//! Posting a JSON summary of each trigger and its outcome to an HTTP endpoint,
+//! so that dashboards and chat bots can follow along. Requests are made by
+//! `curl`, in the background, so that a slow endpoint never delays a run.
+
+use crate::logfile::json_string;
+use std::io::Write;
+use std::path::Path;
+use std::process::{ExitStatus, Stdio};
+
+/// JSON payload describing a trigger, whose `changes` are each path and how it
+/// changed (`created`, `modified`, `removed`, or `renamed`); `status` is `None`
+/// if not yet known, as when restarting a long-running command.
+fn payload(
+    root: &Path,
+    changes: &[(&Path, &str)],
+    event: Option<&str>,
+    status: Option<ExitStatus>,
+) -> String {
+    let paths: Vec<String> = changes
+        .iter()
+        .map(|(path, _)| json_string(&path.to_string_lossy()))
+        .collect();
+    let changes: Vec<String> = changes
+        .iter()
+        .map(|(path, kind)| {
+            format!(
+                "{{\"path\":{},\"kind\":{}}}",
+                json_string(&path.to_string_lossy()),
+                json_string(kind)
+            )
+        })
+        .collect();
+    format!(
+        "{{\"root\":{},\"event\":{},\"paths\":[{}],\"changes\":[{}],\"success\":{},\"code\":{}}}",
+        json_string(&root.to_string_lossy()),
+        json_string(event.unwrap_or("startup")),
+        paths.join(","),
+        changes.join(","),
+        status.map_or("null".to_string(), |s| s.success().to_string()),
+        status
+            .and_then(|s| s.code())
+            .map_or("null".to_string(), |code| code.to_string())
+    )
+}
+
+/// POST the summary of a trigger to `url`.
+pub fn post(
+    url: &str,
+    root: &Path,
+    changes: &[(&Path, &str)],
+    event: Option<&str>,
+    status: Option<ExitStatus>,
+) {
+    let body = payload(root, changes, event, status);
+    let curl = std::process::Command::new("curl")
+        .args(["--silent", "--show-error", "--fail", "--max-time", "10"])
+        .args(["--header", "Content-Type: application/json"])
+        .args(["--data-binary", "@-", url])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn();
+    let mut curl = match curl {
+        Ok(curl) => curl,
+        Err(e) => {
+            log::warn!("unable to run curl for webhook: {}", e);
+            return;
+        }
+    };
+    if let Some(mut stdin) = curl.stdin.take() {
+        let _ = stdin.write_all(body.as_bytes());
+    }
+    let url = url.to_string();
+    std::thread::spawn(move || match curl.wait_with_output() {
+        Ok(output) if output.status.success() => log::debug!("Posted to {}", url),
+        Ok(output) => log::warn!(
+            "webhook {} failed: {}",
+            url,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ),
+        Err(e) => log::warn!("webhook {} failed: {}", url, e),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Verify that the payload lists each changed path with how it changed.
+    fn test_payload() {
+        let changes = [
+            (Path::new("/repo/a.rs"), "created"),
+            (Path::new("/repo/\"b\".rs"), "removed"),
+        ];
+        assert_eq!(
+            concat!(
+                r#"{"root":"/repo","event":"create","#,
+                r#""paths":["/repo/a.rs","/repo/\"b\".rs"],"#,
+                r#""changes":[{"path":"/repo/a.rs","kind":"created"},"#,
+                r#"{"path":"/repo/\"b\".rs","kind":"removed"}],"#,
+                r#""success":null,"code":null}"#
+            ),
+            payload(Path::new("/repo"), &changes, Some("create"), None)
+        );
+        assert_eq!(
+            r#"{"root":"/repo","event":"startup","paths":[],"changes":[],"success":null,"code":null}"#,
+            payload(Path::new("/repo"), &[], None, None)
+        );
+    }
+}